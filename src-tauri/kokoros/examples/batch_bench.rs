@@ -0,0 +1,87 @@
+//! Compares chunk-by-chunk and batched inference of a multi-chunk text.
+//!
+//! ```sh
+//! cargo run --release --example batch_bench -- <model.onnx> <voices.bin> [runs]
+//! ```
+
+use kokoros::tts::koko::TTSKoko;
+use std::time::{Duration, Instant};
+
+const SAMPLE_RATE: f64 = 24000.0;
+
+const TEXT: &str = "The old lighthouse stood at the edge of the cliff for more than a \
+    hundred years. Every night its beam swept across the water, warning ships away from \
+    the rocks below. The keeper climbed the spiral stairs at dusk to light the lamp, and \
+    again at dawn to put it out. When the storms came in from the west, the whole tower \
+    shook, but it never fell. Sailors told stories about the light that never failed, \
+    and children in the village below grew up watching it turn. One winter the keeper \
+    grew too old to climb the stairs, and the lamp was replaced by an electric one that \
+    needed no one at all. The tower still stands, and the light still turns, but the \
+    stories are told less often now.";
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: batch_bench <model.onnx> <voices.bin> [runs]");
+        std::process::exit(1);
+    }
+    let runs: usize = args.get(3).and_then(|runs| runs.parse().ok()).unwrap_or(5);
+
+    let tts = TTSKoko::new(&args[1], &args[2]).await.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let chunks = tts.inspect_speech(TEXT, "en-us", "af_heart", 1.0, None);
+    println!(
+        "{} chunks, {} runs each",
+        chunks.map_or(0, |inspection| inspection.chunks.len()),
+        runs
+    );
+
+    // Warm up both paths so session setup isn't measured. A batch the model
+    // can't run falls back to one chunk at a time and logs a warning, so check
+    // the log before trusting the batched numbers.
+    let lengths: Vec<usize> = [false, true]
+        .into_iter()
+        .map(|batching| {
+            tts.set_batching(batching);
+            synthesize(&tts)
+        })
+        .collect();
+    if lengths[0] != lengths[1] {
+        println!(
+            "batched audio is {} samples long, sequential {}",
+            lengths[1], lengths[0]
+        );
+    }
+
+    let mut results = Vec::new();
+    for (name, batching) in [("sequential", false), ("batched", true)] {
+        tts.set_batching(batching);
+        let mut elapsed = Duration::ZERO;
+        let mut samples = 0;
+        for _ in 0..runs {
+            let start = Instant::now();
+            samples = synthesize(&tts);
+            elapsed += start.elapsed();
+        }
+        let per_run = elapsed.as_secs_f64() / runs as f64;
+        let audio_secs = samples as f64 / SAMPLE_RATE;
+        println!(
+            "{:>10}: {:.3} s per run, {:.1} s of audio, RTF {:.3}",
+            name,
+            per_run,
+            audio_secs,
+            per_run / audio_secs
+        );
+        results.push(per_run);
+    }
+    println!("speedup: {:.2}x", results[0] / results[1]);
+}
+
+fn synthesize(tts: &TTSKoko) -> usize {
+    tts.tts_raw_audio(TEXT, "en-us", "af_heart", 1.0, None, None, None, None)
+        .expect("synthesis failed")
+        .len()
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::io;
+
+/// Errors of the TTS pipeline, from loading the model to encoding its audio.
+///
+/// [`KokoError::code`] is stable for callers to switch on; the messages are for
+/// people and may change.
+#[derive(Debug)]
+pub enum KokoError {
+    /// The model, its config or its voices couldn't be loaded, or the model
+    /// isn't one this crate can run
+    ModelLoad(String),
+    /// No installed voice has this name
+    VoiceNotFound(String),
+    /// A voice pack or style vector doesn't have the shape the model takes
+    InvalidStyleShape(String),
+    /// A voice name or blend spec is malformed, or the voice can't be saved,
+    /// replaced or removed
+    InvalidVoice(String),
+    Phonemization(String),
+    Inference(String),
+    /// Audio couldn't be written as WAV or MP3
+    Encoding(String),
+    Download(String),
+    Io(io::Error),
+}
+
+impl KokoError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            KokoError::ModelLoad(_) => "model_load",
+            KokoError::VoiceNotFound(_) => "voice_not_found",
+            KokoError::InvalidStyleShape(_) => "invalid_style_shape",
+            KokoError::InvalidVoice(_) => "invalid_voice",
+            KokoError::Phonemization(_) => "phonemization",
+            KokoError::Inference(_) => "inference",
+            KokoError::Encoding(_) => "encoding",
+            KokoError::Download(_) => "download",
+            KokoError::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for KokoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KokoError::VoiceNotFound(name) => write!(f, "voice '{}' not found", name),
+            KokoError::Io(e) => write!(f, "I/O error: {}", e),
+            KokoError::ModelLoad(message)
+            | KokoError::InvalidStyleShape(message)
+            | KokoError::InvalidVoice(message)
+            | KokoError::Phonemization(message)
+            | KokoError::Inference(message)
+            | KokoError::Encoding(message)
+            | KokoError::Download(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for KokoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KokoError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for KokoError {
+    fn from(e: io::Error) -> Self {
+        KokoError::Io(e)
+    }
+}
+
+/// ONNX Runtime errors while a session is running; loading maps its own.
+impl From<ort::Error> for KokoError {
+    fn from(e: ort::Error) -> Self {
+        KokoError::Inference(e.to_string())
+    }
+}
+
+impl From<hound::Error> for KokoError {
+    fn from(e: hound::Error) -> Self {
+        match e {
+            hound::Error::IoError(e) => KokoError::Io(e),
+            e => KokoError::Encoding(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_and_messages() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<KokoError>();
+
+        let e = KokoError::VoiceNotFound("af_nobody".into());
+        assert_eq!(e.code(), "voice_not_found");
+        assert_eq!(e.to_string(), "voice 'af_nobody' not found");
+
+        let e = KokoError::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert_eq!(e.code(), "io");
+        assert!(std::error::Error::source(&e).is_some());
+
+        // Boxes into the `Send + Sync` error callers pass across threads
+        let boxed: Box<dyn std::error::Error + Send + Sync> =
+            KokoError::Phonemization("espeak failed".into()).into();
+        assert_eq!(boxed.to_string(), "espeak failed");
+    }
+}
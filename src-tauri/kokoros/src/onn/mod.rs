@@ -1,2 +1,4 @@
+pub mod model_schema;
 pub mod ort_base;
 pub mod ort_koko;
+pub mod session_config;
@@ -0,0 +1,311 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use ort::session::{Session, SessionInputValue};
+use ort::tensor::TensorElementType;
+use ort::value::{Tensor, Value};
+
+use crate::error::KokoError;
+
+/// Names the token ids input goes by, most common first
+const TOKEN_INPUTS: &[&str] = &["tokens", "input_ids"];
+const STYLE_INPUTS: &[&str] = &["style"];
+const SPEED_INPUTS: &[&str] = &["speed"];
+const AUDIO_OUTPUTS: &[&str] = &["audio", "waveform"];
+const DURATION_OUTPUTS: &[&str] = &["durations"];
+
+/// A session input and the name it's fed to
+pub type NamedInput<'a> = (Cow<'a, str>, SessionInputValue<'a>);
+
+/// Name, element type and shape (`-1` for dynamic dimensions) of a model input
+/// or output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorSpec {
+    pub name: String,
+    pub ty: Option<TensorElementType>,
+    pub shape: Vec<i64>,
+}
+
+impl fmt::Display for TensorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ty {
+            Some(ty) => write!(f, "{}: {:?} {:?}", self.name, ty, self.shape),
+            None => write!(f, "{}: not a tensor", self.name),
+        }
+    }
+}
+
+/// Element type the model takes `speed` as. Exports with an int32 speed only
+/// take whole speeds, so the requested speed is rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedType {
+    Float32,
+    Int32,
+}
+
+/// Which inputs and outputs a Kokoro export uses, detected from the session so
+/// the variants community exports come in can all be run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSchema {
+    pub tokens: String,
+    pub style: String,
+    pub speed: String,
+    pub speed_type: SpeedType,
+    /// Whether `speed` takes one value per batch item rather than one for the
+    /// whole batch
+    pub per_item_speed: bool,
+    pub audio: String,
+    /// Per-token durations in frames, for exports that provide them
+    pub durations: Option<String>,
+}
+
+impl ModelSchema {
+    pub fn from_session(session: &Session) -> Result<Self, String> {
+        let spec = |name: &str, ty: &ort::value::ValueType| TensorSpec {
+            name: name.to_string(),
+            ty: ty.tensor_type(),
+            shape: ty
+                .tensor_shape()
+                .map(|shape| shape.to_vec())
+                .unwrap_or_default(),
+        };
+        let inputs: Vec<TensorSpec> = session
+            .inputs
+            .iter()
+            .map(|input| spec(&input.name, &input.input_type))
+            .collect();
+        let outputs: Vec<TensorSpec> = session
+            .outputs
+            .iter()
+            .map(|output| spec(&output.name, &output.output_type))
+            .collect();
+        Self::detect(&inputs, &outputs)
+    }
+
+    /// Matches the model's inputs and outputs against the known variants. The
+    /// error lists what the model has, for models that match none of them.
+    pub fn detect(inputs: &[TensorSpec], outputs: &[TensorSpec]) -> Result<Self, String> {
+        let describe = || {
+            let list = |specs: &[TensorSpec]| {
+                specs
+                    .iter()
+                    .map(|spec| spec.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            format!(
+                "the model has inputs [{}] and outputs [{}]",
+                list(inputs),
+                list(outputs)
+            )
+        };
+        let find =
+            |specs: &[TensorSpec], names: &[&str], types: &[TensorElementType], what: &str| {
+                let spec = specs
+                    .iter()
+                    .find(|spec| names.contains(&spec.name.as_str()))
+                    .ok_or_else(|| {
+                        format!(
+                            "Unsupported model: no {} ({}), {}",
+                            what,
+                            names.join(" or "),
+                            describe()
+                        )
+                    })?;
+                match spec.ty {
+                    Some(ty) if types.contains(&ty) => Ok(spec.clone()),
+                    _ => Err(format!(
+                        "Unsupported model: {} has type {:?}, expected {:?}",
+                        spec.name, spec.ty, types
+                    )),
+                }
+            };
+
+        let tokens = find(
+            inputs,
+            TOKEN_INPUTS,
+            &[TensorElementType::Int64],
+            "token ids input",
+        )?;
+        let style = find(
+            inputs,
+            STYLE_INPUTS,
+            &[TensorElementType::Float32],
+            "style input",
+        )?;
+        let speed = find(
+            inputs,
+            SPEED_INPUTS,
+            &[TensorElementType::Float32, TensorElementType::Int32],
+            "speed input",
+        )?;
+        let audio = find(
+            outputs,
+            AUDIO_OUTPUTS,
+            &[TensorElementType::Float32],
+            "audio output",
+        )?;
+        let durations = outputs
+            .iter()
+            .find(|spec| DURATION_OUTPUTS.contains(&spec.name.as_str()));
+
+        let known = [TOKEN_INPUTS, STYLE_INPUTS, SPEED_INPUTS].concat();
+        if let Some(extra) = inputs
+            .iter()
+            .find(|spec| !known.contains(&spec.name.as_str()))
+        {
+            return Err(format!(
+                "Unsupported model: unknown input {}, {}",
+                extra.name,
+                describe()
+            ));
+        }
+
+        let speed_type = if speed.ty == Some(TensorElementType::Int32) {
+            SpeedType::Int32
+        } else {
+            SpeedType::Float32
+        };
+        let per_item_speed = speed.shape.first().is_some_and(|&dim| dim != 1);
+        Ok(ModelSchema {
+            tokens: tokens.name,
+            style: style.name,
+            speed: speed.name,
+            speed_type,
+            per_item_speed,
+            audio: audio.name,
+            durations: durations.map(|spec| spec.name.clone()),
+        })
+    }
+
+    /// Session inputs for `tokens` and `styles` (one row per batch item) and the
+    /// given speeds.
+    pub fn inputs(
+        &self,
+        tokens: ([usize; 2], Vec<i64>),
+        styles: ([usize; 2], Vec<f32>),
+        speeds: Vec<f32>,
+    ) -> Result<Vec<NamedInput<'_>>, KokoError> {
+        let speed: Value = match self.speed_type {
+            SpeedType::Float32 => Tensor::from_array(([speeds.len()], speeds))?.into(),
+            SpeedType::Int32 => {
+                let speeds: Vec<i32> = speeds
+                    .iter()
+                    .map(|&speed| (speed.round() as i32).max(1))
+                    .collect();
+                Tensor::from_array(([speeds.len()], speeds))?.into()
+            }
+        };
+        Ok(vec![
+            (
+                Cow::Borrowed(self.tokens.as_str()),
+                SessionInputValue::Owned(Tensor::from_array(tokens)?.into()),
+            ),
+            (
+                Cow::Borrowed(self.style.as_str()),
+                SessionInputValue::Owned(Tensor::from_array(styles)?.into()),
+            ),
+            (
+                Cow::Borrowed(self.speed.as_str()),
+                SessionInputValue::Owned(speed),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(name: &str, ty: TensorElementType, shape: &[i64]) -> TensorSpec {
+        TensorSpec {
+            name: name.to_string(),
+            ty: Some(ty),
+            shape: shape.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_detect_variants() {
+        use TensorElementType::*;
+
+        // kokoro-onnx v1.0
+        let schema = ModelSchema::detect(
+            &[
+                spec("tokens", Int64, &[1, -1]),
+                spec("style", Float32, &[1, 256]),
+                spec("speed", Float32, &[1]),
+            ],
+            &[spec("audio", Float32, &[-1])],
+        )
+        .unwrap();
+        assert_eq!(
+            (
+                schema.tokens.as_str(),
+                schema.speed_type,
+                schema.per_item_speed
+            ),
+            ("tokens", SpeedType::Float32, false)
+        );
+        assert_eq!(schema.durations, None);
+
+        // input_ids with an int32 speed per item, waveform and durations outputs
+        let schema = ModelSchema::detect(
+            &[
+                spec("input_ids", Int64, &[-1, -1]),
+                spec("style", Float32, &[-1, 256]),
+                spec("speed", Int32, &[-1]),
+            ],
+            &[
+                spec("waveform", Float32, &[-1, -1]),
+                spec("durations", Int64, &[-1, -1]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(schema.tokens, "input_ids");
+        assert_eq!(schema.speed_type, SpeedType::Int32);
+        assert!(schema.per_item_speed);
+        assert_eq!(schema.audio, "waveform");
+        assert_eq!(schema.durations.as_deref(), Some("durations"));
+    }
+
+    #[test]
+    fn test_detect_unknown() {
+        use TensorElementType::*;
+
+        let outputs = [spec("audio", Float32, &[-1])];
+        let err = ModelSchema::detect(
+            &[
+                spec("text", Int64, &[1, -1]),
+                spec("style", Float32, &[1, 256]),
+            ],
+            &outputs,
+        )
+        .unwrap_err();
+        assert!(err.contains("no token ids input"), "{}", err);
+        assert!(err.contains("text: Int64"), "{}", err);
+
+        let err = ModelSchema::detect(
+            &[
+                spec("tokens", Int64, &[1, -1]),
+                spec("style", Float32, &[1, 256]),
+                spec("speed", Float64, &[1]),
+            ],
+            &outputs,
+        )
+        .unwrap_err();
+        assert!(err.contains("speed has type"), "{}", err);
+
+        let err = ModelSchema::detect(
+            &[
+                spec("tokens", Int64, &[1, -1]),
+                spec("style", Float32, &[1, 256]),
+                spec("speed", Float32, &[1]),
+                spec("language", Int64, &[1]),
+            ],
+            &outputs,
+        )
+        .unwrap_err();
+        assert!(err.contains("unknown input language"), "{}", err);
+    }
+}
@@ -1,32 +1,57 @@
-#[cfg(feature = "cuda")]
-use ort::execution_providers::cuda::CUDAExecutionProvider;
-use ort::execution_providers::cpu::CPUExecutionProvider;
-use ort::session::builder::SessionBuilder;
-use ort::session::Session;
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+use ort::execution_providers::{
+    CPUExecutionProvider, CUDAExecutionProvider, ExecutionProvider, OpenVINOExecutionProvider,
+    XNNPACKExecutionProvider,
+};
 use ort::logging::LogLevel;
+use ort::session::Session;
+use ort::session::builder::{GraphOptimizationLevel, SessionBuilder};
+
+use super::session_config::{Provider, SessionConfig};
 
 pub trait OrtBase {
-    fn load_model(&mut self, model_path: String) -> Result<(), String> {
-        #[cfg(feature = "cuda")]
-        let providers = [CUDAExecutionProvider::default().build()];
-
-        #[cfg(not(feature = "cuda"))]
-        let providers = [CPUExecutionProvider::default().build()];
-
-        match SessionBuilder::new() {
-            Ok(builder) => {
-                let session = builder
-                    .with_execution_providers(providers)
-                    .map_err(|e| format!("Failed to build session: {}", e))?
-                    .with_log_level(LogLevel::Warning)
-                    .map_err(|e| format!("Failed to set log level: {}", e))?
-                    .commit_from_file(model_path)
-                    .map_err(|e| format!("Failed to commit from file: {}", e))?;
-                self.set_sess(session);
-                Ok(())
+    /// Loads `model_path` on the first provider of `config` that works, falling
+    /// back along the list, and returns the provider used.
+    fn load_model(
+        &mut self,
+        model_path: String,
+        config: &SessionConfig,
+    ) -> Result<Provider, String> {
+        let mut errors = Vec::new();
+        for provider in config.provider_order() {
+            match build_session(&model_path, config, provider) {
+                Ok(session) => {
+                    if !errors.is_empty() {
+                        tracing::warn!(
+                            "Falling back to the {} execution provider: {}",
+                            provider,
+                            errors.join("; ")
+                        );
+                    }
+                    tracing::info!(
+                        "Loaded {} on the {} execution provider (intra threads: {}, inter threads: {}, optimization: {:?})",
+                        model_path,
+                        provider,
+                        config
+                            .intra_threads
+                            .map_or("default".to_string(), |n| n.to_string()),
+                        config
+                            .inter_threads
+                            .map_or("default".to_string(), |n| n.to_string()),
+                        config.optimization_level
+                    );
+                    self.set_sess(session, provider);
+                    return Ok(provider);
+                }
+                Err(e) => errors.push(format!("{}: {}", provider, e)),
             }
-            Err(e) => Err(format!("Failed to create session builder: {}", e)),
         }
+        Err(format!(
+            "No execution provider could load the model: {}",
+            errors.join("; ")
+        ))
     }
 
     fn print_info(&self) {
@@ -40,16 +65,145 @@ pub trait OrtBase {
                 eprintln!("  - {}", output.name);
             }
 
-            #[cfg(feature = "cuda")]
-            eprintln!("Configured with: CUDA execution provider");
-
-            #[cfg(not(feature = "cuda"))]
-            eprintln!("Configured with: CPU execution provider");
+            if let Some(provider) = self.provider() {
+                eprintln!("Configured with: {} execution provider", provider);
+            }
         } else {
             eprintln!("Session is not initialized.");
         }
     }
 
-    fn set_sess(&mut self, sess: Session);
+    fn set_sess(&mut self, sess: Session, provider: Provider);
     fn sess(&self) -> Option<&Session>;
+    /// Provider the session runs on, `None` before a model is loaded
+    fn provider(&self) -> Option<Provider>;
+}
+
+/// Session builder with the options of `config` applied, before a provider is
+/// registered.
+fn session_builder(
+    config: &SessionConfig,
+    level: GraphOptimizationLevel,
+) -> Result<SessionBuilder, String> {
+    let mut builder = SessionBuilder::new()
+        .map_err(|e| format!("Failed to create session builder: {}", e))?
+        .with_log_level(LogLevel::Warning)
+        .map_err(|e| format!("Failed to set log level: {}", e))?
+        .with_optimization_level(level)
+        .map_err(|e| format!("Failed to set optimization level: {}", e))?
+        .with_memory_pattern(config.memory_pattern)
+        .map_err(|e| format!("Failed to set memory pattern: {}", e))?;
+    if let Some(threads) = config.intra_threads {
+        builder = builder
+            .with_intra_threads(threads)
+            .map_err(|e| format!("Failed to set intra-op threads: {}", e))?;
+    }
+    if let Some(threads) = config.inter_threads {
+        builder = builder
+            .with_parallel_execution(true)
+            .map_err(|e| format!("Failed to enable parallel execution: {}", e))?
+            .with_inter_threads(threads)
+            .map_err(|e| format!("Failed to set inter-op threads: {}", e))?;
+    }
+    Ok(builder)
+}
+
+/// Registers `provider` on `builder`, failing when this ONNX Runtime build
+/// doesn't include it.
+fn register_provider(
+    builder: &mut SessionBuilder,
+    config: &SessionConfig,
+    provider: Provider,
+) -> Result<(), String> {
+    let execution_provider: Box<dyn ExecutionProvider> = match provider {
+        Provider::Cpu => {
+            Box::new(CPUExecutionProvider::default().with_arena_allocator(config.cpu_arena))
+        }
+        Provider::Xnnpack => {
+            let mut xnnpack = XNNPACKExecutionProvider::default();
+            if let Some(threads) = config.intra_threads.and_then(NonZeroUsize::new) {
+                xnnpack = xnnpack.with_intra_op_num_threads(threads);
+            }
+            Box::new(xnnpack)
+        }
+        Provider::OpenVino => Box::new(OpenVINOExecutionProvider::default()),
+        Provider::Cuda => Box::new(CUDAExecutionProvider::default()),
+    };
+    if !execution_provider.supported_by_platform() {
+        return Err("not supported on this platform".to_string());
+    }
+    match execution_provider.is_available() {
+        Ok(true) => {}
+        Ok(false) => return Err("not included in this ONNX Runtime build".to_string()),
+        Err(e) => return Err(e.to_string()),
+    }
+    execution_provider
+        .register(builder)
+        .map_err(|e| e.to_string())?;
+    if provider == Provider::Cpu {
+        return Ok(());
+    }
+
+    // Nodes the provider can't run fall back to the CPU provider, which gets
+    // the arena setting either way
+    CPUExecutionProvider::default()
+        .with_arena_allocator(config.cpu_arena)
+        .register(builder)
+        .map_err(|e| e.to_string())
+}
+
+/// Session for `model_path` on `provider`, loaded from the optimized model cache
+/// when it's at least as new as the model, and filling the cache otherwise.
+fn build_session(
+    model_path: &str,
+    config: &SessionConfig,
+    provider: Provider,
+) -> Result<Session, String> {
+    let cache = config.optimized_model_path(model_path, provider);
+    if let Some(cache) = cache
+        .as_deref()
+        .filter(|cache| is_fresh(cache, Path::new(model_path)))
+    {
+        // The cached graph is already optimized
+        let mut builder = session_builder(config, GraphOptimizationLevel::Disable)?;
+        register_provider(&mut builder, config, provider)?;
+        match builder.commit_from_file(cache) {
+            Ok(session) => return Ok(session),
+            Err(e) => tracing::warn!("Ignoring optimized model {}: {}", cache.display(), e),
+        }
+    }
+
+    let mut builder = session_builder(config, config.optimization_level.into())?;
+    register_provider(&mut builder, config, provider)?;
+    if let Some(cache) = &cache {
+        match cache.parent().map(std::fs::create_dir_all).transpose() {
+            Ok(_) => {
+                builder = builder
+                    .with_optimized_model_path(cache)
+                    .map_err(|e| format!("Failed to set optimized model path: {}", e))?;
+            }
+            Err(e) => tracing::warn!(
+                "Not caching the optimized model in {}: {}",
+                cache.display(),
+                e
+            ),
+        }
+    }
+    builder
+        .commit_from_file(model_path)
+        .map_err(|e| format!("Failed to commit from file: {}", e))
+}
+
+/// Whether `cache` exists and was written after `model` last changed.
+fn is_fresh(cache: &Path, model: &Path) -> bool {
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    match (modified(cache), modified(model)) {
+        (Some(cache), Some(model)) => cache >= model,
+        (Some(_), None) => true,
+        _ => false,
+    }
 }
@@ -1,34 +1,89 @@
-use std::borrow::Cow;
-
 use ndarray::{ArrayBase, IxDyn, OwnedRepr};
-use ort::{
-    session::{Session, SessionInputValue, SessionInputs, SessionOutputs},
-    value::{Tensor, Value},
-};
+use ort::session::{Session, SessionInputs, SessionOutputs};
 
+use super::model_schema::ModelSchema;
 use super::ort_base;
-use ort_base::OrtBase;
+use super::session_config::{Provider, SessionConfig};
+use crate::error::KokoError;
+use crate::tts::voices::STYLE_DIM;
 use crate::utils::debug::format_debug_prefix;
+use ort_base::OrtBase;
+
+/// Raw audio output of a single inference run
+pub type AudioArray = ArrayBase<OwnedRepr<f32>, IxDyn>;
+
+/// Samples above this amplitude count as sound when trimming batch padding
+const SILENCE_THRESHOLD: f32 = 1e-3;
+
+/// Audio kept after the last sound of a trimmed item, 50 ms at 24 kHz
+const TRIM_TAIL_SAMPLES: usize = 1200;
+
+/// One utterance of a batched inference run, see [`OrtKoko::infer_batch`].
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    /// Token ids, including the 0 padding token at both ends
+    pub tokens: Vec<i64>,
+    pub style: Vec<f32>,
+    pub speed: f32,
+}
+
+/// Audio of one [`BatchItem`], cut from the batch and trimmed to its own length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOutput {
+    pub audio: Vec<f32>,
+    /// Per-token durations (in frames) of the item's own tokens, for exports
+    /// that provide them
+    pub durations: Option<Vec<f32>>,
+}
 
 pub struct OrtKoko {
     sess: Option<Session>,
+    provider: Option<Provider>,
+    schema: Option<ModelSchema>,
 }
 impl ort_base::OrtBase for OrtKoko {
-    fn set_sess(&mut self, sess: Session) {
+    fn set_sess(&mut self, sess: Session, provider: Provider) {
         self.sess = Some(sess);
+        self.provider = Some(provider);
     }
 
     fn sess(&self) -> Option<&Session> {
         self.sess.as_ref()
     }
+
+    fn provider(&self) -> Option<Provider> {
+        self.provider
+    }
 }
 impl OrtKoko {
-    pub fn new(model_path: String) -> Result<Self, String> {
-        let mut instance = OrtKoko { sess: None };
-        instance.load_model(model_path)?;
+    pub fn new(model_path: String) -> Result<Self, KokoError> {
+        Self::with_config(model_path, &SessionConfig::default())
+    }
+
+    pub fn with_config(model_path: String, config: &SessionConfig) -> Result<Self, KokoError> {
+        let mut instance = OrtKoko {
+            sess: None,
+            provider: None,
+            schema: None,
+        };
+        instance
+            .load_model(model_path, config)
+            .map_err(KokoError::ModelLoad)?;
+        let sess = instance
+            .sess
+            .as_ref()
+            .ok_or_else(|| KokoError::ModelLoad("Session is not initialized.".to_string()))?;
+        let schema = ModelSchema::from_session(sess).map_err(KokoError::ModelLoad)?;
+        tracing::debug!("Model schema: {:?}", schema);
+        instance.schema = Some(schema);
         Ok(instance)
     }
 
+    /// Inputs and outputs the loaded model uses
+    pub fn schema(&self) -> Option<&ModelSchema> {
+        self.schema.as_ref()
+    }
+
     pub fn infer(
         &mut self,
         tokens: Vec<Vec<i64>>,
@@ -37,49 +92,318 @@ impl OrtKoko {
         request_id: Option<&str>,
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
-    ) -> Result<ArrayBase<OwnedRepr<f32>, IxDyn>, Box<dyn std::error::Error>> {
+    ) -> Result<ArrayBase<OwnedRepr<f32>, IxDyn>, KokoError> {
+        self.infer_with_durations(tokens, styles, speed, request_id, instance_id, chunk_number)
+            .map(|(audio, _)| audio)
+    }
 
-        let shape = [tokens.len(), tokens[0].len()];
+    /// Same as [`OrtKoko::infer`], but also returns the per-token `durations`
+    /// output (in frames) for exports that provide it, `None` otherwise.
+    pub fn infer_with_durations(
+        &mut self,
+        tokens: Vec<Vec<i64>>,
+        styles: Vec<Vec<f32>>,
+        speed: f32,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+        chunk_number: Option<usize>,
+    ) -> Result<(AudioArray, Option<Vec<f32>>), KokoError> {
+        let (Some(first_tokens), Some(first_style)) = (tokens.first(), styles.first()) else {
+            return Err(KokoError::Inference(
+                "nothing to infer, no tokens or no style".to_string(),
+            ));
+        };
+        if let Some(style) = styles.iter().find(|style| style.len() != STYLE_DIM) {
+            return Err(KokoError::InvalidStyleShape(format!(
+                "style row has {} values, the model takes {}",
+                style.len(),
+                STYLE_DIM
+            )));
+        }
+
+        let shape = [tokens.len(), first_tokens.len()];
+        let shape_style = [styles.len(), first_style.len()];
         let tokens_flat: Vec<i64> = tokens.into_iter().flatten().collect();
-        
-        let debug_prefix = format_debug_prefix(request_id, instance_id);
-        let chunk_info = chunk_number.map(|n| format!("Chunk: {}, ", n)).unwrap_or_default();
-        tracing::debug!("{} {}inference input: tokens_shape={:?}, tokens_count={}, styles_shape={:?}", debug_prefix, chunk_info, shape, tokens_flat.len(), [styles.len(), styles[0].len()]);
-        let tokens = Tensor::from_array((shape, tokens_flat))?;
-        let tokens_value: SessionInputValue = SessionInputValue::Owned(Value::from(tokens));
 
-        let shape_style = [styles.len(), styles[0].len()];
+        let debug_prefix = format_debug_prefix(request_id, instance_id);
+        let chunk_info = chunk_number
+            .map(|n| format!("Chunk: {}, ", n))
+            .unwrap_or_default();
+        tracing::debug!(
+            "{} {}inference input: tokens_shape={:?}, tokens_count={}, styles_shape={:?}",
+            debug_prefix,
+            chunk_info,
+            shape,
+            tokens_flat.len(),
+            shape_style
+        );
         let style_flat: Vec<f32> = styles.into_iter().flatten().collect();
-        let style = Tensor::from_array((shape_style, style_flat))?;
-        let style_value: SessionInputValue = SessionInputValue::Owned(Value::from(style));
-
-        let speed = vec![speed; 1];
-        let speed = Tensor::from_array(([1], speed))?;
-        let speed_value: SessionInputValue = SessionInputValue::Owned(Value::from(speed));
 
-        let inputs: Vec<(Cow<str>, SessionInputValue)> = vec![
-            (Cow::Borrowed("tokens"), tokens_value),
-            (Cow::Borrowed("style"), style_value),
-            (Cow::Borrowed("speed"), speed_value),
-        ];
-
-        if let Some(sess) = &mut self.sess {
+        let (Some(sess), Some(schema)) = (&mut self.sess, &self.schema) else {
+            return Err(KokoError::Inference(
+                "Session is not initialized.".to_string(),
+            ));
+        };
+        let inputs = schema.inputs((shape, tokens_flat), (shape_style, style_flat), vec![speed])?;
+        {
             let outputs: SessionOutputs = sess.run(SessionInputs::from(inputs))?;
-            let (shape, data) = outputs["audio"]
+            let (shape, data) = outputs[schema.audio.as_str()]
                 .try_extract_tensor::<f32>()
-                .expect("Failed to extract tensor");
+                .map_err(|e| {
+                    KokoError::Inference(format!(
+                        "Failed to extract {} output: {}",
+                        schema.audio, e
+                    ))
+                })?;
 
             // Convert Shape and &[f32] to ArrayBase<OwnedRepr<f32>, IxDyn>
             let shape_vec: Vec<usize> = shape.into_iter().map(|&i| i as usize).collect();
             let data_vec: Vec<f32> = data.to_vec();
             let debug_prefix = format_debug_prefix(request_id, instance_id);
-            let chunk_info = chunk_number.map(|n| format!("Chunk: {}, ", n)).unwrap_or_default();
-            tracing::debug!("{} {}inference output: audio_shape={:?}, sample_count={}", debug_prefix, chunk_info, shape_vec, data_vec.len());
-            let output_array = ArrayBase::<OwnedRepr<f32>, IxDyn>::from_shape_vec(shape_vec, data_vec)?;
+            let chunk_info = chunk_number
+                .map(|n| format!("Chunk: {}, ", n))
+                .unwrap_or_default();
+            tracing::debug!(
+                "{} {}inference output: audio_shape={:?}, sample_count={}",
+                debug_prefix,
+                chunk_info,
+                shape_vec,
+                data_vec.len()
+            );
+            let output_array = ArrayBase::<OwnedRepr<f32>, IxDyn>::from_shape_vec(
+                shape_vec, data_vec,
+            )
+            .map_err(|e| KokoError::Inference(format!("Invalid {} output: {}", schema.audio, e)))?;
+
+            let durations = extract_durations(&outputs, schema);
+            if let Some(durations) = &durations {
+                tracing::debug!(
+                    "{} {}inference output: durations_count={}",
+                    debug_prefix,
+                    chunk_info,
+                    durations.len()
+                );
+            }
+
+            Ok((output_array, durations))
+        }
+    }
+
+    /// Whether the model's `speed` input takes one value per batch item rather
+    /// than a single value for the whole batch.
+    pub fn per_item_speed(&self) -> bool {
+        self.schema
+            .as_ref()
+            .is_some_and(|schema| schema.per_item_speed)
+    }
+
+    /// Infers several utterances in one session run.
+    ///
+    /// Token sequences are padded with 0 to the longest one and each item gets its
+    /// own style row. Items need the same speed unless the model takes one per item
+    /// (see [`OrtKoko::per_item_speed`]). The batched audio is split per item and
+    /// cut to the item's own length: from the `durations` output when the export
+    /// has it, otherwise by trimming the trailing silence the padding produced.
+    /// Exports that can't batch return audio of the wrong shape, which is an error.
+    pub fn infer_batch(
+        &mut self,
+        items: Vec<BatchItem>,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+    ) -> Result<Vec<BatchOutput>, KokoError> {
+        match items.len() {
+            0 => return Ok(Vec::new()),
+            1 => {
+                let BatchItem {
+                    tokens,
+                    style,
+                    speed,
+                } = items.into_iter().next().unwrap();
+                let (audio, durations) = self.infer_with_durations(
+                    vec![tokens],
+                    vec![style],
+                    speed,
+                    request_id,
+                    instance_id,
+                    None,
+                )?;
+                return Ok(vec![BatchOutput {
+                    audio: audio.iter().cloned().collect(),
+                    durations,
+                }]);
+            }
+            _ => {}
+        }
+
+        let per_item_speed = self.per_item_speed();
+        if !per_item_speed && items.iter().any(|item| item.speed != items[0].speed) {
+            return Err(KokoError::Inference(
+                "this model takes one speed per batch, batch items with equal speeds".to_string(),
+            ));
+        }
+
+        let batch = items.len();
+        let lengths: Vec<usize> = items.iter().map(|item| item.tokens.len()).collect();
+        let max_len = lengths.iter().copied().max().unwrap_or(0);
+        let style_len = items[0].style.len();
+        if items.iter().any(|item| item.style.len() != style_len) {
+            return Err(KokoError::InvalidStyleShape(
+                "batch items have styles of different sizes".to_string(),
+            ));
+        }
+
+        let mut tokens_flat = Vec::with_capacity(batch * max_len);
+        let mut style_flat = Vec::with_capacity(batch * style_len);
+        let mut speeds = Vec::with_capacity(batch);
+        for item in items {
+            let padding = max_len - item.tokens.len();
+            tokens_flat.extend(item.tokens);
+            tokens_flat.extend(std::iter::repeat_n(0, padding));
+            style_flat.extend(item.style);
+            speeds.push(item.speed);
+        }
+        if !per_item_speed {
+            speeds.truncate(1);
+        }
+
+        let debug_prefix = format_debug_prefix(request_id, instance_id);
+        tracing::debug!(
+            "{} batched inference input: tokens_shape={:?}, styles_shape={:?}, speeds={:?}",
+            debug_prefix,
+            [batch, max_len],
+            [batch, style_len],
+            speeds
+        );
 
-            Ok(output_array)
-        } else {
-            Err("Session is not initialized.".into())
+        let (Some(sess), Some(schema)) = (&mut self.sess, &self.schema) else {
+            return Err(KokoError::Inference(
+                "Session is not initialized.".to_string(),
+            ));
+        };
+        let inputs = schema.inputs(
+            ([batch, max_len], tokens_flat),
+            ([batch, style_len], style_flat),
+            speeds,
+        )?;
+        let outputs: SessionOutputs = sess.run(SessionInputs::from(inputs))?;
+        let (shape, data) = outputs[schema.audio.as_str()]
+            .try_extract_tensor::<f32>()
+            .map_err(|e| {
+                KokoError::Inference(format!("Failed to extract {} output: {}", schema.audio, e))
+            })?;
+        if shape.len() != 2 || shape[0] as usize != batch {
+            return Err(KokoError::Inference(format!(
+                "model returned audio of shape {:?} for a batch of {}, it can't infer batches",
+                &shape[..],
+                batch
+            )));
         }
+        let durations = extract_durations(&outputs, schema);
+        tracing::debug!(
+            "{} batched inference output: audio_shape={:?}",
+            debug_prefix,
+            &shape[..]
+        );
+
+        Ok(split_batch(data, &lengths, max_len, durations.as_deref()))
+    }
+}
+
+/// The durations output of exports that have one. Timestamped exports emit
+/// durations as int64 frames, some as f32.
+fn extract_durations(outputs: &SessionOutputs, schema: &ModelSchema) -> Option<Vec<f32>> {
+    let value = outputs.get(schema.durations.as_deref()?)?;
+    if let Ok((_, data)) = value.try_extract_tensor::<i64>() {
+        Some(data.iter().map(|&d| d as f32).collect())
+    } else if let Ok((_, data)) = value.try_extract_tensor::<f32>() {
+        Some(data.to_vec())
+    } else {
+        None
+    }
+}
+
+/// Splits batched audio (one row per item) back into items of their own length.
+///
+/// With per-token `durations` for the whole padded batch, an item keeps the
+/// share of its row that its real tokens take up; every row is as long as the
+/// item with the most frames. Without them, each row's trailing silence is cut.
+fn split_batch(
+    data: &[f32],
+    lengths: &[usize],
+    max_len: usize,
+    durations: Option<&[f32]>,
+) -> Vec<BatchOutput> {
+    let batch = lengths.len();
+    let samples = data.len() / batch.max(1);
+    let durations = durations.filter(|durations| durations.len() == batch * max_len);
+
+    // Frames of each item, with and without the padding tokens
+    let frames: Option<Vec<(f32, f32)>> = durations.map(|durations| {
+        durations
+            .chunks(max_len)
+            .zip(lengths)
+            .map(|(row, &len)| (row[..len].iter().sum(), row.iter().sum()))
+            .collect()
+    });
+    let max_frames = frames
+        .as_ref()
+        .map(|frames| frames.iter().map(|(_, all)| *all).fold(0.0, f32::max))
+        .unwrap_or(0.0);
+
+    data.chunks(samples.max(1))
+        .take(batch)
+        .enumerate()
+        .map(|(i, row)| {
+            let len = match &frames {
+                Some(frames) if max_frames > 0.0 => {
+                    ((frames[i].0 / max_frames * samples as f32).round() as usize).min(samples)
+                }
+                _ => trailing_sound_end(row),
+            };
+            BatchOutput {
+                audio: row[..len].to_vec(),
+                durations: durations
+                    .map(|durations| durations[i * max_len..i * max_len + lengths[i]].to_vec()),
+            }
+        })
+        .collect()
+}
+
+/// End of the last sound in `audio`, plus a short tail so the decay isn't cut.
+fn trailing_sound_end(audio: &[f32]) -> usize {
+    audio
+        .iter()
+        .rposition(|sample| sample.abs() > SILENCE_THRESHOLD)
+        .map_or(0, |last| (last + 1 + TRIM_TAIL_SAMPLES).min(audio.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_batch_with_durations() {
+        // Two items of 3 and 2 tokens, padded to 3. The second has the most
+        // frames (5, padding included), and rows hold 2 samples per frame.
+        let durations = [1.0, 2.0, 1.0, 1.0, 1.0, 3.0];
+        let data: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let outputs = split_batch(&data, &[3, 2], 3, Some(&durations));
+        assert_eq!(
+            outputs[0].audio,
+            (0..8).map(|i| i as f32).collect::<Vec<_>>()
+        );
+        assert_eq!(outputs[1].audio, vec![10.0, 11.0, 12.0, 13.0]);
+        assert_eq!(outputs[1].durations, Some(vec![1.0, 1.0]));
+    }
+
+    #[test]
+    fn test_split_batch_trims_silence() {
+        let mut data = vec![0.0; 2 * 4000];
+        data[..1000].fill(0.5);
+        data[4000..6000].fill(0.5);
+        let outputs = split_batch(&data, &[5, 9], 9, None);
+        assert_eq!(outputs[0].audio.len(), 1000 + TRIM_TAIL_SAMPLES);
+        assert_eq!(outputs[1].audio.len(), 2000 + TRIM_TAIL_SAMPLES);
+        assert_eq!(outputs[1].durations, None);
     }
 }
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use ort::session::builder::GraphOptimizationLevel;
+
+/// Execution provider an ONNX Runtime session may run on.
+///
+/// Providers other than CPU need an ONNX Runtime build that includes them, and
+/// the matching cargo feature when ONNX Runtime is linked statically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Cpu,
+    Xnnpack,
+    OpenVino,
+    Cuda,
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Provider::Cpu => "CPU",
+            Provider::Xnnpack => "XNNPACK",
+            Provider::OpenVino => "OpenVINO",
+            Provider::Cuda => "CUDA",
+        })
+    }
+}
+
+impl Provider {
+    /// Providers this build was compiled with support for, most preferred first
+    pub fn enabled() -> Vec<Provider> {
+        let mut providers = Vec::new();
+        if cfg!(feature = "cuda") {
+            providers.push(Provider::Cuda);
+        }
+        if cfg!(feature = "openvino") {
+            providers.push(Provider::OpenVino);
+        }
+        if cfg!(feature = "xnnpack") {
+            providers.push(Provider::Xnnpack);
+        }
+        providers.push(Provider::Cpu);
+        providers
+    }
+
+    /// Whether the provider compiles parts of the graph into nodes ONNX Runtime
+    /// can't save, so optimized models can't be cached for it
+    pub fn compiles_graph(&self) -> bool {
+        matches!(self, Provider::OpenVino)
+    }
+}
+
+/// How much ONNX Runtime rewrites the graph before running it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizationLevel {
+    Disable,
+    Basic,
+    Extended,
+    #[default]
+    All,
+}
+
+impl From<OptimizationLevel> for GraphOptimizationLevel {
+    fn from(level: OptimizationLevel) -> Self {
+        match level {
+            OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+            OptimizationLevel::Basic => GraphOptimizationLevel::Level1,
+            OptimizationLevel::Extended => GraphOptimizationLevel::Level2,
+            OptimizationLevel::All => GraphOptimizationLevel::Level3,
+        }
+    }
+}
+
+/// Options for the ONNX Runtime sessions a model is loaded into.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Threads used within an operator, ONNX Runtime's choice when unset
+    pub intra_threads: Option<usize>,
+    /// Threads used to run independent operators in parallel; setting it
+    /// enables parallel execution
+    pub inter_threads: Option<usize>,
+    pub optimization_level: OptimizationLevel,
+    /// Whether memory is planned ahead from the shapes of earlier runs
+    pub memory_pattern: bool,
+    /// Whether the CPU provider allocates from an arena it keeps around
+    pub cpu_arena: bool,
+    /// Directory optimized models are cached in, so later startups skip the
+    /// graph optimizations
+    pub optimized_model_dir: Option<PathBuf>,
+    /// Providers to try, most preferred first. CPU is tried last when it isn't
+    /// listed. Defaults to the providers enabled by cargo features.
+    pub providers: Vec<Provider>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            intra_threads: None,
+            inter_threads: None,
+            optimization_level: OptimizationLevel::default(),
+            memory_pattern: true,
+            cpu_arena: true,
+            optimized_model_dir: None,
+            providers: Provider::enabled(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Providers in the order they're tried, without duplicates and ending in
+    /// CPU at the latest.
+    pub fn provider_order(&self) -> Vec<Provider> {
+        let mut order: Vec<Provider> = Vec::new();
+        for provider in &self.providers {
+            if !order.contains(provider) {
+                order.push(*provider);
+            }
+            if *provider == Provider::Cpu {
+                return order;
+            }
+        }
+        order.push(Provider::Cpu);
+        order
+    }
+
+    /// Where the optimized version of `model_path` for `provider` is cached.
+    /// Optimized graphs may contain provider-specific nodes, so each provider
+    /// and level gets its own file. `None` when caching is off or `provider`
+    /// compiles the graph.
+    pub fn optimized_model_path(&self, model_path: &str, provider: Provider) -> Option<PathBuf> {
+        if provider.compiles_graph() {
+            return None;
+        }
+        let dir = self.optimized_model_dir.as_ref()?;
+        let stem = Path::new(model_path).file_stem()?.to_string_lossy();
+        let level = serde_json::to_value(self.optimization_level).ok()?;
+        Some(dir.join(format!(
+            "{}.{}.{}.onnx",
+            stem,
+            provider.to_string().to_lowercase(),
+            level.as_str()?
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_order() {
+        let config: SessionConfig =
+            serde_json::from_str(r#"{"providers": ["cuda", "xnnpack", "cuda"]}"#).unwrap();
+        assert_eq!(
+            config.provider_order(),
+            vec![Provider::Cuda, Provider::Xnnpack, Provider::Cpu]
+        );
+        assert!(config.memory_pattern);
+
+        // Nothing after CPU is ever tried
+        let config = SessionConfig {
+            providers: vec![Provider::OpenVino, Provider::Cpu, Provider::Cuda],
+            ..SessionConfig::default()
+        };
+        assert_eq!(
+            config.provider_order(),
+            vec![Provider::OpenVino, Provider::Cpu]
+        );
+        assert_eq!(
+            SessionConfig::default().provider_order(),
+            Provider::enabled()
+        );
+    }
+
+    #[test]
+    fn test_enabled_providers() {
+        let providers = Provider::enabled();
+        assert_eq!(providers.last(), Some(&Provider::Cpu));
+        assert_eq!(providers.contains(&Provider::Cuda), cfg!(feature = "cuda"));
+        assert_eq!(
+            providers.contains(&Provider::OpenVino),
+            cfg!(feature = "openvino")
+        );
+        assert_eq!(
+            providers.contains(&Provider::Xnnpack),
+            cfg!(feature = "xnnpack")
+        );
+    }
+
+    #[test]
+    fn test_optimized_model_path() {
+        let mut config = SessionConfig::default();
+        assert_eq!(
+            config.optimized_model_path("models/kokoro.onnx", Provider::Cpu),
+            None
+        );
+        config.optimized_model_dir = Some(PathBuf::from("cache"));
+        config.optimization_level = OptimizationLevel::Extended;
+        assert_eq!(
+            config.optimized_model_path("models/kokoro.onnx", Provider::Xnnpack),
+            Some(PathBuf::from("cache/kokoro.xnnpack.extended.onnx"))
+        );
+        // OpenVINO's compiled nodes can't be saved
+        assert_eq!(
+            config.optimized_model_path("models/kokoro.onnx", Provider::OpenVino),
+            None
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use crate::tts::segment;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    // 1. 2) 3: (4), and the like
+    static ref NUMBERED_ITEM_RE: Regex = Regex::new(r"^\(?[0-9]+[.\)\:],?$").unwrap();
+}
+
+/// Words that start a clause: long chunks are split before them, and a chunk
+/// ending in one hands it to the next chunk
+pub const BREAK_WORDS: &[&str] = &[
+    "and", "or", "but", "&", "because", "if", "since", "though", "although", "however", "which",
+];
+
+/// How text is split into word-based chunks for streaming synthesis.
+///
+/// Text is split into sentences (see [`segment::split_sentences`]), sentences at
+/// clause punctuation, and chunks that are still long are halved near their
+/// middle, preferably after punctuation and otherwise before a break word.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChunkingPolicy {
+    /// Words after which a soft break ends a chunk
+    pub target_words: usize,
+    /// Chunks with at least this many words are split near their middle
+    pub max_words: usize,
+    /// `max_words` for the first chunk; smaller values start the audio sooner
+    pub first_chunk_words: usize,
+    /// Cut the first chunk below `first_chunk_words` even where there's no
+    /// punctuation or break word, trading some prosody for a sooner start
+    pub hard_split_first: bool,
+    /// How many leading chunks may be split at `split_punctuation` before
+    /// break words are tried
+    pub punctuation_chunks: usize,
+    /// Marks a long chunk may be split after, most preferred first
+    pub split_punctuation: Vec<String>,
+    /// Marks that always end a chunk
+    pub hard_breaks: Vec<char>,
+    /// Marks that end a chunk once it has `target_words` words
+    pub soft_breaks: Vec<char>,
+    pub break_words: Vec<String>,
+    /// Whether list markers like `1.` and `(2)` get a chunk of their own
+    pub numbered_items: bool,
+    /// How many times a long chunk may be halved
+    pub max_depth: usize,
+    /// Fewest words the first half of a split may have
+    pub min_split_words: usize,
+}
+
+impl Default for ChunkingPolicy {
+    fn default() -> Self {
+        Self {
+            target_words: 10,
+            max_words: 12,
+            first_chunk_words: 12,
+            hard_split_first: false,
+            punctuation_chunks: 2,
+            split_punctuation: vec![",".into(), "，".into(), "、".into()],
+            hard_breaks: vec![':', ';', '：', '；'],
+            soft_breaks: vec![',', '，', '、'],
+            break_words: BREAK_WORDS.iter().map(|w| w.to_string()).collect(),
+            numbered_items: true,
+            max_depth: 3,
+            min_split_words: 3,
+        }
+    }
+}
+
+impl ChunkingPolicy {
+    /// Default policy aiming for chunks of about `words` words.
+    pub fn with_target_words(words: usize) -> Self {
+        Self {
+            target_words: words,
+            max_words: words,
+            first_chunk_words: words,
+            ..Self::default()
+        }
+    }
+
+    /// Splits `text` into chunks. Apart from whitespace, the chunks are `text`
+    /// in its original order.
+    pub fn split(&self, text: &str) -> Vec<String> {
+        let mut chunks = Vec::new();
+        for sentence in segment::split_sentences(text) {
+            let mut current: Vec<&str> = Vec::new();
+            for word in sentence.split_whitespace() {
+                let numbered = self.numbered_items && NUMBERED_ITEM_RE.is_match(word);
+                if numbered && !current.is_empty() {
+                    chunks.push(current.join(" "));
+                    current.clear();
+                }
+                current.push(word);
+
+                let hard = word.ends_with(self.hard_breaks.as_slice());
+                let soft = word.ends_with(self.soft_breaks.as_slice())
+                    && current.len() >= self.target_words;
+                if hard || soft || numbered {
+                    chunks.push(current.join(" "));
+                    current.clear();
+                }
+            }
+            // The end of a sentence always ends a chunk
+            if !current.is_empty() {
+                chunks.push(current.join(" "));
+            }
+        }
+
+        let mut split = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let words: Vec<&str> = chunk.split_whitespace().collect();
+            let threshold = if index == 0 {
+                self.first_chunk_words
+            } else {
+                self.max_words
+            };
+            self.split_long(
+                &words,
+                threshold,
+                index < self.punctuation_chunks,
+                0,
+                &mut split,
+            );
+            if index == 0 && self.hard_split_first {
+                cut_first(&mut split, threshold);
+            }
+        }
+
+        self.move_break_words(&mut split);
+        split
+    }
+
+    /// Halves `words` near the middle until the parts are shorter than
+    /// `threshold` or `max_depth` is reached.
+    fn split_long(
+        &self,
+        words: &[&str],
+        threshold: usize,
+        use_punctuation: bool,
+        depth: usize,
+        out: &mut Vec<String>,
+    ) {
+        if depth >= self.max_depth || words.len() < threshold {
+            out.push(words.join(" "));
+            return;
+        }
+
+        let center = words.len() / 2;
+        let valid = |pos: &usize| *pos >= self.min_split_words.max(1) && *pos < words.len();
+        let after_punctuation = || {
+            self.split_punctuation.iter().find_map(|mark| {
+                closest_to(words, center, |word| word.ends_with(mark.as_str()))
+                    .map(|i| i + 1)
+                    .filter(valid)
+            })
+        };
+        let before_break_word =
+            || closest_to(words, center, |word| self.is_break_word(word)).filter(valid);
+        let pos = if use_punctuation {
+            after_punctuation().or_else(before_break_word)
+        } else {
+            before_break_word()
+        };
+
+        match pos {
+            Some(pos) => {
+                self.split_long(&words[..pos], threshold, use_punctuation, depth + 1, out);
+                self.split_long(&words[pos..], threshold, use_punctuation, depth + 1, out);
+            }
+            // No suitable break point, keep the chunk whole
+            None => out.push(words.join(" ")),
+        }
+    }
+
+    /// Moves a break word ending a chunk to the start of the next one, where it
+    /// begins the clause it belongs to.
+    fn move_break_words(&self, chunks: &mut [String]) {
+        for i in 0..chunks.len().saturating_sub(1) {
+            let words: Vec<&str> = chunks[i].split_whitespace().collect();
+            let Some(last_word) = words.last() else {
+                continue;
+            };
+            // Only move if it won't leave an empty chunk
+            if words.len() > 1 && self.is_break_word(last_word) {
+                let next = format!("{} {}", last_word, chunks[i + 1]);
+                chunks[i] = words[..words.len() - 1].join(" ");
+                chunks[i + 1] = next;
+            }
+        }
+    }
+
+    fn is_break_word(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.break_words.contains(&word)
+    }
+}
+
+/// Cuts the first chunk right below `threshold` words if it's still that long.
+fn cut_first(chunks: &mut Vec<String>, threshold: usize) {
+    let Some(first) = chunks.first() else {
+        return;
+    };
+    let words: Vec<&str> = first.split_whitespace().collect();
+    let cut = threshold.saturating_sub(1).max(1);
+    if words.len() > cut && words.len() >= threshold {
+        let (head, tail) = (words[..cut].join(" "), words[cut..].join(" "));
+        chunks[0] = head;
+        chunks.insert(1, tail);
+    }
+}
+
+/// Index of the word matching `predicate` closest to `center`, the earlier one
+/// on a tie.
+fn closest_to(words: &[&str], center: usize, predicate: impl Fn(&str) -> bool) -> Option<usize> {
+    words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| predicate(word))
+        .min_by_key(|(i, _)| i.abs_diff(center))
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Text without its whitespace, which chunking may change
+    fn content(text: &str) -> String {
+        text.chars().filter(|c| !c.is_whitespace()).collect()
+    }
+
+    #[test]
+    fn test_sentences_and_clauses() {
+        let policy = ChunkingPolicy::default();
+        assert_eq!(
+            policy.split("Hello there. Steps: 1. Open it; then close it! 你好。再见！"),
+            vec![
+                "Hello there.",
+                "Steps:",
+                "1.",
+                "Open it;",
+                "then close it!",
+                "你好。",
+                "再见！"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_splits_long_chunks() {
+        let policy = ChunkingPolicy::default();
+        let text = "We walked along the river for hours, talking about the old days \
+                    and the people we had known before the war.";
+        assert_eq!(
+            policy.split(text),
+            vec![
+                "We walked along the river for hours,",
+                "talking about the old days",
+                "and the people we had known before the war."
+            ]
+        );
+
+        // A smaller first chunk, and break words only
+        let policy = ChunkingPolicy {
+            first_chunk_words: 4,
+            punctuation_chunks: 0,
+            ..ChunkingPolicy::default()
+        };
+        assert_eq!(
+            policy.split("One two three and four five six seven. Eight nine ten."),
+            vec![
+                "One two three",
+                "and four five six seven.",
+                "Eight nine ten."
+            ]
+        );
+
+        // Without a break point the first chunk is only cut when asked to
+        let text = "Seven words with no place to break. Then more.";
+        let policy = ChunkingPolicy {
+            first_chunk_words: 4,
+            ..ChunkingPolicy::default()
+        };
+        assert_eq!(policy.split(text)[0], "Seven words with no place to break.");
+        let policy = ChunkingPolicy {
+            hard_split_first: true,
+            ..policy
+        };
+        assert_eq!(
+            policy.split(text),
+            vec!["Seven words with", "no place to break.", "Then more."]
+        );
+    }
+
+    fn policies() -> impl Strategy<Value = ChunkingPolicy> {
+        (
+            1..20usize,
+            1..20usize,
+            1..20usize,
+            0..4usize,
+            0..5usize,
+            0..5usize,
+            any::<bool>(),
+        )
+            .prop_map(
+                |(
+                    target,
+                    max,
+                    first,
+                    punctuation_chunks,
+                    max_depth,
+                    min_split,
+                    hard_split_first,
+                )| ChunkingPolicy {
+                    target_words: target,
+                    max_words: max,
+                    first_chunk_words: first,
+                    hard_split_first,
+                    punctuation_chunks,
+                    max_depth,
+                    min_split_words: min_split,
+                    ..ChunkingPolicy::default()
+                },
+            )
+    }
+
+    fn texts() -> impl Strategy<Value = String> {
+        let word = prop_oneof![
+            "[A-Za-z]{1,8}[,.;:!?]?",
+            "\\(?[0-9]{1,2}[.):],?",
+            prop::sample::select(BREAK_WORDS).prop_map(String::from),
+            "(Dr|Mr|e\\.g|U\\.S)\\.",
+            "[一-龥]{1,6}[。，！？、]?",
+            "[\n]",
+        ];
+        prop::collection::vec(word, 0..80).prop_map(|words| words.join(" "))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_keeps_text_in_order(policy in policies(), text in texts()) {
+            let chunks = policy.split(&text);
+            let joined = chunks.join(" ");
+            prop_assert_eq!(content(&joined), content(&text));
+            prop_assert!(chunks.iter().all(|chunk| !chunk.trim().is_empty()));
+        }
+
+        #[test]
+        fn prop_keeps_arbitrary_text(text in "\\PC{0,200}") {
+            let joined = ChunkingPolicy::default().split(&text).join(" ");
+            prop_assert_eq!(content(&joined), content(&text));
+        }
+    }
+}
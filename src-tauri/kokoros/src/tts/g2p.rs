@@ -0,0 +1,187 @@
+use crate::tts::heteronym;
+use crate::tts::lexicon::LexiconG2p;
+use crate::tts::phonemizer_service::PhonemizerService;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Grapheme-to-phoneme conversion into Kokoro's phoneme vocabulary.
+///
+/// Output follows espeak's layout: words separated by a space, punctuation kept
+/// and attached to the word before it.
+pub trait G2p: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether this backend can phonemize `lang` (an espeak language code such as `en-us`).
+    fn supports(&self, lang: &str) -> bool;
+
+    fn phonemize(&self, text: &str, lang: &str) -> Result<String, String>;
+
+    fn phonemize_batch(&self, texts: &[&str], lang: &str) -> Result<Vec<String>, String> {
+        texts
+            .iter()
+            .map(|text| self.phonemize(text, lang))
+            .collect()
+    }
+}
+
+/// espeak-ng, through the shared [`PhonemizerService`].
+pub struct EspeakG2p;
+
+impl G2p for EspeakG2p {
+    fn name(&self) -> &'static str {
+        "espeak"
+    }
+
+    fn supports(&self, _lang: &str) -> bool {
+        true
+    }
+
+    fn phonemize(&self, text: &str, lang: &str) -> Result<String, String> {
+        PhonemizerService::global().phonemize(text, lang)
+    }
+
+    fn phonemize_batch(&self, texts: &[&str], lang: &str) -> Result<Vec<String>, String> {
+        PhonemizerService::global().phonemize_batch(texts, lang)
+    }
+}
+
+/// Selects a [`G2p`] backend, e.g. from settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum G2pKind {
+    #[default]
+    Espeak,
+    /// Bundled English dictionary with letter-to-sound rules, see [`LexiconG2p`]
+    Lexicon,
+}
+
+impl G2pKind {
+    pub fn backend(self) -> Arc<dyn G2p> {
+        match self {
+            G2pKind::Espeak => Arc::new(EspeakG2p),
+            G2pKind::Lexicon => Arc::new(LexiconG2p::bundled()),
+        }
+    }
+}
+
+impl FromStr for G2pKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "espeak" => Ok(G2pKind::Espeak),
+            "lexicon" => Ok(G2pKind::Lexicon),
+            _ => Err(format!(
+                "unknown G2P backend '{}', expected espeak or lexicon",
+                s
+            )),
+        }
+    }
+}
+
+/// Phonemizes with `g2p`, falling back to espeak for languages it doesn't support.
+///
+/// English output gets heteronyms (read, live, lead, ...) corrected from context,
+/// see [`heteronym::apply_overrides`].
+pub fn phonemize_batch(g2p: &dyn G2p, texts: &[&str], lang: &str) -> Result<Vec<String>, String> {
+    let phonemes = if g2p.supports(lang) {
+        g2p.phonemize_batch(texts, lang)?
+    } else {
+        EspeakG2p.phonemize_batch(texts, lang)?
+    };
+
+    if lang != "en" && !lang.starts_with("en-") {
+        return Ok(phonemes);
+    }
+    Ok(texts
+        .iter()
+        .zip(phonemes)
+        .map(|(text, phonemes)| heteronym::apply_overrides(text, &phonemes))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words with their expected misaki-style pronunciation. The first half is in the
+    /// bundled dictionary, the second half goes through the letter-to-sound rules.
+    const GOLDEN_WORDS: &[(&str, &str)] = &[
+        ("hello", "həlˈO"),
+        ("world", "wˈɜɹld"),
+        ("the", "ðə"),
+        ("thousand", "θˈWzənd"),
+        ("water", "wˈɔTəɹ"),
+        ("cat", "kˈæt"),
+        ("ship", "ʃˈɪp"),
+        ("cake", "kˈAk"),
+        ("bike", "bˈIk"),
+        ("hope", "hˈOp"),
+        ("light", "lˈIt"),
+        ("rain", "ɹˈAn"),
+        ("boat", "bˈOt"),
+        ("green", "ɡɹˈin"),
+        ("quick", "kwˈɪk"),
+        ("shell", "ʃˈɛl"),
+        ("city", "sˈɪti"),
+        ("farmer", "fˈɑɹməɹ"),
+    ];
+
+    #[test]
+    fn test_lexicon_golden_words() {
+        let g2p = G2pKind::Lexicon.backend();
+        for (word, expected) in GOLDEN_WORDS {
+            assert_eq!(&g2p.phonemize(word, "en-us").unwrap(), expected, "{word}");
+        }
+    }
+
+    /// Maps espeak's IPA onto misaki's shorthands so the two can be compared.
+    fn espeak_to_misaki(phonemes: &str) -> String {
+        phonemes
+            .replace("eɪ", "A")
+            .replace("aɪ", "I")
+            .replace("oʊ", "O")
+            .replace("aʊ", "W")
+            .replace("ɔɪ", "Y")
+            .replace('ɚ', "əɹ")
+            .replace('ɾ', "T")
+            .replace('r', "ɹ")
+            .replace('ᵻ', "ɪ")
+            .replace(['ː', 'ˌ'], "")
+    }
+
+    /// Words espeak transcribes differently from misaki, with espeak's version
+    /// after [`espeak_to_misaki`]: the r of "world" as a long vowel and a
+    /// flapped t in "city". From espeak-ng's en-us rules; re-check them with
+    /// `--ignored` on a machine with espeak-ng before relying on a change.
+    const ESPEAK_DIFFERENCES: &[(&str, &str)] = &[("world", "wˈɜld"), ("city", "sˈɪTi")];
+
+    #[test]
+    #[ignore = "needs espeak-ng and its data; run with --ignored"]
+    fn test_espeak_golden_words() {
+        let espeak = G2pKind::Espeak.backend();
+        for (word, golden) in GOLDEN_WORDS {
+            let expected = ESPEAK_DIFFERENCES
+                .iter()
+                .find(|(different, _)| different == word)
+                .map_or(*golden, |(_, phonemes)| *phonemes);
+            let phonemes = espeak.phonemize(word, "en-us").unwrap();
+            assert_eq!(espeak_to_misaki(phonemes.trim()), expected, "{word}");
+        }
+    }
+
+    #[test]
+    fn test_heteronyms_in_pipeline() {
+        let lexicon = LexiconG2p::bundled();
+        let phonemes = phonemize_batch(&lexicon, &["I have read it.", "Read it."], "en-us");
+        assert_eq!(phonemes.unwrap(), vec!["ˈI hæv ɹˈɛd ɪt.", "ɹˈid ɪt."]);
+    }
+
+    #[test]
+    fn test_kind_from_str() {
+        assert_eq!("lexicon".parse::<G2pKind>().unwrap(), G2pKind::Lexicon);
+        assert!("festival".parse::<G2pKind>().is_err());
+        assert!(!G2pKind::Lexicon.backend().supports("fr-fr"));
+    }
+}
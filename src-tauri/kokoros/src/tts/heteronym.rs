@@ -0,0 +1,375 @@
+use crate::tts::timings::align_words;
+
+/// Punctuation the phonemizers keep attached to words
+const PUNCTUATION: &str = ";:,.!?¡¿—…\"«»“”()";
+
+const DETERMINERS: &[&str] = &[
+    "a", "an", "the", "my", "your", "his", "her", "its", "our", "their", "this", "that", "these",
+    "those", "every", "each", "no", "some", "any", "another", "one", "new", "old",
+];
+const SUBJECTS: &[&str] = &["i", "you", "we", "they", "he", "she", "it", "who", "people"];
+const THIRD_PERSON: &[&str] = &["he", "she", "it", "who"];
+/// Words after which a verb takes its base form
+const BASE_FORM_CUES: &[&str] = &[
+    "to",
+    "will",
+    "would",
+    "can",
+    "could",
+    "should",
+    "must",
+    "might",
+    "may",
+    "shall",
+    "do",
+    "does",
+    "did",
+    "don't",
+    "doesn't",
+    "didn't",
+    "won't",
+    "can't",
+    "couldn't",
+    "wouldn't",
+    "shouldn't",
+    "let's",
+    "please",
+    "i'll",
+    "you'll",
+    "we'll",
+    "they'll",
+    "he'll",
+    "she'll",
+];
+const HAVE: &[&str] = &[
+    "have", "has", "had", "i've", "you've", "we've", "they've", "i'd", "you'd", "we'd", "they'd",
+    "he'd", "she'd", "having", "haven't", "hasn't", "hadn't",
+];
+const BE: &[&str] = &[
+    "am", "is", "are", "was", "were", "be", "been", "being", "i'm", "you're", "we're", "they're",
+    "he's", "she's", "it's", "isn't", "aren't", "wasn't", "weren't",
+];
+const PAST_CUES: &[&str] = &[
+    "yesterday",
+    "ago",
+    "last",
+    "earlier",
+    "already",
+    "previously",
+    "once",
+    "was",
+    "were",
+];
+const PARTICLES: &[&str] = &[
+    "up", "down", "around", "through", "back", "apart", "off", "open",
+];
+const LIVE_NOUNS: &[&str] = &[
+    "music",
+    "show",
+    "stream",
+    "streaming",
+    "broadcast",
+    "performance",
+    "event",
+    "concert",
+    "audience",
+    "feed",
+    "video",
+    "coverage",
+    "wire",
+    "recording",
+    "version",
+    "chat",
+    "demo",
+    "update",
+    "updates",
+    "session",
+    "band",
+    "album",
+];
+const LEAD_METAL: &[&str] = &[
+    "pipe",
+    "pipes",
+    "paint",
+    "poisoning",
+    "pencil",
+    "pencils",
+    "weight",
+    "weights",
+    "shot",
+    "acid",
+    "levels",
+    "exposure",
+];
+const CRYING_CUES: &[&str] = &[
+    "cry", "cried", "crying", "eye", "eyes", "sad", "joy", "wept", "weep", "shed", "rolled",
+    "streamed", "welled", "cheek", "cheeks",
+];
+const RIPPING_CUES: &[&str] = &[
+    "paper", "fabric", "shirt", "jeans", "cloth", "sheet", "muscle", "ligament", "page", "seam",
+];
+
+/// Word-level context of a heteronym in a sentence.
+struct Context<'a> {
+    prev: &'a str,
+    next: &'a str,
+    sentence: &'a [String],
+}
+
+impl Context<'_> {
+    fn prev_in(&self, words: &[&str]) -> bool {
+        words.contains(&self.prev)
+    }
+
+    fn next_in(&self, words: &[&str]) -> bool {
+        words.contains(&self.next)
+    }
+
+    fn sentence_has(&self, words: &[&str]) -> bool {
+        self.sentence.iter().any(|w| words.contains(&w.as_str()))
+    }
+
+    /// The heteronym is used as a verb in its base or present form
+    fn verb_position(&self) -> bool {
+        self.prev_in(BASE_FORM_CUES) || self.prev_in(SUBJECTS)
+    }
+}
+
+/// Picks the pronunciation of a heteronym (lowercase, without punctuation) from its
+/// context, in Kokoro's (misaki) phoneme set. `None` if `word` isn't a known heteronym.
+fn pronounce(word: &str, cx: &Context) -> Option<&'static str> {
+    let phonemes = match word {
+        "read" => {
+            // "have read", "was read" and "he read" (not "reads") are past forms
+            let past = cx.prev_in(HAVE)
+                || cx.prev_in(BE)
+                || cx.prev_in(THIRD_PERSON)
+                || (!cx.prev_in(BASE_FORM_CUES) && cx.sentence_has(PAST_CUES));
+            if past { "ɹˈɛd" } else { "ɹˈid" }
+        }
+        "live" => {
+            // "live music", "a live show", "we go live", "we're live"
+            let adjective = cx.next_in(LIVE_NOUNS)
+                || cx.prev_in(DETERMINERS)
+                || cx.prev_in(&["go", "goes", "going", "went", "gone"])
+                || cx.prev_in(BE);
+            if adjective { "lˈIv" } else { "lˈɪv" }
+        }
+        "lives" => {
+            let noun = cx.prev_in(DETERMINERS)
+                || cx.prev_in(&["save", "saved", "saving", "many", "whose", "nine", "two"]);
+            if noun { "lˈIvz" } else { "lˈɪvz" }
+        }
+        "lead" => {
+            if cx.prev == "of" || cx.next_in(LEAD_METAL) {
+                "lˈɛd"
+            } else {
+                "lˈid"
+            }
+        }
+        "record" => {
+            if cx.verb_position() {
+                "ɹəkˈɔɹd"
+            } else {
+                "ɹˈɛkəɹd"
+            }
+        }
+        "records" => {
+            if cx.prev_in(THIRD_PERSON) {
+                "ɹəkˈɔɹdz"
+            } else {
+                "ɹˈɛkəɹdz"
+            }
+        }
+        "present" => {
+            if cx.prev != "at" && cx.verb_position() {
+                "pɹizˈɛnt"
+            } else {
+                "pɹˈɛzənt"
+            }
+        }
+        "presents" => {
+            if cx.prev_in(THIRD_PERSON) {
+                "pɹizˈɛnts"
+            } else {
+                "pɹˈɛzənts"
+            }
+        }
+        "wind" => {
+            if cx.verb_position() || cx.next_in(PARTICLES) {
+                "wˈInd"
+            } else {
+                "wˈɪnd"
+            }
+        }
+        "winds" => {
+            if cx.prev_in(&["road", "path", "river", "trail", "it", "he", "she"])
+                || cx.next_in(PARTICLES)
+            {
+                "wˈIndz"
+            } else {
+                "wˈɪndz"
+            }
+        }
+        "tear" | "tears" => {
+            let wear_and_tear = cx.prev == "and"
+                && cx
+                    .sentence
+                    .windows(3)
+                    .any(|w| w[0] == "wear" && w[1] == "and" && w[2] == word);
+            let ripping = wear_and_tear
+                || cx.verb_position()
+                || cx.next_in(PARTICLES)
+                || cx.sentence_has(RIPPING_CUES);
+            let crying = cx.sentence_has(CRYING_CUES) || cx.prev == "in";
+            match (word, ripping && !crying) {
+                ("tear", true) => "tˈɛɹ",
+                ("tear", false) => "tˈɪɹ",
+                (_, true) => "tˈɛɹz",
+                (_, false) => "tˈɪɹz",
+            }
+        }
+        _ => return None,
+    };
+    Some(phonemes)
+}
+
+/// Lowercases a word and strips surrounding punctuation, keeping inner apostrophes.
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+        .trim_matches('\'')
+        .to_lowercase()
+        .replace('’', "'")
+}
+
+/// Pronunciations for the heteronyms in `sentence`, as (word index, phonemes)
+/// pairs where the index counts whitespace-separated words.
+pub fn disambiguate(sentence: &str) -> Vec<(usize, &'static str)> {
+    let words: Vec<String> = sentence.split_whitespace().map(normalize).collect();
+    (0..words.len())
+        .filter_map(|i| {
+            let cx = Context {
+                prev: i.checked_sub(1).map_or("", |p| words[p].as_str()),
+                next: words.get(i + 1).map_or("", |n| n.as_str()),
+                sentence: &words,
+            };
+            Some((i, pronounce(&words[i], &cx)?))
+        })
+        .collect()
+}
+
+/// Replaces the phonemizer's pronunciation of every heteronym in `text` with the one
+/// [`disambiguate`] picked, keeping the punctuation attached to the phoneme word.
+///
+/// Words are matched with [`align_words`]; a heteronym is only overridden when it maps
+/// onto exactly one phoneme word that starts with the same sound, so a misalignment
+/// leaves the phonemizer's output untouched.
+pub fn apply_overrides(text: &str, phonemes: &str) -> String {
+    let overrides = disambiguate(text);
+    if overrides.is_empty() {
+        return phonemes.to_string();
+    }
+
+    let text_words = text.split_whitespace().count();
+    let mut phoneme_words: Vec<String> = phonemes.split_whitespace().map(String::from).collect();
+
+    for (text_range, phoneme_range) in align_words(text_words, phoneme_words.len()) {
+        if text_range.len() != 1 || phoneme_range.len() != 1 {
+            continue;
+        }
+        let Some((_, replacement)) = overrides.iter().find(|(i, _)| *i == text_range.start) else {
+            continue;
+        };
+
+        let current = &phoneme_words[phoneme_range.start];
+        let leading: String = current
+            .chars()
+            .take_while(|c| PUNCTUATION.contains(*c))
+            .collect();
+        let trailing: String = {
+            let mut chars: Vec<char> = current
+                .chars()
+                .rev()
+                .take_while(|c| PUNCTUATION.contains(*c))
+                .collect();
+            chars.reverse();
+            chars.into_iter().collect()
+        };
+        let first_sound = |s: &str| s.chars().find(|c| !PUNCTUATION.contains(*c) && *c != 'ˈ');
+        if first_sound(current) != first_sound(replacement) {
+            continue;
+        }
+        phoneme_words[phoneme_range.start] = format!("{}{}{}", leading, replacement, trailing);
+    }
+
+    phoneme_words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sentences with the expected pronunciation of their (first) heteronym.
+    const CORPUS: &[(&str, &str)] = &[
+        ("I want to read that book.", "ɹˈid"),
+        ("I have read that book.", "ɹˈɛd"),
+        ("She read it to me yesterday.", "ɹˈɛd"),
+        ("I read it last night.", "ɹˈɛd"),
+        ("Can you read this?", "ɹˈid"),
+        ("The book was read aloud.", "ɹˈɛd"),
+        ("Where do you live?", "lˈɪv"),
+        ("We are going to live stream tonight.", "lˈIv"),
+        ("The band plays live music.", "lˈIv"),
+        ("We go live in five minutes.", "lˈIv"),
+        ("They live in Berlin.", "lˈɪv"),
+        ("Our lives changed.", "lˈIvz"),
+        ("She lives nearby.", "lˈɪvz"),
+        ("You lead the team.", "lˈid"),
+        ("The pipe was made of lead.", "lˈɛd"),
+        ("Old lead paint is toxic.", "lˈɛd"),
+        ("Please record this call.", "ɹəkˈɔɹd"),
+        ("That was a world record.", "ɹˈɛkəɹd"),
+        ("We record every meeting.", "ɹəkˈɔɹd"),
+        ("She gave me a present.", "pɹˈɛzənt"),
+        ("I will present the results.", "pɹizˈɛnt"),
+        ("At present we are busy.", "pɹˈɛzənt"),
+        ("The wind is strong today.", "wˈɪnd"),
+        ("Don't forget to wind the clock.", "wˈInd"),
+        ("Wind up the toy.", "wˈInd"),
+        ("A tear rolled down her cheek.", "tˈɪɹ"),
+        ("Don't tear the paper.", "tˈɛɹ"),
+        ("It shows signs of wear and tear.", "tˈɛɹ"),
+        ("She was in tears.", "tˈɪɹz"),
+    ];
+
+    #[test]
+    fn test_corpus() {
+        for (sentence, expected) in CORPUS {
+            let overrides = disambiguate(sentence);
+            assert_eq!(
+                overrides.first().map(|(_, p)| *p),
+                Some(*expected),
+                "{sentence}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        // espeak reads "read" as present tense regardless of context
+        assert_eq!(
+            apply_overrides("I have read it.", "ˈaɪ hæv ɹˈiːd ɪt."),
+            "ˈaɪ hæv ɹˈɛd ɪt."
+        );
+        assert_eq!(
+            apply_overrides("Made of lead.", "mˈeɪd ʌv lˈiːd."),
+            "mˈeɪd ʌv lˈɛd."
+        );
+        // Misaligned words are left alone
+        assert_eq!(
+            apply_overrides("I have read it.", "ˈaɪ hæv ɪt."),
+            "ˈaɪ hæv ɪt."
+        );
+        assert_eq!(apply_overrides("No heteronyms here.", "nˈoʊ"), "nˈoʊ");
+    }
+}
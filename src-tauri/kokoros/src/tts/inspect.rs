@@ -0,0 +1,105 @@
+use crate::tts::vocab::Vocab;
+use serde::Serialize;
+
+/// Speaking time per phoneme at speed 1.0, roughly Kokoro's pace for English
+const SECONDS_PER_PHONEME: f32 = 0.075;
+
+/// Pause Kokoro tends to leave after a clause and after a sentence
+const CLAUSE_PAUSE: f32 = 0.2;
+const SENTENCE_PAUSE: f32 = 0.4;
+
+/// Symbols that modify the phoneme before them instead of being spoken
+const MODIFIERS: &str = "ˈˌːˑ";
+
+/// What synthesizing a text would do, worked out without running the model.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeechInspection {
+    pub input: String,
+    /// Text as the pipeline phonemizes it: split into sentences, trimmed and
+    /// terminated, then joined again
+    pub normalized_text: String,
+    pub model_id: String,
+    pub g2p: &'static str,
+    pub chunks: Vec<ChunkInspection>,
+    pub estimated_duration_secs: f32,
+}
+
+/// One chunk of a [`SpeechInspection`], inferred as a single model call.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkInspection {
+    pub text: String,
+    /// Character range of `text` in [`SpeechInspection::normalized_text`]
+    pub start: usize,
+    pub end: usize,
+    /// espeak language the chunk was phonemized in
+    pub language: String,
+    /// Voice or blend the chunk is spoken with
+    pub voice: String,
+    pub phonemes: String,
+    /// Token ids sent to the model, without the padding tokens
+    pub token_ids: Vec<i64>,
+    /// Phonemes the model's vocabulary doesn't have, which tokenization drops
+    pub dropped: Vec<DroppedSymbol>,
+    /// Row of the voice pack the style vector is taken from
+    pub style_index: usize,
+    pub estimated_duration_secs: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DroppedSymbol {
+    pub symbol: char,
+    /// Character position in the chunk's phonemes
+    pub position: usize,
+}
+
+/// Symbols of `phonemes` that `vocab` doesn't know, with their positions.
+pub fn dropped_symbols(phonemes: &str, vocab: &Vocab) -> Vec<DroppedSymbol> {
+    phonemes
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| !vocab.contains(*c))
+        .map(|(position, symbol)| DroppedSymbol { symbol, position })
+        .collect()
+}
+
+/// Rough speaking time of `phonemes` at `speed`, from the number of spoken
+/// phonemes and the pauses punctuation adds.
+pub fn estimate_duration(phonemes: &str, speed: f32) -> f32 {
+    let seconds: f32 = phonemes
+        .chars()
+        .map(|c| match c {
+            '.' | '!' | '?' | '…' => SENTENCE_PAUSE,
+            ',' | ';' | ':' | '—' => CLAUSE_PAUSE,
+            _ if c.is_whitespace() || MODIFIERS.contains(c) => 0.0,
+            _ if c.is_alphabetic() => SECONDS_PER_PHONEME,
+            _ => 0.0,
+        })
+        .sum();
+    seconds / speed.max(f32::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dropped_symbols() {
+        let vocab = Vocab::default();
+        assert_eq!(
+            dropped_symbols("hˈɛlO☺ wˈɜɹld", &vocab),
+            vec![DroppedSymbol {
+                symbol: '☺',
+                position: 5
+            }]
+        );
+        assert!(dropped_symbols("həlˈoʊ", &vocab).is_empty());
+    }
+
+    #[test]
+    fn test_estimate_duration() {
+        // Four phonemes and a sentence pause; stress marks and spaces are free
+        let normal = estimate_duration("hˈɛ lO.", 1.0);
+        assert!((normal - (4.0 * SECONDS_PER_PHONEME + SENTENCE_PAUSE)).abs() < 1e-6);
+        assert!((estimate_duration("hˈɛ lO.", 2.0) - normal / 2.0).abs() < 1e-6);
+    }
+}
@@ -1,27 +1,32 @@
-use crate::onn::ort_koko::{self};
-use crate::tts::tokenize::tokenize;
-use crate::utils;
+use crate::error::KokoError;
+use crate::onn::ort_koko::{self, BatchItem};
+use crate::onn::session_config::SessionConfig;
+use crate::tts::chunking::ChunkingPolicy;
+use crate::tts::g2p::{self, G2p, G2pKind};
+use crate::tts::inspect::{self, ChunkInspection, SpeechInspection};
+use crate::tts::langid;
+use crate::tts::model_config::{self, ModelConfig};
+use crate::tts::phoneme_chunks::{PhonemizedChunk, chunk_phonemized};
+use crate::tts::realtime::RealTimeFactor;
+use crate::tts::style_space::StyleSpace;
+use crate::tts::timings::{AudioWithTimings, TimingsBuilder};
+use crate::tts::voices::{self, VoiceBlend, VoiceInfo, VoiceLibrary, VoicePack, VoiceSource};
+use crate::tts::warmup::{self, WARMUP_LANG, WARMUP_TEXT, WarmupTimings};
 use crate::utils::debug::format_debug_prefix;
-use lazy_static::lazy_static;
-use ndarray::Array3;
-use ndarray_npy::NpzReader;
+use crate::utils::download::{Download, DownloadManager, DownloadOptions};
 use std::collections::HashMap;
-use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-
-use espeak_rs::text_to_phonemes;
-
-// Global mutex to serialize espeak-rs calls to prevent phoneme randomization
-// espeak-rs uses global state internally and is not thread-safe
-lazy_static! {
-    static ref ESPEAK_MUTEX: Mutex<()> = Mutex::new(());
-}
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
 
 // Flag to ensure voice styles are only logged once
 static VOICES_LOGGED: AtomicBool = AtomicBool::new(false);
 
+/// Chunks inferred per session run when the model can batch, see
+/// [`TTSKoko::set_batching`]
+const BATCH_SIZE: usize = 4;
+
 #[derive(Debug, Clone)]
 pub struct TTSOpts<'a> {
     pub txt: &'a str,
@@ -38,8 +43,16 @@ pub struct TTSKoko {
     #[allow(dead_code)]
     model_path: String,
     model: Arc<Mutex<ort_koko::OrtKoko>>,
-    styles: HashMap<String, Vec<[[f32; 256]; 1]>>,
+    styles: Arc<RwLock<VoiceLibrary>>,
+    custom_voices_path: PathBuf,
+    user_voices_dir: Arc<RwLock<Option<PathBuf>>>,
+    g2p: Arc<RwLock<Arc<dyn G2p>>>,
+    switch_voices: Arc<AtomicBool>,
+    model_config: Arc<ModelConfig>,
     init_config: InitConfig,
+    rtf: Arc<RealTimeFactor>,
+    /// See [`TTSKoko::set_batching`]
+    batching: Arc<AtomicBool>,
 }
 
 /// Parallel TTS with multiple ONNX instances for true concurrency
@@ -48,15 +61,57 @@ pub struct TTSKokoParallel {
     #[allow(dead_code)]
     model_path: String,
     models: Vec<Arc<Mutex<ort_koko::OrtKoko>>>,
-    styles: HashMap<String, Vec<[[f32; 256]; 1]>>,
+    styles: Arc<RwLock<VoiceLibrary>>,
+    custom_voices_path: PathBuf,
+    user_voices_dir: Arc<RwLock<Option<PathBuf>>>,
+    g2p: Arc<RwLock<Arc<dyn G2p>>>,
+    switch_voices: Arc<AtomicBool>,
+    model_config: Arc<ModelConfig>,
     init_config: InitConfig,
+    /// Speed of each model instance, in the order of `models`
+    rtfs: Vec<Arc<RealTimeFactor>>,
+}
+
+/// Style to infer a chunk with
+enum Style<'a> {
+    /// A voice or blend spec, resolved through [`TTSKoko::mix_styles`]
+    Named(&'a str),
+    /// A pack that isn't saved, such as a voice design candidate
+    Pack(&'a VoicePack),
+}
+
+/// A chunk ready for inference with the language and voice it was planned for
+struct PlannedChunk {
+    chunk: PhonemizedChunk,
+    lang: String,
+    voice: String,
+}
+
+/// Result of inferring a single text chunk
+struct ChunkOutput {
+    phonemes: String,
+    leading_silence: usize,
+    audio: Vec<f32>,
+    durations: Option<Vec<f32>>,
 }
 
 #[derive(Clone)]
 pub struct InitConfig {
     pub model_url: String,
     pub voices_url: String,
+    /// Model to load the vocabulary and limits of, from the model's file name when
+    /// unset; see [`ModelConfig::load`]
+    pub model_id: Option<String>,
     pub sample_rate: u32,
+    /// G2P backend to start with, see [`TTSKoko::set_g2p`]
+    pub g2p: G2pKind,
+    /// Whether detected foreign spans are spoken by a voice for their language,
+    /// off by default, see [`TTSKoko::set_switch_voices`]
+    pub switch_voices: bool,
+    /// ONNX Runtime options for every model instance
+    pub session: SessionConfig,
+    /// How missing model files are downloaded
+    pub download: DownloadOptions,
 }
 
 impl Default for InitConfig {
@@ -64,246 +119,391 @@ impl Default for InitConfig {
         Self {
             model_url: "https://github.com/thewh1teagle/kokoro-onnx/releases/download/model-files-v1.0/kokoro-v1.0.onnx".into(),
             voices_url: "https://github.com/thewh1teagle/kokoro-onnx/releases/download/model-files-v1.0/voices-v1.0.bin".into(),
+            model_id: None,
             sample_rate: 24000,
+            g2p: G2pKind::default(),
+            switch_voices: false,
+            session: SessionConfig::default(),
+            download: DownloadOptions::default(),
         }
     }
 }
 
 impl TTSKoko {
-    pub async fn new(model_path: &str, voices_path: &str) -> Self {
+    pub async fn new(model_path: &str, voices_path: &str) -> Result<Self, KokoError> {
         Self::from_config(model_path, voices_path, InitConfig::default()).await
     }
 
-    pub async fn from_config(model_path: &str, voices_path: &str, cfg: InitConfig) -> Self {
-        if !Path::new(model_path).exists() {
-            utils::fileio::download_file_from_url(cfg.model_url.as_str(), model_path)
-                .await
-                .expect("download model failed.");
-        }
-
-        if !Path::new(voices_path).exists() {
-            utils::fileio::download_file_from_url(cfg.voices_url.as_str(), voices_path)
-                .await
-                .expect("download voices data file failed.");
-        }
-
-        let model = Arc::new(Mutex::new(
-            ort_koko::OrtKoko::new(model_path.to_string())
-                .expect("Failed to create Kokoro TTS model"),
-        ));
+    pub async fn from_config(
+        model_path: &str,
+        voices_path: &str,
+        cfg: InitConfig,
+    ) -> Result<Self, KokoError> {
+        Self::fetch_files(model_path, voices_path, &cfg).await?;
+        let model_config = Self::load_model_config(model_path, &cfg).await?;
+        let model = Arc::new(Mutex::new(ort_koko::OrtKoko::with_config(
+            model_path.to_string(),
+            &cfg.session,
+        )?));
         // TODO: if(not streaming) { model.print_info(); }
         // model.print_info();
 
-        let styles = Self::load_voices(voices_path);
+        let mut styles = VoiceLibrary::default();
+        styles.extend(Self::load_voices(voices_path)?, VoiceSource::Builtin);
+        let custom_voices_path = voices::custom_voices_path(voices_path);
+        Self::load_custom_voices(&custom_voices_path, &mut styles);
 
-        TTSKoko {
+        Ok(TTSKoko {
             model_path: model_path.to_string(),
             model,
-            styles,
+            styles: Arc::new(RwLock::new(styles)),
+            custom_voices_path,
+            user_voices_dir: Arc::new(RwLock::new(None)),
+            g2p: Arc::new(RwLock::new(cfg.g2p.backend())),
+            switch_voices: Arc::new(AtomicBool::new(cfg.switch_voices)),
+            model_config: Arc::new(model_config),
             init_config: cfg,
+            rtf: Arc::new(RealTimeFactor::default()),
+            batching: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Downloads the model and voices when they aren't there yet, unless
+    /// downloads are off.
+    async fn fetch_files(
+        model_path: &str,
+        voices_path: &str,
+        cfg: &InitConfig,
+    ) -> Result<(), KokoError> {
+        let downloads = DownloadManager::new(cfg.download.clone())?;
+        for (url, path) in [(&cfg.model_url, model_path), (&cfg.voices_url, voices_path)] {
+            let path = Path::new(path);
+            downloads
+                .fetch(&Download::unverified(url, path), path)
+                .await?;
         }
+        Ok(())
     }
 
-    fn split_text_into_chunks(&self, text: &str, max_tokens: usize) -> Vec<String> {
-        let mut chunks = Vec::new();
+    /// Vocabulary and limits of the model, downloading the config of a known model
+    /// that needs one when it isn't next to the model yet.
+    async fn load_model_config(
+        model_path: &str,
+        cfg: &InitConfig,
+    ) -> Result<ModelConfig, KokoError> {
+        let model_id = cfg
+            .model_id
+            .clone()
+            .unwrap_or_else(|| model_config::model_id_from_path(model_path));
+        let missing_url = ModelConfig::config_url(&model_id)
+            .filter(|_| ModelConfig::find_config(model_path).is_none());
+        if let Some(url) = missing_url {
+            let config_path = ModelConfig::default_config_path(model_path);
+            DownloadManager::new(cfg.download.clone())?
+                .fetch(&Download::unverified(url, &config_path), &config_path)
+                .await?;
+        }
+        ModelConfig::load(model_path, cfg.model_id.as_deref())
+            .map_err(|e| KokoError::ModelLoad(format!("Failed to load Kokoro model config: {}", e)))
+    }
 
-        // First split by sentences - using common sentence ending punctuation
-        let sentences: Vec<&str> = text
-            .split(|c| c == '.' || c == '?' || c == '!' || c == ';')
-            .filter(|s| !s.trim().is_empty())
-            .collect();
+    /// The loaded model's id, vocabulary and token limit.
+    pub fn model_config(&self) -> &ModelConfig {
+        &self.model_config
+    }
 
-        let mut current_chunk = String::new();
+    /// How fast this instance has been synthesizing, measured on every chunk.
+    pub fn real_time_factor(&self) -> &RealTimeFactor {
+        &self.rtf
+    }
 
-        for sentence in sentences {
-            // Clean up the sentence and add back punctuation
-            let sentence = format!("{}.", sentence.trim());
+    /// Switches the G2P backend used by this instance and every clone of it.
+    pub fn set_g2p(&self, kind: G2pKind) {
+        tracing::info!("Using {:?} G2P backend", kind);
+        *self.g2p.write().unwrap() = kind.backend();
+    }
 
-            // Convert to phonemes to check token count
-            let sentence_phonemes = {
-                let _guard = ESPEAK_MUTEX.lock().unwrap();
-                text_to_phonemes(&sentence, "en", None, true, false)
-                    .unwrap_or_default()
-                    .join("")
-            };
-            let token_count = tokenize(&sentence_phonemes).len();
-
-            if token_count > max_tokens {
-                // If single sentence is too long, split by words
-                let words: Vec<&str> = sentence.split_whitespace().collect();
-                let mut word_chunk = String::new();
-
-                for word in words {
-                    let test_chunk = if word_chunk.is_empty() {
-                        word.to_string()
-                    } else {
-                        format!("{} {}", word_chunk, word)
-                    };
+    pub fn g2p_name(&self) -> &'static str {
+        self.g2p.read().unwrap().name()
+    }
 
-                    let test_phonemes = {
-                        let _guard = ESPEAK_MUTEX.lock().unwrap();
-                        text_to_phonemes(&test_chunk, "en", None, true, false)
-                            .unwrap_or_default()
-                            .join("")
-                    };
-                    let test_tokens = tokenize(&test_phonemes).len();
-
-                    if test_tokens > max_tokens {
-                        if !word_chunk.is_empty() {
-                            chunks.push(word_chunk);
-                        }
-                        word_chunk = word.to_string();
-                    } else {
-                        word_chunk = test_chunk;
-                    }
-                }
+    /// Whether text detected as another language (with `lan` set to
+    /// [`langid::AUTO`]) is spoken by a voice for that language, when one is
+    /// installed, instead of the requested voice with a foreign accent.
+    pub fn set_switch_voices(&self, switch_voices: bool) {
+        self.switch_voices.store(switch_voices, Ordering::Relaxed);
+    }
 
-                if !word_chunk.is_empty() {
-                    chunks.push(word_chunk);
-                }
-            } else if !current_chunk.is_empty() {
-                // Try to append to current chunk
-                let test_text = format!("{} {}", current_chunk, sentence);
-                let test_phonemes = {
-                    let _guard = ESPEAK_MUTEX.lock().unwrap();
-                    text_to_phonemes(&test_text, "en", None, true, false)
-                        .unwrap_or_default()
-                        .join("")
-                };
-                let test_tokens = tokenize(&test_phonemes).len();
-
-                if test_tokens > max_tokens {
-                    // If combining would exceed limit, start new chunk
-                    chunks.push(current_chunk);
-                    current_chunk = sentence;
-                } else {
-                    current_chunk = test_text;
-                }
-            } else {
-                current_chunk = sentence;
+    /// Whether [`TTSKoko::tts_raw_audio`] infers several chunks per session run.
+    ///
+    /// Off by default: padded items run without an attention mask, and exports
+    /// without a `durations` output get their chunks' trailing pauses trimmed,
+    /// so turn it on only where `examples/batch_bench.rs` shows a gain. A call
+    /// whose batch fails, e.g. on a model that can't batch, infers its chunks
+    /// one at a time instead and leaves the setting alone.
+    pub fn set_batching(&self, batching: bool) {
+        self.batching.store(batching, Ordering::Relaxed);
+    }
+
+    pub fn batching(&self) -> bool {
+        self.batching.load(Ordering::Relaxed)
+    }
+
+    /// Preloads the G2P backend and synthesizes a short dummy text, so the
+    /// first real request doesn't pay for loading language data and
+    /// initializing the ONNX graph. The warm-up run isn't counted in
+    /// [`TTSKoko::real_time_factor`].
+    pub fn warm_up(&self) -> Result<WarmupTimings, KokoError> {
+        let g2p = self.g2p.read().unwrap().clone();
+        let phonemizer =
+            warmup::preload_phonemizer(g2p.as_ref()).map_err(KokoError::Phonemization)?;
+
+        let voices = self.get_available_voices();
+        let voice = warmup::warmup_voice(&voices)
+            .ok_or_else(|| KokoError::VoiceNotFound(warmup::WARMUP_VOICE.to_string()))?;
+        // Measured into a throwaway recorder: real requests may already be
+        // running and recording into the shared one
+        let unmeasured = TTSKoko {
+            rtf: Arc::new(RealTimeFactor::default()),
+            ..self.clone()
+        };
+        let start = Instant::now();
+        unmeasured.tts_raw_audio(
+            WARMUP_TEXT,
+            WARMUP_LANG,
+            voice,
+            1.0,
+            None,
+            None,
+            Some("warmup"),
+            None,
+        )?;
+        let instance = start.elapsed();
+
+        Ok(WarmupTimings::new(phonemizer, &[instance]))
+    }
+
+    /// Splits `txt` into chunks that fit the model's token limit, each with the
+    /// voice to speak it in.
+    ///
+    /// With `lan` set to [`langid::AUTO`] the text is first split into language
+    /// spans, see [`langid::detect_spans`], and each span is phonemized in its own
+    /// language. The requested voice's language is the default.
+    fn plan_chunks(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+    ) -> Result<Vec<PlannedChunk>, KokoError> {
+        let max_tokens = self.model_config.max_chunk_tokens();
+        let spans = if lan == langid::AUTO {
+            let default_lang = voices::voice_language(style_name).unwrap_or("en-us");
+            langid::detect_spans(txt, default_lang)
+                .into_iter()
+                .map(|span| {
+                    let voice = self.span_voice(style_name, span.lang);
+                    (span.text, span.lang, voice)
+                })
+                .collect()
+        } else {
+            vec![(txt.to_string(), lan, style_name.to_string())]
+        };
+
+        let mut planned = Vec::new();
+        for (text, lang, voice) in spans {
+            for chunk in self.split_text_into_chunks(&text, lang, max_tokens)? {
+                planned.push(PlannedChunk {
+                    chunk,
+                    lang: lang.to_string(),
+                    voice: voice.clone(),
+                });
             }
         }
+        Ok(planned)
+    }
 
-        // Add the last chunk if not empty
-        if !current_chunk.is_empty() {
-            chunks.push(current_chunk);
+    /// Runs everything up to inference for `txt` and reports what the model would
+    /// get: chunks, phonemes, token ids, dropped symbols and style rows, with an
+    /// estimate of the audio's length.
+    pub fn inspect_speech(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        initial_silence: Option<usize>,
+    ) -> Result<SpeechInspection, KokoError> {
+        let vocab = &self.model_config.vocab;
+        let mut normalized_text = String::new();
+        let mut chunks = Vec::new();
+
+        for PlannedChunk { chunk, lang, voice } in self.plan_chunks(txt, lan, style_name)? {
+            if !normalized_text.is_empty() {
+                normalized_text.push(' ');
+            }
+            let start = normalized_text.chars().count();
+            normalized_text.push_str(&chunk.text);
+
+            // Same tokens infer_chunk sends, before padding
+            let mut token_ids = vec![30; initial_silence.unwrap_or(0)];
+            token_ids.extend(vocab.tokenize(&chunk.phonemes));
+
+            chunks.push(ChunkInspection {
+                start,
+                end: normalized_text.chars().count(),
+                language: lang,
+                style_index: self.style_index(&voice, token_ids.len())?,
+                voice,
+                dropped: inspect::dropped_symbols(&chunk.phonemes, vocab),
+                estimated_duration_secs: inspect::estimate_duration(&chunk.phonemes, speed),
+                token_ids,
+                phonemes: chunk.phonemes,
+                text: chunk.text,
+            });
         }
 
-        chunks
+        Ok(SpeechInspection {
+            input: txt.to_string(),
+            normalized_text,
+            model_id: self.model_config.id.clone(),
+            g2p: self.g2p_name(),
+            estimated_duration_secs: chunks.iter().map(|c| c.estimated_duration_secs).sum(),
+            chunks,
+        })
     }
 
-    /// Smart word-based chunking for async streaming
-    /// Creates chunks based on natural speech boundaries using word count and punctuation
-    pub fn split_text_into_speech_chunks(&self, text: &str, max_words: usize) -> Vec<String> {
-        let mut chunks = Vec::new();
+    /// Row of the voice pack [`TTSKoko::mix_styles`] takes for `tokens_len` tokens.
+    fn style_index(&self, style_name: &str, tokens_len: usize) -> Result<usize, KokoError> {
+        let styles = self.styles.read().unwrap();
+        let rows = match styles.packs.get(style_name) {
+            Some(pack) => pack.len(),
+            None => VoiceBlend::parse(style_name)?
+                .components
+                .iter()
+                .map(|(name, _)| {
+                    styles
+                        .packs
+                        .get(name)
+                        .map(|pack| pack.len())
+                        .ok_or_else(|| KokoError::VoiceNotFound(name.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .min()
+                .unwrap_or(0),
+        };
+        Ok(voices::style_index(rows, tokens_len))
+    }
 
-        // Split by sentence-ending punctuation first
-        let sentences: Vec<&str> = text
-            .split(|c| c == '.' || c == '!' || c == '?')
-            .filter(|s| !s.trim().is_empty())
-            .collect();
+    /// Voice for a span in `lang` when the request asked for `style_name`.
+    fn span_voice(&self, style_name: &str, lang: &str) -> String {
+        if !self.switch_voices.load(Ordering::Relaxed) {
+            return style_name.to_string();
+        }
+        voices::compatible_voice(style_name, lang, &self.get_available_voices())
+            .unwrap_or_else(|| style_name.to_string())
+    }
 
-        for sentence in sentences {
-            let sentence = sentence.trim();
-            if sentence.is_empty() {
-                continue;
-            }
+    /// Splits `text` into chunks that fit the model's token limit, phonemizing all
+    /// sentences in one batch (see [`chunk_phonemized`]).
+    fn split_text_into_chunks(
+        &self,
+        text: &str,
+        lan: &str,
+        max_tokens: usize,
+    ) -> Result<Vec<PhonemizedChunk>, KokoError> {
+        let g2p = Arc::clone(&self.g2p.read().unwrap());
+        chunk_phonemized(text, max_tokens, &self.model_config.vocab, |sentences| {
+            let sentences: Vec<&str> = sentences.iter().map(String::as_str).collect();
+            g2p::phonemize_batch(g2p.as_ref(), &sentences, lan)
+        })
+        .map_err(KokoError::Phonemization)
+    }
 
-            // Count words in this sentence
-            let words: Vec<&str> = sentence.split_whitespace().collect();
-            let word_count = words.len();
-
-            if word_count <= max_words {
-                // Small sentence - add as complete chunk (preserve original punctuation)
-                chunks.push(format!("{}.", sentence));
-            } else {
-                // Large sentence - split by punctuation marks while preserving them
-                let mut sub_clauses = Vec::new();
-                let mut current_pos = 0;
-
-                for (i, ch) in sentence.char_indices() {
-                    if ch == ',' || ch == ';' || ch == ':' {
-                        if i > current_pos {
-                            let clause_with_punct = format!("{}{}", &sentence[current_pos..i], ch);
-                            sub_clauses.push(clause_with_punct);
-                        }
-                        current_pos = i + 1;
-                    }
-                }
+    /// Smart word-based chunking for async streaming, with the default
+    /// [`ChunkingPolicy`] aiming for `max_words` words per chunk
+    pub fn split_text_into_speech_chunks(&self, text: &str, max_words: usize) -> Vec<String> {
+        ChunkingPolicy::with_target_words(max_words).split(text)
+    }
 
-                // Add remaining text
-                if current_pos < sentence.len() {
-                    sub_clauses.push(sentence[current_pos..].to_string());
-                }
+    /// Infers a single phonemized chunk that already fits the model's token limit.
+    fn infer_chunk(
+        &self,
+        chunk: &PhonemizedChunk,
+        style: Style,
+        speed: f32,
+        initial_silence: Option<usize>,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+        chunk_number: Option<usize>,
+    ) -> Result<ChunkOutput, KokoError> {
+        let PhonemizedChunk { text, phonemes } = chunk;
+        let debug_prefix = format_debug_prefix(request_id, instance_id);
+        let chunk_info = chunk_number
+            .map(|n| format!("Chunk: {}, ", n))
+            .unwrap_or_default();
+        tracing::debug!(
+            "{} {}text: '{}' -> phonemes: '{}'",
+            debug_prefix,
+            chunk_info,
+            text,
+            phonemes
+        );
+        let mut tokens = self.model_config.vocab.tokenize(phonemes);
 
-                let sub_clauses: Vec<&str> = sub_clauses
-                    .iter()
-                    .map(|s| s.trim())
-                    .filter(|s| !s.is_empty())
-                    .collect();
-
-                let mut current_chunk = String::new();
-                let mut current_word_count = 0;
-
-                for clause in sub_clauses {
-                    let clause = clause.trim();
-                    let clause_words: Vec<&str> = clause.split_whitespace().collect();
-                    let clause_word_count = clause_words.len();
-
-                    if current_word_count + clause_word_count <= max_words {
-                        // Add clause to current chunk (preserve original punctuation)
-                        if current_chunk.is_empty() {
-                            current_chunk = clause.to_string();
-                        } else {
-                            current_chunk = format!("{} {}", current_chunk, clause);
-                        }
-                        current_word_count += clause_word_count;
-                    } else {
-                        // Start new chunk (preserve original punctuation)
-                        if !current_chunk.is_empty() {
-                            chunks.push(current_chunk);
-                        }
-                        current_chunk = clause.to_string();
-                        current_word_count = clause_word_count;
-                    }
-                }
+        let leading_silence = initial_silence.unwrap_or(0);
+        for _ in 0..leading_silence {
+            tokens.insert(0, 30);
+        }
 
-                // Add final chunk (preserve original punctuation)
-                if !current_chunk.is_empty() {
-                    chunks.push(current_chunk);
-                }
-            }
+        // Get style vectors once
+        let styles = match style {
+            Style::Named(style_name) => self.mix_styles(style_name, tokens.len())?,
+            Style::Pack(pack) => vec![voices::style_row(pack, tokens.len()).to_vec()],
+        };
+
+        // pad a 0 to start and end of tokens
+        let mut padded_tokens = vec![0];
+        for &token in &tokens {
+            padded_tokens.push(token);
         }
+        padded_tokens.push(0);
 
-        // If no sentences found, fall back to word-based chunking
-        if chunks.is_empty() {
-            let words: Vec<&str> = text.split_whitespace().collect();
-            let mut current_chunk = String::new();
-            let mut current_word_count = 0;
-
-            for word in words {
-                if current_word_count + 1 <= max_words {
-                    if current_chunk.is_empty() {
-                        current_chunk = word.to_string();
-                    } else {
-                        current_chunk = format!("{} {}", current_chunk, word);
-                    }
-                    current_word_count += 1;
-                } else {
-                    if !current_chunk.is_empty() {
-                        chunks.push(current_chunk);
-                    }
-                    current_chunk = word.to_string();
-                    current_word_count = 1;
-                }
-            }
+        let tokens = vec![padded_tokens];
 
-            if !current_chunk.is_empty() {
-                chunks.push(current_chunk);
+        let start = Instant::now();
+        match self.model.lock().unwrap().infer_with_durations(
+            tokens,
+            styles,
+            speed,
+            request_id,
+            instance_id,
+            chunk_number,
+        ) {
+            Ok((chunk_audio, durations)) => {
+                self.rtf.record(
+                    start.elapsed(),
+                    chunk_audio.len(),
+                    self.init_config.sample_rate,
+                );
+                Ok(ChunkOutput {
+                    phonemes: phonemes.clone(),
+                    leading_silence,
+                    audio: chunk_audio.iter().cloned().collect(),
+                    durations,
+                })
+            }
+            Err(e) => {
+                tracing::error!(
+                    "{} {}processing failed for text {:?}: {}",
+                    debug_prefix,
+                    chunk_info,
+                    text,
+                    e
+                );
+                Err(e)
             }
         }
-
-        chunks
     }
 
     pub fn tts_raw_audio(
@@ -316,72 +516,134 @@ impl TTSKoko {
         request_id: Option<&str>,
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
-    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<f32>, KokoError> {
         // Split text into appropriate chunks
-        let chunks = self.split_text_into_chunks(txt, 500); // Using 500 to leave 12 tokens of margin
-        let mut final_audio = Vec::new();
+        let chunks = self.plan_chunks(txt, lan, style_name)?;
 
-        for chunk in chunks {
-            // Convert chunk to phonemes
-            let phonemes = {
-                let _guard = ESPEAK_MUTEX.lock().unwrap();
-                text_to_phonemes(&chunk, lan, None, true, false)
-                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?
-                    .join("")
-            };
-            let debug_prefix = format_debug_prefix(request_id, instance_id);
-            let chunk_info = chunk_number
-                .map(|n| format!("Chunk: {}, ", n))
-                .unwrap_or_default();
-            tracing::debug!(
-                "{} {}text: '{}' -> phonemes: '{}'",
-                debug_prefix,
-                chunk_info,
-                chunk,
-                phonemes
-            );
-            let mut tokens = tokenize(&phonemes);
-
-            for _ in 0..initial_silence.unwrap_or(0) {
-                tokens.insert(0, 30);
+        if chunks.len() > 1 && self.batching.load(Ordering::Relaxed) {
+            let items = self.batch_items(&chunks, speed, initial_silence)?;
+            match self.infer_batched(items, request_id, instance_id) {
+                Ok(audio) => return Ok(audio),
+                Err(e) => {
+                    tracing::warn!(
+                        "Batched inference failed, inferring chunks one at a time: {}",
+                        e
+                    );
+                }
             }
+        }
 
-            // Get style vectors once
-            let styles = self.mix_styles(style_name, tokens.len())?;
+        let mut final_audio = Vec::new();
+        for PlannedChunk { chunk, voice, .. } in chunks {
+            let output = self.infer_chunk(
+                &chunk,
+                Style::Named(&voice),
+                speed,
+                initial_silence,
+                request_id,
+                instance_id,
+                chunk_number,
+            )?;
+            final_audio.extend_from_slice(&output.audio);
+        }
 
-            // pad a 0 to start and end of tokens
-            let mut padded_tokens = vec![0];
-            for &token in &tokens {
-                padded_tokens.push(token);
-            }
-            padded_tokens.push(0);
+        Ok(final_audio)
+    }
 
-            let tokens = vec![padded_tokens];
+    /// Model inputs of each chunk, as [`TTSKoko::infer_chunk`] would build them.
+    fn batch_items(
+        &self,
+        chunks: &[PlannedChunk],
+        speed: f32,
+        initial_silence: Option<usize>,
+    ) -> Result<Vec<BatchItem>, KokoError> {
+        chunks
+            .iter()
+            .map(|PlannedChunk { chunk, voice, .. }| {
+                let mut tokens = vec![30; initial_silence.unwrap_or(0)];
+                tokens.extend(self.model_config.vocab.tokenize(&chunk.phonemes));
+                let style = self.mix_styles(voice, tokens.len())?.remove(0);
+
+                // pad a 0 to start and end of tokens
+                tokens.insert(0, 0);
+                tokens.push(0);
+                Ok(BatchItem {
+                    tokens,
+                    style,
+                    speed,
+                })
+            })
+            .collect()
+    }
 
-            match self.model.lock().unwrap().infer(
-                tokens,
-                styles.clone(),
+    /// Infers `items` [`BATCH_SIZE`] at a time and joins their audio in order.
+    fn infer_batched(
+        &self,
+        items: Vec<BatchItem>,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+    ) -> Result<Vec<f32>, KokoError> {
+        let mut audio = Vec::new();
+        let mut items = items.into_iter().peekable();
+        while items.peek().is_some() {
+            let batch: Vec<BatchItem> = items.by_ref().take(BATCH_SIZE).collect();
+            let start = Instant::now();
+            let outputs = self
+                .model
+                .lock()
+                .unwrap()
+                .infer_batch(batch, request_id, instance_id)?;
+            let samples = outputs.iter().map(|output| output.audio.len()).sum();
+            self.rtf
+                .record(start.elapsed(), samples, self.init_config.sample_rate);
+            for output in outputs {
+                audio.extend(output.audio);
+            }
+        }
+        Ok(audio)
+    }
+
+    /// Like [`TTSKoko::tts_raw_audio`], but also returns when each word and phoneme
+    /// is spoken.
+    ///
+    /// Timings come from the model's per-token `durations` output when the loaded
+    /// export provides it, and are spread evenly over each chunk's audio otherwise
+    /// (flagged by [`AudioWithTimings::estimated`]).
+    pub fn tts_raw_audio_with_timings(
+        &self,
+        txt: &str,
+        lan: &str,
+        style_name: &str,
+        speed: f32,
+        initial_silence: Option<usize>,
+        request_id: Option<&str>,
+        instance_id: Option<&str>,
+        chunk_number: Option<usize>,
+    ) -> Result<AudioWithTimings, KokoError> {
+        let chunks = self.plan_chunks(txt, lan, style_name)?;
+        let mut timings =
+            TimingsBuilder::new(self.init_config.sample_rate, &self.model_config.vocab);
+
+        for PlannedChunk { chunk, voice, .. } in chunks {
+            let output = self.infer_chunk(
+                &chunk,
+                Style::Named(&voice),
                 speed,
+                initial_silence,
                 request_id,
                 instance_id,
                 chunk_number,
-            ) {
-                Ok(chunk_audio) => {
-                    let chunk_audio: Vec<f32> = chunk_audio.iter().cloned().collect();
-                    final_audio.extend_from_slice(&chunk_audio);
-                }
-                Err(e) => {
-                    eprintln!("Error processing chunk: {:?}", e);
-                    eprintln!("Chunk text was: {:?}", chunk);
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Chunk processing failed: {:?}", e),
-                    )));
-                }
-            }
+            )?;
+            timings.push_chunk(
+                &chunk.text,
+                &output.phonemes,
+                output.leading_silence,
+                output.durations.as_deref(),
+                &output.audio,
+            );
         }
 
-        Ok(final_audio)
+        Ok(timings.finish())
     }
 
     /// Streaming version that yields audio chunks as they're generated
@@ -396,72 +658,25 @@ impl TTSKoko {
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
         mut chunk_callback: F,
-    ) -> Result<(), Box<dyn std::error::Error>>
+    ) -> Result<(), KokoError>
     where
-        F: FnMut(Vec<f32>) -> Result<(), Box<dyn std::error::Error>>,
+        F: FnMut(Vec<f32>) -> Result<(), KokoError>,
     {
         // Split text into appropriate chunks
-        let chunks = self.split_text_into_chunks(txt, 500); // Using 500 to leave 12 tokens of margin
-
-        for chunk in chunks {
-            // Convert chunk to phonemes
-            let phonemes = {
-                let _guard = ESPEAK_MUTEX.lock().unwrap();
-                text_to_phonemes(&chunk, lan, None, true, false)
-                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?
-                    .join("")
-            };
-            let debug_prefix = format_debug_prefix(request_id, instance_id);
-            let chunk_info = chunk_number
-                .map(|n| format!("Chunk: {}, ", n))
-                .unwrap_or_default();
-            tracing::debug!(
-                "{} {}text: '{}' -> phonemes: '{}'",
-                debug_prefix,
-                chunk_info,
-                chunk,
-                phonemes
-            );
-            let mut tokens = tokenize(&phonemes);
-
-            for _ in 0..initial_silence.unwrap_or(0) {
-                tokens.insert(0, 30);
-            }
-
-            // Get style vectors once
-            let styles = self.mix_styles(style_name, tokens.len())?;
-
-            // pad a 0 to start and end of tokens
-            let mut padded_tokens = vec![0];
-            for &token in &tokens {
-                padded_tokens.push(token);
-            }
-            padded_tokens.push(0);
-
-            let tokens = vec![padded_tokens];
+        let chunks = self.plan_chunks(txt, lan, style_name)?;
 
-            match self.model.lock().unwrap().infer(
-                tokens,
-                styles.clone(),
+        for PlannedChunk { chunk, voice, .. } in chunks {
+            let output = self.infer_chunk(
+                &chunk,
+                Style::Named(&voice),
                 speed,
+                initial_silence,
                 request_id,
                 instance_id,
                 chunk_number,
-            ) {
-                Ok(chunk_audio) => {
-                    let chunk_audio: Vec<f32> = chunk_audio.iter().cloned().collect();
-                    // Yield this chunk via callback
-                    chunk_callback(chunk_audio)?;
-                }
-                Err(e) => {
-                    eprintln!("Error processing chunk: {:?}", e);
-                    eprintln!("Chunk text was: {:?}", chunk);
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Chunk processing failed: {:?}", e),
-                    )));
-                }
-            }
+            )?;
+            // Yield this chunk via callback
+            chunk_callback(output.audio)?;
         }
 
         Ok(())
@@ -478,7 +693,7 @@ impl TTSKoko {
             speed,
             initial_silence,
         }: TTSOpts,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), KokoError> {
         let audio = self.tts_raw_audio(
             &txt,
             lan,
@@ -519,77 +734,268 @@ impl TTSKoko {
             }
             writer.finalize()?;
         }
-        eprintln!("Audio saved to {}", save_path);
+        tracing::info!("Audio saved to {}", save_path);
         Ok(())
     }
 
+    /// Style vector for `style_name`, which is either a voice name or a blend such as
+    /// `af_heart:0.75+af_aoede:0.25` (see [`VoiceBlend`]).
     pub fn mix_styles(
         &self,
         style_name: &str,
         tokens_len: usize,
-    ) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
-        if !style_name.contains("+") {
-            if let Some(style) = self.styles.get(style_name) {
-                if tokens_len >= style.len() {
-                    let effective_tokens_len = style.len().saturating_sub(1).min(tokens_len);
-                    let styles = vec![style[effective_tokens_len][0].to_vec()];
-                    return Ok(styles);
-                }
+    ) -> Result<Vec<Vec<f32>>, KokoError> {
+        let styles = self.styles.read().unwrap();
+        if let Some(style) = styles.packs.get(style_name) {
+            return Ok(vec![voices::style_row(style, tokens_len).to_vec()]);
+        }
 
-                let styles = vec![style[tokens_len][0].to_vec()];
-                Ok(styles)
-            } else {
-                Err(format!("can not found from styles_map: {}", style_name).into())
-            }
-        } else {
-            eprintln!("parsing style mix");
-            let styles: Vec<&str> = style_name.split('+').collect();
+        let blend = VoiceBlend::parse(style_name)?;
+        tracing::debug!("blending styles: {:?}", blend.components);
+        Ok(vec![blend.blend_row(&styles.packs, tokens_len)?])
+    }
 
-            let mut style_names = Vec::new();
-            let mut style_portions = Vec::new();
+    /// Blends whole voice packs according to `spec`, optionally rescaling the
+    /// weights to sum to 1. Unknown voices are an error.
+    pub fn blend_voices(&self, spec: &str, normalize: bool) -> Result<VoicePack, KokoError> {
+        let mut blend = VoiceBlend::parse(spec)?;
+        if normalize {
+            blend = blend.normalized()?;
+        }
+        blend.blend_pack(&self.styles.read().unwrap().packs)
+    }
 
-            for style in styles {
-                if let Some((name, portion)) = style.split_once('.') {
-                    if let Ok(portion) = portion.parse::<f32>() {
-                        style_names.push(name);
-                        style_portions.push(portion * 0.1);
-                    }
-                }
+    /// Principal axes of the built-in voices' style space (see [`StyleSpace`]).
+    ///
+    /// Custom and imported voices are left out so designed voices don't shift the axes.
+    pub fn style_space(&self) -> Result<StyleSpace, KokoError> {
+        let builtin = self
+            .styles
+            .read()
+            .unwrap()
+            .voices_from(VoiceSource::Builtin);
+        StyleSpace::analyze(&builtin).map_err(KokoError::InvalidVoice)
+    }
+
+    /// Generates a voice from `base` (a voice name or blend spec) moved along the
+    /// axes of [`TTSKoko::style_space`] by `offsets`, in units of each axis' scale.
+    pub fn design_voice(
+        &self,
+        base: &str,
+        offsets: &HashMap<String, f32>,
+    ) -> Result<VoicePack, KokoError> {
+        let base = self.blend_voices(base, false)?;
+        self.style_space()?
+            .generate(&base, offsets)
+            .map_err(KokoError::InvalidVoice)
+    }
+
+    /// Synthesizes `txt` with a voice pack that doesn't have to be saved, e.g. to
+    /// audition [`TTSKoko::design_voice`] candidates.
+    pub fn preview_voice(
+        &self,
+        txt: &str,
+        lan: &str,
+        pack: &VoicePack,
+        speed: f32,
+    ) -> Result<Vec<f32>, KokoError> {
+        let mut audio = Vec::new();
+        for chunk in self.split_text_into_chunks(txt, lan, self.model_config.max_chunk_tokens())? {
+            let output =
+                self.infer_chunk(&chunk, Style::Pack(pack), speed, None, None, None, None)?;
+            audio.extend_from_slice(&output.audio);
+        }
+        Ok(audio)
+    }
+
+    /// Blends `spec` and saves the result as a new voice called `name`.
+    ///
+    /// Custom voices are persisted next to the voices file and show up in
+    /// [`TTSKoko::get_available_voices`] right away. Saving under the name of an
+    /// existing custom voice replaces it; built-in voices can't be overwritten.
+    pub fn save_voice_blend(
+        &self,
+        name: &str,
+        spec: &str,
+        normalize: bool,
+    ) -> Result<(), KokoError> {
+        let pack = self.blend_voices(spec, normalize)?;
+        self.save_custom_voice(name, pack)
+    }
+
+    /// Persists `pack` as a custom voice called `name`.
+    pub fn save_custom_voice(&self, name: &str, pack: VoicePack) -> Result<(), KokoError> {
+        voices::validate_voice_name(name)?;
+
+        let mut styles = self.styles.write().unwrap();
+        if let Some(source) = styles.source(name).filter(|s| *s != VoiceSource::Custom) {
+            return Err(KokoError::InvalidVoice(format!(
+                "'{}' is a {:?} voice and can't be replaced",
+                name, source
+            )));
+        }
+
+        let mut custom = styles.voices_from(VoiceSource::Custom);
+        custom.insert(name.to_string(), pack.clone());
+        voices::write_npz_voices(&self.custom_voices_path, &custom)?;
+        styles.insert(name.to_string(), pack, VoiceSource::Custom);
+
+        tracing::info!(
+            "Saved custom voice '{}' to {}",
+            name,
+            self.custom_voices_path.display()
+        );
+        Ok(())
+    }
+
+    /// Sets the directory imported voices are persisted in and loads the ones
+    /// imported in earlier sessions. Returns how many were loaded.
+    pub fn load_user_voices(&self, dir: impl Into<PathBuf>) -> Result<usize, KokoError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir.join(voices::IMPORTED_VOICES_FILE);
+        let imported = if path.exists() {
+            voices::read_npz_voices(&path)?
+        } else {
+            HashMap::new()
+        };
+        let count = imported.len();
+
+        let mut styles = self.styles.write().unwrap();
+        for (name, pack) in imported {
+            if styles
+                .source(&name)
+                .is_some_and(|s| s != VoiceSource::Imported)
+            {
+                tracing::warn!("Skipping imported voice '{}': name already in use", name);
+                continue;
             }
-            eprintln!("styles: {:?}, portions: {:?}", style_names, style_portions);
+            styles.insert(name, pack, VoiceSource::Imported);
+        }
+        *self.user_voices_dir.write().unwrap() = Some(dir);
 
-            let mut blended_style = vec![vec![0.0; 256]; 1];
+        tracing::info!("Imported voices loaded: {}", count);
+        Ok(count)
+    }
 
-            for (name, portion) in style_names.iter().zip(style_portions.iter()) {
-                if let Some(style) = self.styles.get(*name) {
-                    let style_slice = &style[tokens_len][0]; // This is a [256] array
-                    // Blend into the blended_style
-                    for j in 0..256 {
-                        blended_style[0][j] += style_slice[j] * portion;
-                    }
-                }
+    /// Imports the voices in a `.npy`, `.npz` or `.bin` file (see [`voices::read_voice_file`]).
+    ///
+    /// `name` renames a single-voice file. Imported voices replace earlier imports of the
+    /// same name but never built-in or custom voices, and are persisted in the directory
+    /// set by [`TTSKoko::load_user_voices`]. Returns the names of the imported voices.
+    pub fn import_voices(
+        &self,
+        path: impl AsRef<Path>,
+        name: Option<&str>,
+    ) -> Result<Vec<String>, KokoError> {
+        let dir = self
+            .user_voices_dir
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| {
+                KokoError::InvalidVoice("no user voices directory configured".to_string())
+            })?;
+
+        let mut voices = voices::read_voice_file(path.as_ref())?;
+        if let Some(name) = name {
+            if voices.len() != 1 {
+                return Err(KokoError::InvalidVoice(format!(
+                    "can't rename a file with {} voices, import it without a name",
+                    voices.len()
+                )));
             }
-            Ok(blended_style)
+            let pack = voices.drain().next().map(|(_, pack)| pack).unwrap();
+            voices.insert(name.to_string(), pack);
         }
+
+        let mut styles = self.styles.write().unwrap();
+        for name in voices.keys() {
+            voices::validate_voice_name(name)?;
+            if let Some(source) = styles.source(name).filter(|s| *s != VoiceSource::Imported) {
+                return Err(KokoError::InvalidVoice(format!(
+                    "'{}' is a {:?} voice and can't be replaced",
+                    name, source
+                )));
+            }
+        }
+
+        let mut imported = styles.voices_from(VoiceSource::Imported);
+        imported.extend(voices.clone());
+        voices::write_npz_voices(dir.join(voices::IMPORTED_VOICES_FILE), &imported)?;
+
+        let mut names: Vec<String> = voices.keys().cloned().collect();
+        names.sort();
+        styles.extend(voices, VoiceSource::Imported);
+
+        tracing::info!(
+            "Imported voices {:?} from {}",
+            names,
+            path.as_ref().display()
+        );
+        Ok(names)
     }
 
-    fn load_voices(voices_path: &str) -> HashMap<String, Vec<[[f32; 256]; 1]>> {
-        let mut npz = NpzReader::new(File::open(voices_path).unwrap()).unwrap();
-        let mut map = HashMap::new();
+    /// Removes a custom or imported voice, including from disk.
+    pub fn remove_voice(&self, name: &str) -> Result<(), KokoError> {
+        let mut styles = self.styles.write().unwrap();
+        let path = match styles.source(name) {
+            None => return Err(KokoError::VoiceNotFound(name.to_string())),
+            Some(VoiceSource::Builtin) => {
+                return Err(KokoError::InvalidVoice(format!(
+                    "'{}' is a built-in voice and can't be removed",
+                    name
+                )));
+            }
+            Some(VoiceSource::Custom) => self.custom_voices_path.clone(),
+            Some(VoiceSource::Imported) => self
+                .user_voices_dir
+                .read()
+                .unwrap()
+                .as_ref()
+                .ok_or_else(|| {
+                    KokoError::InvalidVoice("no user voices directory configured".to_string())
+                })?
+                .join(voices::IMPORTED_VOICES_FILE),
+        };
 
-        for voice in npz.names().unwrap() {
-            let voice_data: Result<Array3<f32>, _> = npz.by_name(&voice);
-            let voice_data = voice_data.unwrap();
-            let mut tensor = vec![[[0.0; 256]; 1]; 511];
-            for (i, inner_value) in voice_data.outer_iter().enumerate() {
-                for (j, inner_inner_value) in inner_value.outer_iter().enumerate() {
-                    for (k, number) in inner_inner_value.iter().enumerate() {
-                        tensor[i][j][k] = *number;
-                    }
-                }
+        let source = styles.source(name).unwrap();
+        let mut remaining = styles.voices_from(source);
+        remaining.remove(name);
+        voices::write_npz_voices(&path, &remaining)?;
+        styles.remove(name);
+
+        tracing::info!("Removed {:?} voice '{}'", source, name);
+        Ok(())
+    }
+
+    /// Metadata of every available voice, sorted by name.
+    pub fn list_voices(&self) -> Vec<VoiceInfo> {
+        self.styles.read().unwrap().info()
+    }
+
+    fn load_custom_voices(path: &Path, styles: &mut VoiceLibrary) {
+        if !path.exists() {
+            return;
+        }
+        match voices::read_npz_voices(path) {
+            Ok(custom) => {
+                tracing::info!("Custom voices loaded: {}", custom.len());
+                styles.extend(custom, VoiceSource::Custom);
             }
-            map.insert(voice, tensor);
+            Err(e) => tracing::warn!(
+                "Failed to load custom voices from {}: {}",
+                path.display(),
+                e
+            ),
         }
+    }
+
+    fn load_voices(voices_path: &str) -> Result<HashMap<String, VoicePack>, KokoError> {
+        let map = voices::read_voices(voices_path).map_err(|e| {
+            KokoError::ModelLoad(format!("Failed to load voices from {}: {}", voices_path, e))
+        })?;
 
         let _sorted_voices = {
             let mut voices = map.keys().collect::<Vec<_>>();
@@ -646,12 +1052,12 @@ impl TTSKoko {
             voices
         };
 
-        map
+        Ok(map)
     }
 
     // Returns a sorted list of available voice names
     pub fn get_available_voices(&self) -> Vec<String> {
-        let mut voices: Vec<String> = self.styles.keys().cloned().collect();
+        let mut voices: Vec<String> = self.styles.read().unwrap().packs.keys().cloned().collect();
         voices.sort();
         voices
     }
@@ -662,7 +1068,7 @@ impl TTSKokoParallel {
         model_path: &str,
         voices_path: &str,
         num_instances: usize,
-    ) -> Self {
+    ) -> Result<Self, KokoError> {
         Self::from_config_with_instances(
             model_path,
             voices_path,
@@ -677,21 +1083,13 @@ impl TTSKokoParallel {
         voices_path: &str,
         cfg: InitConfig,
         num_instances: usize,
-    ) -> Self {
-        if !Path::new(model_path).exists() {
-            utils::fileio::download_file_from_url(cfg.model_url.as_str(), model_path)
-                .await
-                .expect("download model failed.");
-        }
-
-        if !Path::new(voices_path).exists() {
-            utils::fileio::download_file_from_url(cfg.voices_url.as_str(), voices_path)
-                .await
-                .expect("download voices data file failed.");
-        }
+    ) -> Result<Self, KokoError> {
+        TTSKoko::fetch_files(model_path, voices_path, &cfg).await?;
+        let model_config = TTSKoko::load_model_config(model_path, &cfg).await?;
 
         // Create multiple ONNX model instances
         let mut models = Vec::new();
+        let mut rtfs = Vec::new();
         for i in 0..num_instances {
             tracing::info!(
                 "Creating TTS instance [{}] ({}/{})",
@@ -699,21 +1097,31 @@ impl TTSKokoParallel {
                 i + 1,
                 num_instances
             );
-            let model = Arc::new(Mutex::new(
-                ort_koko::OrtKoko::new(model_path.to_string())
-                    .expect("Failed to create Kokoro TTS model"),
-            ));
+            let model = Arc::new(Mutex::new(ort_koko::OrtKoko::with_config(
+                model_path.to_string(),
+                &cfg.session,
+            )?));
             models.push(model);
+            rtfs.push(Arc::new(RealTimeFactor::default()));
         }
 
-        let styles = TTSKoko::load_voices(voices_path);
+        let mut styles = VoiceLibrary::default();
+        styles.extend(TTSKoko::load_voices(voices_path)?, VoiceSource::Builtin);
+        let custom_voices_path = voices::custom_voices_path(voices_path);
+        TTSKoko::load_custom_voices(&custom_voices_path, &mut styles);
 
-        TTSKokoParallel {
+        Ok(TTSKokoParallel {
             model_path: model_path.to_string(),
             models,
-            styles,
+            styles: Arc::new(RwLock::new(styles)),
+            custom_voices_path,
+            user_voices_dir: Arc::new(RwLock::new(None)),
+            g2p: Arc::new(RwLock::new(cfg.g2p.backend())),
+            switch_voices: Arc::new(AtomicBool::new(cfg.switch_voices)),
+            model_config: Arc::new(model_config),
             init_config: cfg,
-        }
+            rtfs,
+        })
     }
 
     /// Get a specific model instance for a worker
@@ -722,6 +1130,49 @@ impl TTSKokoParallel {
         Arc::clone(&self.models[index])
     }
 
+    /// How fast the model instance of a worker has been synthesizing.
+    pub fn real_time_factor(&self, worker_id: usize) -> &RealTimeFactor {
+        &self.rtfs[worker_id % self.rtfs.len()]
+    }
+
+    /// Like [`TTSKoko::warm_up`], with a dummy synthesis on every instance.
+    pub fn warm_up(&self) -> Result<WarmupTimings, KokoError> {
+        let g2p = self.g2p.read().unwrap().clone();
+        let phonemizer =
+            warmup::preload_phonemizer(g2p.as_ref()).map_err(KokoError::Phonemization)?;
+
+        let voices = self.get_available_voices();
+        let voice = warmup::warmup_voice(&voices)
+            .ok_or_else(|| KokoError::VoiceNotFound(warmup::WARMUP_VOICE.to_string()))?;
+        // See TTSKoko::warm_up; the clone shares the model instances
+        let unmeasured = TTSKokoParallel {
+            rtfs: self
+                .rtfs
+                .iter()
+                .map(|_| Arc::new(RealTimeFactor::default()))
+                .collect(),
+            ..self.clone()
+        };
+        let mut instances = Vec::new();
+        for (i, model) in self.models.iter().enumerate() {
+            let start = Instant::now();
+            unmeasured.tts_raw_audio_with_instance(
+                WARMUP_TEXT,
+                WARMUP_LANG,
+                voice,
+                1.0,
+                None,
+                None,
+                Some(&format!("{:02x}", i)),
+                None,
+                Arc::clone(model),
+            )?;
+            instances.push(start.elapsed());
+        }
+
+        Ok(WarmupTimings::new(phonemizer, &instances))
+    }
+
     /// TTS processing with specific model instance (no global lock)
     pub fn tts_raw_audio_with_instance(
         &self,
@@ -734,80 +1185,125 @@ impl TTSKokoParallel {
         instance_id: Option<&str>,
         chunk_number: Option<usize>,
         model_instance: Arc<Mutex<ort_koko::OrtKoko>>,
-    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
-        // Convert text to phonemes
-        let phonemes = {
-            let _guard = ESPEAK_MUTEX.lock().unwrap();
-            text_to_phonemes(text, language, None, true, false)?
-        };
-        let phonemes = phonemes.join("");
-        let debug_prefix = format_debug_prefix(request_id, instance_id);
-        tracing::debug!(
-            "{} text: '{}' -> phonemes: '{}'",
-            debug_prefix,
-            text,
-            phonemes
-        );
-
-        // Tokenize phonemes
-        let mut tokens = tokenize(&phonemes);
-
-        // Add initial silence if specified
-        for _ in 0..initial_silence.unwrap_or(0) {
-            tokens.insert(0, 30);
-        }
-
-        // Get style vectors - create temporary TTSKoko instance to use mix_styles
+    ) -> Result<Vec<f32>, KokoError> {
+        // Create temporary TTSKoko instance to use mix_styles
         let temp_tts = TTSKoko {
             model_path: self.model_path.clone(),
             model: Arc::clone(&self.models[0]), // Just for interface compatibility
-            styles: self.styles.clone(),
+            styles: Arc::clone(&self.styles),
+            custom_voices_path: self.custom_voices_path.clone(),
+            user_voices_dir: Arc::clone(&self.user_voices_dir),
+            g2p: Arc::clone(&self.g2p),
+            switch_voices: Arc::clone(&self.switch_voices),
+            model_config: Arc::clone(&self.model_config),
             init_config: self.init_config.clone(),
+            rtf: Arc::new(RealTimeFactor::default()),
+            batching: Arc::new(AtomicBool::new(false)),
+        };
+        let rtf = self
+            .models
+            .iter()
+            .position(|model| Arc::ptr_eq(model, &model_instance))
+            .map(|index| Arc::clone(&self.rtfs[index]));
+
+        // Detected language spans are phonemized and inferred one after another
+        let spans = if language == langid::AUTO {
+            let default_lang = voices::voice_language(style_name).unwrap_or("en-us");
+            langid::detect_spans(text, default_lang)
+                .into_iter()
+                .map(|span| {
+                    let voice = temp_tts.span_voice(style_name, span.lang);
+                    (span.text, span.lang, voice)
+                })
+                .collect()
+        } else {
+            vec![(text.to_string(), language, style_name.to_string())]
         };
-        let styles = temp_tts.mix_styles(style_name, tokens.len())?;
 
-        // pad a 0 to start and end of tokens
-        let mut padded_tokens = vec![0];
-        for &token in &tokens {
-            padded_tokens.push(token);
-        }
-        padded_tokens.push(0);
+        let g2p = Arc::clone(&self.g2p.read().unwrap());
+        let mut audio_vec = Vec::new();
+        for (i, (text, language, voice)) in spans.iter().enumerate() {
+            // Convert text to phonemes
+            let phonemes = g2p::phonemize_batch(g2p.as_ref(), &[text], language)
+                .map_err(KokoError::Phonemization)?
+                .remove(0);
+            let debug_prefix = format_debug_prefix(request_id, instance_id);
+            tracing::debug!(
+                "{} text: '{}' -> phonemes: '{}'",
+                debug_prefix,
+                text,
+                phonemes
+            );
 
-        let tokens_vec = vec![padded_tokens];
+            // Tokenize phonemes
+            let mut tokens = self.model_config.vocab.tokenize(&phonemes);
 
-        tracing::debug!("shape_style: {:?}", styles.len());
+            // Add initial silence if specified
+            if i == 0 {
+                for _ in 0..initial_silence.unwrap_or(0) {
+                    tokens.insert(0, 30);
+                }
+            }
 
-        // Run TTS inference with provided model instance
-        let mut model = model_instance.lock().unwrap();
-        let audio = model.infer(
-            tokens_vec,
-            styles.clone(),
-            speed,
-            request_id,
-            instance_id,
-            chunk_number,
-        )?;
+            // Get style vectors
+            let styles = temp_tts.mix_styles(voice, tokens.len())?;
+
+            // pad a 0 to start and end of tokens
+            let mut padded_tokens = vec![0];
+            for &token in &tokens {
+                padded_tokens.push(token);
+            }
+            padded_tokens.push(0);
+
+            let tokens_vec = vec![padded_tokens];
 
-        // Convert ndarray to Vec<f32>
-        let audio_vec: Vec<f32> = audio.iter().cloned().collect();
+            tracing::debug!("shape_style: {:?}", styles.len());
+
+            // Run TTS inference with provided model instance
+            let mut model = model_instance.lock().unwrap();
+            let start = Instant::now();
+            let audio = model.infer(
+                tokens_vec,
+                styles.clone(),
+                speed,
+                request_id,
+                instance_id,
+                chunk_number,
+            )?;
+            if let Some(rtf) = &rtf {
+                rtf.record(start.elapsed(), audio.len(), self.init_config.sample_rate);
+            }
+
+            // Convert ndarray to Vec<f32>
+            audio_vec.extend(audio.iter().cloned());
+        }
         Ok(audio_vec)
     }
 
     /// Forward compatibility - split text method
     pub fn split_text_into_speech_chunks(&self, text: &str, max_words: usize) -> Vec<String> {
-        // Use TTSKoko's implementation for now - create temporary instance
-        let temp_tts = TTSKoko {
-            model_path: self.model_path.clone(),
-            model: Arc::clone(&self.models[0]), // Just for interface compatibility
-            styles: self.styles.clone(),
-            init_config: self.init_config.clone(),
-        };
-        temp_tts.split_text_into_speech_chunks(text, max_words)
+        ChunkingPolicy::with_target_words(max_words).split(text)
+    }
+
+    /// Metadata of every available voice, sorted by name.
+    pub fn list_voices(&self) -> Vec<VoiceInfo> {
+        self.styles.read().unwrap().info()
+    }
+
+    /// Switches the G2P backend of every instance.
+    pub fn set_g2p(&self, kind: G2pKind) {
+        tracing::info!("Using {:?} G2P backend", kind);
+        *self.g2p.write().unwrap() = kind.backend();
+    }
+
+    /// See [`TTSKoko::set_switch_voices`].
+    pub fn set_switch_voices(&self, switch_voices: bool) {
+        self.switch_voices.store(switch_voices, Ordering::Relaxed);
     }
 
     /// Get available voices
     pub fn get_available_voices(&self) -> Vec<String> {
-        let mut voices: Vec<String> = self.styles.keys().cloned().collect();
+        let mut voices: Vec<String> = self.styles.read().unwrap().packs.keys().cloned().collect();
         voices.sort();
         voices
     }
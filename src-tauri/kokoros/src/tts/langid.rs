@@ -0,0 +1,491 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+/// Language code that asks for detection instead of a fixed espeak language
+pub const AUTO: &str = "auto";
+
+/// Log-probability cost of switching language between two words, so a lone
+/// foreign-looking word (a name, a loanword) doesn't start a span of its own
+const SWITCH_PENALTY: f32 = 4.0;
+
+/// Bonus for staying in the caller's language at the start of a run
+const DEFAULT_PRIOR: f32 = 2.0;
+
+/// Bonus for a word that appears verbatim in a language's sample
+const KNOWN_WORD_BONUS: f32 = 3.0;
+
+/// Trigram types assumed per language when smoothing unseen trigrams
+const TRIGRAM_VOCABULARY: f32 = 4000.0;
+
+/// Words from a language's sample, and not the default language's, a span needs
+/// before it's spoken in that language; names and loanwords look foreign but
+/// aren't in the samples
+const MIN_KNOWN_WORDS: usize = 2;
+
+/// Common words of each Latin-script language, used both as a word list and as the
+/// corpus its character trigram profile is built from. English is keyed `en` and
+/// resolved to the caller's variant.
+const LATIN_SAMPLES: &[(&str, &str)] = &[
+    (
+        "en",
+        "a i the of and to in is you that it he was for on are as with his they at be this \
+         have from or one had by word but not what all were we when your can said there \
+         use an each which she do how their if will up other about out many then them \
+         these so some her would make like him into time has look two more write go see \
+         number no way could people my than first water been call who oil its now find \
+         long down day did get come made may part thank you very much where is what \
+         should think know just because through world hello good morning right here \
+         left went back after again little home house old great still every never",
+    ),
+    (
+        "fr-fr",
+        "le la les de des du un une et est en que qui dans pour pas sur au avec ce il \
+         elle ne se plus par je tu nous vous ils elles son sa ses mais comme tout nous \
+         fait été être avoir très bien aussi où leur même donc alors ça c'est j'ai \
+         n'est qu'il je ne sais pas mon ami merci beaucoup bonjour au revoir oui non \
+         toujours peut-être chose quelque encore après avant déjà voilà pourquoi \
+         français monde vie belle château première été hôtel garçon",
+    ),
+    (
+        "es",
+        "el la los las de del que y en un una es por con no se su para al lo como más \
+         pero sus le ya o este sí porque esta entre cuando muy sin sobre también me \
+         hasta hay donde quien desde todo nos durante todos uno les ni contra otros \
+         ese eso ante ellos esto mí antes algunos qué unos yo otro otras otra él tanto \
+         esa estos mucho quienes nada muchos cual poco ella estar estas algunas algo \
+         nosotros gracias hola buenos días señor niño mañana año español cómo está",
+    ),
+    (
+        "it",
+        "il lo la i gli le di del della che e è un una per non in con sono si mi ci \
+         ma come anche più questo quello sei siamo hanno gli nel nella alla allo agli \
+         perché però cosa molto bene grazie ciao buongiorno buonasera prego sempre \
+         ancora dove quando chi io tu lui lei noi voi loro mio tuo suo nostro vostro \
+         essere avere fare andare città perché così già può giù italiano bellissimo \
+         gnocchi famiglia figlio zucchero",
+    ),
+    (
+        "pt-br",
+        "o a os as de do da dos das que e é um uma em no na nos nas por para com não \
+         se ao mais mas como foi ele ela eles elas seu sua isso isto está são muito \
+         também já quando então ainda você vocês nós eu tu meu minha obrigado \
+         obrigada olá bom dia boa noite tudo bem coisa sempre onde porque até depois \
+         ação coração não irmão mãe pão informação português cidade fazer",
+    ),
+    (
+        "de",
+        "der die das und ist nicht ein eine zu den von mit sich des auf für im dem \
+         auch es an als werden aus er hat dass sie nach wird bei einer um am sind noch \
+         wie einem über einen so zum war haben nur oder aber vor zur bis mehr durch \
+         kann ich du wir ihr mein dein danke bitte guten morgen tag schön straße \
+         mädchen grüße wünsche natürlich vielleicht deutsch sprechen",
+    ),
+];
+
+lazy_static! {
+    static ref PROFILES: Vec<LatinProfile> = LATIN_SAMPLES
+        .iter()
+        .map(|(lang, sample)| LatinProfile::new(lang, sample))
+        .collect();
+}
+
+/// A run of text in one language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageSpan {
+    pub text: String,
+    /// espeak language code, e.g. `fr-fr`
+    pub lang: &'static str,
+}
+
+/// Character trigram frequencies and word list of a Latin-script language.
+struct LatinProfile {
+    lang: &'static str,
+    words: Vec<String>,
+    trigrams: HashMap<String, f32>,
+    total: f32,
+}
+
+impl LatinProfile {
+    fn new(lang: &'static str, sample: &str) -> Self {
+        let words: Vec<String> = sample.split_whitespace().map(str::to_string).collect();
+        let mut trigrams = HashMap::new();
+        for word in &words {
+            for trigram in word_trigrams(word) {
+                *trigrams.entry(trigram).or_insert(0.0) += 1.0;
+            }
+        }
+        let total = trigrams.values().sum();
+        Self {
+            lang,
+            words,
+            trigrams,
+            total,
+        }
+    }
+
+    /// Log-likelihood of a lowercase word under this profile.
+    fn score(&self, word: &str) -> f32 {
+        let trigrams = word_trigrams(word);
+        let mut score: f32 = trigrams
+            .iter()
+            .map(|t| {
+                let count = self.trigrams.get(t).copied().unwrap_or(0.0);
+                ((count + 0.5) / (self.total + 0.5 * TRIGRAM_VOCABULARY)).ln()
+            })
+            .sum();
+        if self.words.iter().any(|w| w == word) {
+            score += KNOWN_WORD_BONUS;
+        }
+        score
+    }
+}
+
+/// Trigrams of a word padded with a space on each side, so word starts and
+/// endings are scored too.
+fn word_trigrams(word: &str) -> Vec<String> {
+    let padded: Vec<char> = std::iter::once(' ')
+        .chain(word.chars())
+        .chain(std::iter::once(' '))
+        .collect();
+    padded.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    /// Han characters, Japanese when kana appear in the same run
+    Han,
+    Kana,
+    Hangul,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+}
+
+fn script(c: char) -> Option<Script> {
+    Some(match c as u32 {
+        _ if c.is_ascii_alphabetic() => Script::Latin,
+        0x00C0..=0x024F | 0x1E00..=0x1EFF if c.is_alphabetic() => Script::Latin,
+        0x0370..=0x03FF => Script::Greek,
+        0x0400..=0x052F => Script::Cyrillic,
+        0x0590..=0x05FF => Script::Hebrew,
+        0x0600..=0x06FF | 0x0750..=0x077F => Script::Arabic,
+        0x0900..=0x097F => Script::Devanagari,
+        0x0E00..=0x0E7F => Script::Thai,
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF => Script::Han,
+        _ => return None,
+    })
+}
+
+/// Whether two scripts belong to the same run; kanji and kana make up Japanese text.
+fn same_run(a: Script, b: Script) -> bool {
+    let cjk = |s| matches!(s, Script::Han | Script::Kana);
+    a == b || (cjk(a) && cjk(b))
+}
+
+/// Splits `text` into spans of one language each, in order and without losing
+/// any text.
+///
+/// Non-Latin scripts identify their language directly (kana → Japanese, Han alone →
+/// Mandarin, Hangul → Korean, Cyrillic → Russian, ...). Latin-script runs are
+/// classified word by word with character trigram profiles, then smoothed with a
+/// Viterbi pass that charges for every switch, so only phrases, not stray words,
+/// change language. `default_lang` (an espeak code such as `en-us`) is used for
+/// text without letters, for English, and is favoured when a run is ambiguous.
+pub fn detect_spans(text: &str, default_lang: &'static str) -> Vec<LanguageSpan> {
+    let mut spans: Vec<LanguageSpan> = Vec::new();
+    for (run, run_script) in script_runs(text) {
+        match run_script {
+            Some(Script::Latin) => {
+                for (words, lang) in classify_latin(&run, default_lang) {
+                    push_span(&mut spans, words, lang);
+                }
+            }
+            Some(s) => {
+                let lang = script_language(s, &run);
+                push_span(&mut spans, run, lang);
+            }
+            None => push_span(&mut spans, run, default_lang),
+        }
+    }
+    spans
+}
+
+/// Appends `text`, merging it into the previous span when the language matches.
+fn push_span(spans: &mut Vec<LanguageSpan>, text: String, lang: &'static str) {
+    match spans.last_mut() {
+        Some(last) if last.lang == lang => last.text.push_str(&text),
+        _ => spans.push(LanguageSpan { text, lang }),
+    }
+}
+
+/// espeak language of a non-Latin run.
+fn script_language(script: Script, run: &str) -> &'static str {
+    match script {
+        Script::Han | Script::Kana => {
+            if run.chars().any(|c| self::script(c) == Some(Script::Kana)) {
+                "ja"
+            } else {
+                "cmn"
+            }
+        }
+        Script::Hangul => "ko",
+        Script::Cyrillic => "ru",
+        Script::Greek => "el",
+        Script::Arabic => "ar",
+        Script::Hebrew => "he",
+        Script::Devanagari => "hi",
+        Script::Thai => "th",
+        Script::Latin => unreachable!("Latin runs are classified by n-grams"),
+    }
+}
+
+/// Cuts `text` into runs of one script. Characters without a script (spaces,
+/// digits, punctuation) stay with the run before them, or the first run when the
+/// text starts with them; a text without letters is a single run with no script.
+fn script_runs(text: &str) -> Vec<(String, Option<Script>)> {
+    let mut runs: Vec<(String, Option<Script>)> = Vec::new();
+    let mut leading = String::new();
+
+    for c in text.chars() {
+        let Some(s) = script(c) else {
+            match runs.last_mut() {
+                Some((run, _)) => run.push(c),
+                None => leading.push(c),
+            }
+            continue;
+        };
+        match runs.last_mut() {
+            Some((run, Some(current))) if same_run(*current, s) => run.push(c),
+            _ => runs.push((std::mem::take(&mut leading) + &c.to_string(), Some(s))),
+        }
+    }
+    if runs.is_empty() && !leading.is_empty() {
+        runs.push((leading, None));
+    }
+    runs
+}
+
+/// Splits a Latin-script run into pieces with their most likely language.
+fn classify_latin(run: &str, default_lang: &'static str) -> Vec<(String, &'static str)> {
+    // Lowercase words with their original text and the spaces and punctuation
+    // after them; anything before the first word goes with it
+    let mut pieces: Vec<(String, String)> = Vec::new();
+    let mut leading = String::new();
+    let mut in_word = false;
+    for c in run.chars() {
+        let is_word_char = c.is_alphabetic() || (in_word && (c == '\'' || c == '-'));
+        if is_word_char && !in_word {
+            pieces.push((String::new(), std::mem::take(&mut leading)));
+        }
+        match pieces.last_mut() {
+            Some((word, text)) => {
+                if is_word_char {
+                    word.extend(c.to_lowercase());
+                }
+                text.push(c);
+            }
+            None => leading.push(c),
+        }
+        in_word = is_word_char;
+    }
+    if pieces.is_empty() {
+        return vec![(run.to_string(), default_lang)];
+    }
+
+    let english = if default_lang.starts_with("en") {
+        default_lang
+    } else {
+        "en-us"
+    };
+    let resolve = |lang: &'static str| if lang == "en" { english } else { lang };
+    let states: Vec<&'static str> = PROFILES.iter().map(|p| resolve(p.lang)).collect();
+
+    // Viterbi over the words with a fixed cost for changing language
+    let emissions: Vec<Vec<f32>> = pieces
+        .iter()
+        .map(|(word, _)| PROFILES.iter().map(|p| p.score(word)).collect())
+        .collect();
+    let mut best: Vec<f32> = states
+        .iter()
+        .zip(&emissions[0])
+        .map(|(&lang, e)| {
+            e + if lang == default_lang {
+                DEFAULT_PRIOR
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let mut back: Vec<Vec<usize>> = Vec::with_capacity(pieces.len());
+    for emission in &emissions[1..] {
+        let mut next = Vec::with_capacity(states.len());
+        let mut from = Vec::with_capacity(states.len());
+        for (to, e) in emission.iter().enumerate() {
+            let (prev, score) = best
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (i, s - if i == to { 0.0 } else { SWITCH_PENALTY }))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            next.push(score + e);
+            from.push(prev);
+        }
+        best = next;
+        back.push(from);
+    }
+
+    let mut state = (0..states.len())
+        .max_by(|&a, &b| best[a].total_cmp(&best[b]))
+        .unwrap();
+    let mut path = vec![state; pieces.len()];
+    for (i, from) in back.iter().enumerate().rev() {
+        state = from[state];
+        path[i] = state;
+    }
+
+    // Foreign spans without enough of their language's own words go back to
+    // the default language
+    let default_profile = states.iter().position(|&lang| lang == default_lang);
+    let mut start = 0;
+    while start < path.len() {
+        let state = path[start];
+        let end = (start..path.len())
+            .find(|&i| path[i] != state)
+            .unwrap_or(path.len());
+        if let Some(default_state) = default_profile.filter(|&d| d != state) {
+            let known = pieces[start..end]
+                .iter()
+                .filter(|(word, _)| {
+                    PROFILES[state].words.contains(word)
+                        && !PROFILES[default_state].words.contains(word)
+                })
+                .count();
+            if known < MIN_KNOWN_WORDS {
+                path[start..end].fill(default_state);
+            }
+        }
+        start = end;
+    }
+
+    pieces
+        .into_iter()
+        .zip(path)
+        .map(|((_, text), state)| (text, states[state]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn langs(text: &str, default_lang: &'static str) -> Vec<(String, &'static str)> {
+        let spans = detect_spans(text, default_lang);
+        let joined: String = spans.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(joined, text, "spans must cover the text in order");
+        spans.into_iter().map(|s| (s.text, s.lang)).collect()
+    }
+
+    #[test]
+    fn test_scripts() {
+        assert_eq!(
+            langs("My favourite film is 君の名は by Shinkai.", "en-us"),
+            vec![
+                ("My favourite film is ".to_string(), "en-us"),
+                ("君の名は ".to_string(), "ja"),
+                ("by Shinkai.".to_string(), "en-us"),
+            ]
+        );
+        assert_eq!(
+            langs("你好 means hello", "en-gb"),
+            vec![
+                ("你好 ".to_string(), "cmn"),
+                ("means hello".to_string(), "en-gb"),
+            ]
+        );
+        assert_eq!(
+            langs("Привет, мир! Hello", "en-us"),
+            vec![
+                ("Привет, мир! ".to_string(), "ru"),
+                ("Hello".to_string(), "en-us"),
+            ]
+        );
+        assert_eq!(
+            langs("カタカナ", "en-us"),
+            vec![("カタカナ".to_string(), "ja")]
+        );
+        assert_eq!(langs("42!", "fr-fr"), vec![("42!".to_string(), "fr-fr")]);
+        assert!(detect_spans("", "en-us").is_empty());
+    }
+
+    #[test]
+    fn test_latin_phrases() {
+        assert_eq!(
+            langs(
+                "How are you today? Je ne sais pas, mon ami, c'est la vie.",
+                "en-us"
+            ),
+            vec![
+                ("How are you today? ".to_string(), "en-us"),
+                (
+                    "Je ne sais pas, mon ami, c'est la vie.".to_string(),
+                    "fr-fr"
+                ),
+            ]
+        );
+        assert_eq!(
+            langs("She said muchas gracias, señor and left.", "en-us"),
+            vec![
+                ("She said ".to_string(), "en-us"),
+                ("muchas gracias, señor ".to_string(), "es"),
+                ("and left.".to_string(), "en-us"),
+            ]
+        );
+        assert_eq!(
+            langs("Danke schön für alles", "en-us"),
+            vec![("Danke schön für alles".to_string(), "de")]
+        );
+    }
+
+    #[test]
+    fn test_single_words_stay() {
+        // Names and loanwords don't switch the language on their own
+        assert_eq!(
+            langs("I met Pierre at the café yesterday.", "en-us"),
+            vec![("I met Pierre at the café yesterday.".to_string(), "en-us")]
+        );
+        assert_eq!(
+            langs("Merci pour le weekend.", "fr-fr"),
+            vec![("Merci pour le weekend.".to_string(), "fr-fr")]
+        );
+    }
+
+    #[test]
+    fn test_english_replies_stay_english() {
+        // Chat replies with names, places and loanwords, none of which may
+        // switch the language
+        let replies = [
+            "Sure! Here's a quick recipe for spaghetti carbonara with pancetta and parmesan.",
+            "Angela Merkel and Emmanuel Macron met in Berlin to discuss the budget.",
+            "The café on Rue de Rivoli serves a great croissant and an espresso.",
+            "Good question. Jürgen Klopp coached Borussia Dortmund before moving to Liverpool.",
+            "My schedule is pretty flexible, so let's say Tuesday at noon in São Paulo time?",
+            "A piñata, a fiesta and some jalapeños make for a fun birthday party.",
+            "That's a classic déjà vu moment, but I'm happy to go over the plan again.",
+            "You can find the Bauhaus collection at the museum near Alexanderplatz.",
+            "Honestly, the best approach is to keep it simple and test each step.",
+        ];
+        for reply in replies {
+            assert_eq!(
+                langs(reply, "en-us"),
+                vec![(reply.to_string(), "en-us")],
+                "{reply}"
+            );
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use crate::tts::g2p::G2p;
+use crate::tts::vocab::VOCAB;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// American English dictionary bundled with the crate, see the file header for the format
+const BUNDLED_LEXICON: &str = include_str!("lexicon_en_us.txt");
+
+/// Primary stress mark
+const STRESS: char = 'ˈ';
+
+/// Phonemes that carry stress, including misaki's diphthong shorthands
+const VOWELS: &str = "AIOWYaeiouæɑɐɒɔəɛɜɪʊʌ";
+
+lazy_static! {
+    static ref BUNDLED: Arc<HashMap<String, String>> = Arc::new(parse_lexicon(BUNDLED_LEXICON));
+}
+
+/// Letter-to-sound rules, longest graphemes first. Context-dependent letters
+/// (soft c and g, silent and "magic" e, y, doubled consonants) are handled in code.
+const RULES: &[(&str, &str)] = &[
+    ("tion", "ʃən"),
+    ("sion", "ʒən"),
+    ("ture", "ʧəɹ"),
+    ("ough", "ɔ"),
+    ("augh", "ɔ"),
+    ("eigh", "A"),
+    ("igh", "I"),
+    ("tch", "ʧ"),
+    ("dge", "ʤ"),
+    ("sch", "sk"),
+    ("ch", "ʧ"),
+    ("sh", "ʃ"),
+    ("th", "θ"),
+    ("ph", "f"),
+    ("wh", "w"),
+    ("ck", "k"),
+    ("ng", "ŋ"),
+    ("qu", "kw"),
+    ("kn", "n"),
+    ("wr", "ɹ"),
+    ("ee", "i"),
+    ("ea", "i"),
+    ("oo", "u"),
+    ("ou", "W"),
+    ("ow", "O"),
+    ("ai", "A"),
+    ("ay", "A"),
+    ("oi", "Y"),
+    ("oy", "Y"),
+    ("oa", "O"),
+    ("au", "ɔ"),
+    ("aw", "ɔ"),
+    ("ew", "u"),
+    ("ie", "i"),
+    ("ei", "A"),
+    ("ue", "u"),
+    ("ar", "ɑɹ"),
+    ("er", "əɹ"),
+    ("ir", "ɜɹ"),
+    ("ur", "ɜɹ"),
+    ("or", "ɔɹ"),
+    ("a", "æ"),
+    ("b", "b"),
+    ("c", "k"),
+    ("d", "d"),
+    ("e", "ɛ"),
+    ("f", "f"),
+    ("g", "ɡ"),
+    ("h", "h"),
+    ("i", "ɪ"),
+    ("j", "ʤ"),
+    ("k", "k"),
+    ("l", "l"),
+    ("m", "m"),
+    ("n", "n"),
+    ("o", "ɑ"),
+    ("p", "p"),
+    ("q", "k"),
+    ("r", "ɹ"),
+    ("s", "s"),
+    ("t", "t"),
+    ("u", "ʌ"),
+    ("v", "v"),
+    ("w", "w"),
+    ("x", "ks"),
+    ("z", "z"),
+];
+
+/// English G2P from a pronunciation dictionary in Kokoro's (misaki) phoneme set,
+/// with letter-to-sound rules for words the dictionary doesn't know.
+///
+/// Unlike espeak it has no global state and needs no native library, and the
+/// dictionary matches the transcriptions Kokoro was trained on.
+pub struct LexiconG2p {
+    /// Shared with the bundled dictionary until extended
+    entries: Arc<HashMap<String, String>>,
+}
+
+impl LexiconG2p {
+    /// The bundled American English dictionary, parsed once and shared.
+    pub fn bundled() -> Self {
+        Self {
+            entries: Arc::clone(&BUNDLED),
+        }
+    }
+
+    /// Adds or replaces entries, e.g. from a user dictionary in the bundled format.
+    pub fn extend(&mut self, lexicon: &str) {
+        Arc::make_mut(&mut self.entries).extend(parse_lexicon(lexicon));
+    }
+
+    /// Pronunciation of a single lowercase word.
+    pub fn word(&self, word: &str) -> String {
+        if let Some(phonemes) = self.entries.get(word) {
+            return phonemes.clone();
+        }
+        if let Some(stem) = word.strip_suffix("'s") {
+            return self.word(stem) + "z";
+        }
+        letter_to_sound(word)
+    }
+}
+
+impl G2p for LexiconG2p {
+    fn name(&self) -> &'static str {
+        "lexicon"
+    }
+
+    fn supports(&self, lang: &str) -> bool {
+        lang == "en" || lang.starts_with("en-")
+    }
+
+    fn phonemize(&self, text: &str, _lang: &str) -> Result<String, String> {
+        let mut out = String::new();
+        for token in text.split_whitespace() {
+            let mut word = String::new();
+            let mut phonemes = String::new();
+            for c in token.chars() {
+                if c.is_alphanumeric() || (c == '\'' && !word.is_empty()) {
+                    word.extend(c.to_lowercase());
+                    continue;
+                }
+                phonemes.push_str(&self.spell(&std::mem::take(&mut word)));
+                // Keep punctuation the model knows, attached like espeak does
+                if VOCAB.contains_key(&c) && !c.is_alphanumeric() {
+                    phonemes.push(c);
+                }
+            }
+            phonemes.push_str(&self.spell(&word));
+
+            if phonemes.is_empty() {
+                continue;
+            }
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(&phonemes);
+        }
+        Ok(out)
+    }
+}
+
+impl LexiconG2p {
+    /// Phonemes of a word or number, empty for an empty string.
+    fn spell(&self, word: &str) -> String {
+        let word = word.trim_end_matches('\'');
+        if word.is_empty() {
+            return String::new();
+        }
+        match word.parse::<u64>() {
+            Ok(n) => number_to_words(n)
+                .split(' ')
+                .map(|w| self.word(w))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Err(_) => self.word(word),
+        }
+    }
+}
+
+fn parse_lexicon(lexicon: &str) -> HashMap<String, String> {
+    lexicon
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(word, phonemes)| (word.trim().to_lowercase(), phonemes.trim().to_string()))
+        .collect()
+}
+
+/// Spells out a number the way it's read, e.g. `42` -> `forty two`.
+fn number_to_words(n: u64) -> String {
+    const ONES: [&str; 20] = [
+        "zero",
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    const TENS: [&str; 10] = [
+        "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    const SCALES: [(u64, &str); 3] = [
+        (1_000_000_000, "billion"),
+        (1_000_000, "million"),
+        (1_000, "thousand"),
+    ];
+
+    if n < 20 {
+        return ONES[n as usize].to_string();
+    }
+    if n < 100 {
+        return match n % 10 {
+            0 => TENS[(n / 10) as usize].to_string(),
+            ones => format!("{} {}", TENS[(n / 10) as usize], ONES[ones as usize]),
+        };
+    }
+    if n < 1000 {
+        return match n % 100 {
+            0 => format!("{} hundred", ONES[(n / 100) as usize]),
+            rest => format!(
+                "{} hundred {}",
+                ONES[(n / 100) as usize],
+                number_to_words(rest)
+            ),
+        };
+    }
+    for (scale, name) in SCALES {
+        if n >= scale {
+            return match n % scale {
+                0 => format!("{} {}", number_to_words(n / scale), name),
+                rest => format!(
+                    "{} {} {}",
+                    number_to_words(n / scale),
+                    name,
+                    number_to_words(rest)
+                ),
+            };
+        }
+    }
+    unreachable!()
+}
+
+/// Guesses the pronunciation of an unknown word with [`RULES`] and stresses its
+/// first vowel.
+fn letter_to_sound(word: &str) -> String {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    let is_vowel = |c: char| "aeiou".contains(c);
+    let mut phonemes = String::new();
+    let mut i = 0;
+
+    while i < letters.len() {
+        let c = letters[i];
+        let next = letters.get(i + 1).copied();
+        let at_end = i + 1 == letters.len();
+
+        // Doubled consonants are pronounced once
+        if i > 0 && letters[i - 1] == c && !is_vowel(c) && c != 'c' {
+            i += 1;
+            continue;
+        }
+        // Silent final e
+        if c == 'e' && at_end && letters.len() > 2 {
+            break;
+        }
+        // Vowel, consonant, final e: the vowel says its name (cake, bike, hope)
+        if is_vowel(c)
+            && i + 3 == letters.len()
+            && letters[i + 2] == 'e'
+            && next.is_some_and(|n| !is_vowel(n) && n != 'r')
+        {
+            phonemes.push_str(match c {
+                'a' => "A",
+                'e' => "i",
+                'i' => "I",
+                'o' => "O",
+                _ => "u",
+            });
+            i += 1;
+            continue;
+        }
+        // Soft c and g before e, i, y
+        if (c == 'c' || c == 'g') && next.is_some_and(|n| "eiy".contains(n)) {
+            phonemes.push_str(if c == 'c' { "s" } else { "ʤ" });
+            i += 1;
+            continue;
+        }
+        if c == 'y' {
+            let other_vowels = letters.iter().any(|&l| is_vowel(l));
+            phonemes.push_str(match (i, at_end) {
+                (0, _) => "j",
+                (_, true) if other_vowels => "i",
+                (_, true) => "I",
+                _ => "ɪ",
+            });
+            i += 1;
+            continue;
+        }
+
+        let rest: String = letters[i..].iter().collect();
+        match RULES
+            .iter()
+            .find(|(grapheme, _)| rest.starts_with(grapheme))
+        {
+            Some((grapheme, sound)) => {
+                phonemes.push_str(sound);
+                i += grapheme.len();
+            }
+            None => i += 1,
+        }
+    }
+
+    match phonemes.find(|c| VOWELS.contains(c)) {
+        Some(first_vowel) => {
+            phonemes.insert(first_vowel, STRESS);
+            phonemes
+        }
+        None => phonemes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_lexicon_uses_vocab() {
+        for (word, phonemes) in BUNDLED.iter() {
+            assert!(
+                phonemes.chars().all(|c| VOCAB.contains_key(&c)),
+                "{word}: {phonemes} has symbols outside the vocabulary"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sentence() {
+        let g2p = LexiconG2p::bundled();
+        assert_eq!(
+            g2p.phonemize("Hello, world! I have 21 cats.", "en-us")
+                .unwrap(),
+            "həlˈO, wˈɜɹld! ˈI hæv twˈɛnti wˈʌn kˈæts."
+        );
+        assert_eq!(
+            g2p.phonemize("Kokoro's ...", "en-us").unwrap(),
+            "kˈOkəɹOz ..."
+        );
+    }
+
+    #[test]
+    fn test_extend_leaves_bundled_alone() {
+        let bundled = LexiconG2p::bundled();
+        assert!(Arc::ptr_eq(&bundled.entries, &BUNDLED));
+
+        let mut custom = LexiconG2p::bundled();
+        custom.extend("kokoro\tkəkˈɔɹO");
+        assert_eq!(custom.word("kokoro"), "kəkˈɔɹO");
+        assert!(!Arc::ptr_eq(&custom.entries, &BUNDLED));
+        assert_eq!(bundled.word("kokoro"), "kˈOkəɹO");
+    }
+
+    #[test]
+    fn test_number_to_words() {
+        assert_eq!(number_to_words(0), "zero");
+        assert_eq!(number_to_words(42), "forty two");
+        assert_eq!(
+            number_to_words(1_205_030),
+            "one million two hundred five thousand thirty"
+        );
+    }
+}
@@ -1,5 +1,23 @@
+pub mod chunking;
+pub mod g2p;
+pub mod heteronym;
+pub mod inspect;
 pub mod koko;
+pub mod langid;
+pub mod lexicon;
+pub mod model_config;
 pub mod normalize;
+pub mod phoneme_chunks;
 pub mod phonemizer;
+pub mod phonemizer_service;
+pub mod prepare;
+pub mod realtime;
+pub mod registry;
+pub mod segment;
+pub mod style_space;
+pub mod timings;
 pub mod tokenize;
+pub mod viseme;
 pub mod vocab;
+pub mod voices;
+pub mod warmup;
@@ -0,0 +1,249 @@
+use crate::tts::vocab::Vocab;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Kokoro v1.0, the multilingual model this crate ships its vocabulary for
+pub const KOKORO_V1_0: &str = "kokoro-v1.0";
+
+/// Kokoro v1.1 Chinese, with its own vocabulary and voice set
+pub const KOKORO_V1_1_ZH: &str = "kokoro-v1.1-zh";
+
+/// Positions of Kokoro's text encoder (`plbert.max_position_embeddings`)
+pub const DEFAULT_CONTEXT_LENGTH: usize = 512;
+
+/// Tokens kept free below the context length for the padding tokens and
+/// initial silence
+const CHUNK_MARGIN: usize = 12;
+
+/// Known models and where their config can be downloaded when it isn't next to
+/// the model. `None` means the bundled defaults apply.
+const KNOWN_MODELS: &[(&str, Option<&str>)] = &[
+    (KOKORO_V1_0, None),
+    (
+        KOKORO_V1_1_ZH,
+        Some("https://huggingface.co/hexgrad/Kokoro-82M-v1.1-zh/resolve/main/config.json"),
+    ),
+];
+
+/// Share of the symbols the G2P backends produce (the Kokoro v1.0 vocabulary)
+/// below which loading a model logs that most phonemes will be dropped at
+/// tokenization. No backend produces misaki's Chinese phoneme set (Zhuyin with
+/// tone numbers) yet, so Chinese text on Kokoro v1.1-zh is one such case.
+const MIN_G2P_COVERAGE: f32 = 0.9;
+
+/// What the loaded model expects: its symbol table and how many tokens it takes.
+#[derive(Debug, Clone)]
+pub struct ModelConfig {
+    /// Model id such as `kokoro-v1.1-zh`
+    pub id: String,
+    pub vocab: Arc<Vocab>,
+    pub context_length: usize,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            id: KOKORO_V1_0.to_string(),
+            vocab: Arc::new(Vocab::default()),
+            context_length: DEFAULT_CONTEXT_LENGTH,
+        }
+    }
+}
+
+impl ModelConfig {
+    /// Parses a Kokoro `config.json`. Only `vocab` is required; the context length
+    /// comes from `plbert.max_position_embeddings` when present.
+    pub fn from_json(id: &str, json: &str) -> Result<Self, String> {
+        let config: Value =
+            serde_json::from_str(json).map_err(|e| format!("invalid model config: {}", e))?;
+        let vocab = config
+            .get("vocab")
+            .ok_or_else(|| "model config has no vocab".to_string())
+            .and_then(Vocab::from_json)?;
+        let context_length = config
+            .pointer("/plbert/max_position_embeddings")
+            .and_then(Value::as_u64)
+            .map_or(DEFAULT_CONTEXT_LENGTH, |n| n as usize);
+
+        Ok(Self {
+            id: id.to_string(),
+            vocab: Arc::new(vocab),
+            context_length,
+        })
+    }
+
+    /// Config of the model at `model_path`.
+    ///
+    /// A config JSON next to the model wins (`<model>.json`, then `config.json`).
+    /// Otherwise `model_id`, or the model's file name when it's `None`, selects a
+    /// known model; models that can't run on the bundled vocabulary are an error.
+    /// Models whose phonemes the G2P backends mostly don't produce load with a
+    /// warning, see [`ModelConfig::g2p_coverage`].
+    pub fn load(model_path: &str, model_id: Option<&str>) -> Result<Self, String> {
+        let id = model_id
+            .map(str::to_string)
+            .unwrap_or_else(|| model_id_from_path(model_path));
+
+        if let Some(path) = Self::find_config(model_path) {
+            let json = std::fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            let config =
+                Self::from_json(&id, &json).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let coverage = config.g2p_coverage();
+            if coverage < MIN_G2P_COVERAGE {
+                tracing::warn!(
+                    "{}: the vocabulary has {:.0}% of the phonemes the G2P backends produce, \
+                     the rest will be dropped",
+                    path.display(),
+                    coverage * 100.0
+                );
+            }
+            tracing::info!(
+                "Model {} uses {} ({} symbols, {} tokens)",
+                config.id,
+                path.display(),
+                config.vocab.len(),
+                config.context_length
+            );
+            return Ok(config);
+        }
+
+        match known_model(&id) {
+            Some((known, None)) => Ok(Self {
+                id: known.to_string(),
+                ..Self::default()
+            }),
+            Some((known, Some(url))) => Err(format!(
+                "model {} needs its config.json next to the model, download it from {}",
+                known, url
+            )),
+            // Unknown exports of the v1.0 weights are the common case
+            None => Ok(Self {
+                id,
+                ..Self::default()
+            }),
+        }
+    }
+
+    /// Where the config of a known model can be downloaded, if it needs one.
+    pub fn config_url(model_id: &str) -> Option<&'static str> {
+        known_model(model_id).and_then(|(_, url)| url)
+    }
+
+    /// Path a downloaded config should be saved to so [`ModelConfig::load`] finds it.
+    pub fn default_config_path(model_path: &str) -> PathBuf {
+        Path::new(model_path).with_extension("json")
+    }
+
+    /// Config JSON next to the model, if there is one.
+    pub fn find_config(model_path: &str) -> Option<PathBuf> {
+        let model_path = Path::new(model_path);
+        [
+            Some(model_path.with_extension("json")),
+            model_path.parent().map(|dir| dir.join("config.json")),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|path| path.is_file())
+    }
+
+    /// Share of the symbols the G2P backends produce that this model has a
+    /// token for. Backends only produce symbols of the Kokoro v1.0 vocabulary.
+    pub fn g2p_coverage(&self) -> f32 {
+        let produced = Vocab::default();
+        let covered = produced
+            .symbols()
+            .filter(|&c| self.vocab.contains(c))
+            .count();
+        covered as f32 / produced.len().max(1) as f32
+    }
+
+    /// Most phoneme tokens a chunk may have.
+    pub fn max_chunk_tokens(&self) -> usize {
+        self.context_length.saturating_sub(CHUNK_MARGIN)
+    }
+}
+
+/// Model id from a file name, e.g. `kokoro-v1.1-zh` for `kokoro-v1.1-zh.int8.onnx`.
+pub fn model_id_from_path(model_path: &str) -> String {
+    let name = Path::new(model_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    KNOWN_MODELS
+        .iter()
+        .map(|(id, _)| *id)
+        .find(|id| name.starts_with(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| name.strip_suffix(".onnx").unwrap_or(&name).to_string())
+}
+
+fn known_model(id: &str) -> Option<(&'static str, Option<&'static str>)> {
+    KNOWN_MODELS.iter().find(|(known, _)| *known == id).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_id_from_path() {
+        assert_eq!(
+            model_id_from_path("resources/kokoro-v1.0.onnx"),
+            KOKORO_V1_0
+        );
+        assert_eq!(
+            model_id_from_path("/models/Kokoro-v1.1-zh.int8.onnx"),
+            KOKORO_V1_1_ZH
+        );
+        assert_eq!(model_id_from_path("my-export.onnx"), "my-export");
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("kokoros-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let model = dir.join("kokoro-v1.1-zh.onnx");
+        let model = model.to_str().unwrap();
+
+        // The zh model can't fall back to the bundled vocabulary
+        assert!(ModelConfig::load(model, None).is_err());
+        assert_eq!(
+            ModelConfig::load(&dir.join("kokoro-v1.0.onnx").to_string_lossy(), None)
+                .unwrap()
+                .max_chunk_tokens(),
+            500
+        );
+
+        std::fs::write(
+            dir.join("config.json"),
+            r#"{"n_token": 3, "plbert": {"max_position_embeddings": 256},
+                "vocab": {"ㄅ": 1, "1": 2, " ": 3}}"#,
+        )
+        .unwrap();
+        let config = ModelConfig::load(model, None).unwrap();
+        assert_eq!(config.id, KOKORO_V1_1_ZH);
+        assert_eq!(config.vocab.tokenize("ㄅ1 a"), vec![1, 2, 3]);
+        assert!(config.g2p_coverage() < MIN_G2P_COVERAGE);
+
+        let export = dir.join("my-export.onnx");
+        let export = export.to_str().unwrap();
+
+        // The v1.0 symbols plus some of its own
+        let mut vocab: serde_json::Map<String, Value> = crate::tts::vocab::get_vocab()
+            .into_iter()
+            .map(|(c, id)| (c.to_string(), Value::from(id)))
+            .collect();
+        vocab.insert("ㄅ".to_string(), Value::from(200));
+        let json = serde_json::json!({"plbert": {"max_position_embeddings": 256}, "vocab": vocab});
+        std::fs::write(dir.join("config.json"), json.to_string()).unwrap();
+        let config = ModelConfig::load(export, None).unwrap();
+        assert_eq!(config.id, "my-export");
+        assert_eq!(config.max_chunk_tokens(), 244);
+        assert_eq!(config.g2p_coverage(), 1.0);
+        assert_eq!(config.vocab.tokenize("ㄅ"), vec![200]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
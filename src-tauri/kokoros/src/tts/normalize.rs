@@ -1,30 +1,28 @@
 use lazy_static::lazy_static;
-use regex::Regex;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
 
+// The `regex` crate has no look-around, so context a pattern only checks is
+// captured and put back, or checked in a replacement closure
 lazy_static! {
     static ref WHITESPACE_RE: Regex = Regex::new(r"[^\S \n]").unwrap();
     static ref MULTI_SPACE_RE: Regex = Regex::new(r"  +").unwrap();
-    static ref NEWLINE_SPACE_RE: Regex = Regex::new(r"(?<=\n) +(?=\n)").unwrap();
-    static ref DOCTOR_RE: Regex = Regex::new(r"\bD[Rr]\.(?= [A-Z])").unwrap();
-    static ref MISTER_RE: Regex = Regex::new(r"\b(?:Mr\.|MR\.(?= [A-Z]))").unwrap();
-    static ref MISS_RE: Regex = Regex::new(r"\b(?:Ms\.|MS\.(?= [A-Z]))").unwrap();
-    static ref MRS_RE: Regex = Regex::new(r"\b(?:Mrs\.|MRS\.(?= [A-Z]))").unwrap();
-    static ref ETC_RE: Regex = Regex::new(r"\betc\.(?! [A-Z])").unwrap();
+    static ref NEWLINE_SPACE_RE: Regex = Regex::new(r"\n(?: +\n)+").unwrap();
+    static ref DOCTOR_RE: Regex = Regex::new(r"\bD[Rr]\.( [A-Z])").unwrap();
+    static ref MISTER_RE: Regex = Regex::new(r"\b(?:Mr\.|MR\.( [A-Z]))").unwrap();
+    static ref MISS_RE: Regex = Regex::new(r"\b(?:Ms\.|MS\.( [A-Z]))").unwrap();
+    static ref MRS_RE: Regex = Regex::new(r"\b(?:Mrs\.|MRS\.( [A-Z]))").unwrap();
+    static ref ETC_RE: Regex = Regex::new(r"\betc\.( [A-Z])?").unwrap();
     static ref YEAH_RE: Regex = Regex::new(r"(?i)\b(y)eah?\b").unwrap();
-    static ref NUMBERS_RE: Regex =
-        Regex::new(r"\d*\.\d+|\b\d{4}s?\b|(?<!:)\b(?:[1-9]|1[0-2]):[0-5]\d\b(?!:)").unwrap();
-    static ref COMMA_NUM_RE: Regex = Regex::new(r"(?<=\d),(?=\d)").unwrap();
     static ref MONEY_RE: Regex = Regex::new(
         r"(?i)[$£]\d+(?:\.\d+)?(?: hundred| thousand| (?:[bm]|tr)illion)*\b|[$£]\d+\.\d\d?\b"
     )
     .unwrap();
     static ref POINT_NUM_RE: Regex = Regex::new(r"\d*\.\d+").unwrap();
-    static ref RANGE_RE: Regex = Regex::new(r"(?<=\d)-(?=\d)").unwrap();
-    static ref S_AFTER_NUM_RE: Regex = Regex::new(r"(?<=\d)S").unwrap();
-    static ref POSSESSIVE_RE: Regex = Regex::new(r"(?<=[BCDFGHJ-NP-TV-Z])'?s\b").unwrap();
-    static ref X_POSSESSIVE_RE: Regex = Regex::new(r"(?<=X')S\b").unwrap();
+    static ref S_AFTER_NUM_RE: Regex = Regex::new(r"(\d)S").unwrap();
+    static ref POSSESSIVE_RE: Regex = Regex::new(r"([BCDFGHJ-NP-TV-Z])'?s\b").unwrap();
+    static ref X_POSSESSIVE_RE: Regex = Regex::new(r"X'S\b").unwrap();
     static ref INITIALS_RE: Regex = Regex::new(r"(?:[A-Za-z]\.){2,} [a-z]").unwrap();
-    static ref ACRONYM_RE: Regex = Regex::new(r"(?i)(?<=[A-Z])\.(?=[A-Z])").unwrap();
 }
 
 pub fn normalize_text(text: &str) -> String {
@@ -47,25 +45,368 @@ pub fn normalize_text(text: &str) -> String {
     // Apply regex replacements
     text = WHITESPACE_RE.replace_all(&text, " ").to_string();
     text = MULTI_SPACE_RE.replace_all(&text, " ").to_string();
-    text = NEWLINE_SPACE_RE.replace_all(&text, "").to_string();
-    text = DOCTOR_RE.replace_all(&text, "Doctor").to_string();
-    text = MISTER_RE.replace_all(&text, "Mister").to_string();
-    text = MISS_RE.replace_all(&text, "Miss").to_string();
-    text = MRS_RE.replace_all(&text, "Mrs").to_string();
-    text = ETC_RE.replace_all(&text, "etc").to_string();
+    text = NEWLINE_SPACE_RE
+        .replace_all(&text, |caps: &Captures| {
+            "\n".repeat(caps[0].matches('\n').count())
+        })
+        .to_string();
+    text = DOCTOR_RE.replace_all(&text, "Doctor$1").to_string();
+    text = MISTER_RE.replace_all(&text, "Mister$1").to_string();
+    text = MISS_RE.replace_all(&text, "Miss$1").to_string();
+    text = MRS_RE.replace_all(&text, "Mrs$1").to_string();
+    text = ETC_RE
+        .replace_all(&text, |caps: &Captures| match caps.get(1) {
+            // Ends a sentence there
+            Some(_) => caps[0].to_string(),
+            None => "etc".to_string(),
+        })
+        .to_string();
     text = YEAH_RE.replace_all(&text, "${1}e'a").to_string();
     // Note: split_num, flip_money, and point_num functions need to be implemented
-    text = COMMA_NUM_RE.replace_all(&text, "").to_string();
-    text = RANGE_RE.replace_all(&text, " to ").to_string();
-    text = S_AFTER_NUM_RE.replace_all(&text, " S").to_string();
-    text = POSSESSIVE_RE.replace_all(&text, "'S").to_string();
-    text = X_POSSESSIVE_RE.replace_all(&text, "s").to_string();
+    text = replace_between(&text, ',', "", |c| c.is_ascii_digit());
+    text = replace_between(&text, '-', " to ", |c| c.is_ascii_digit());
+    text = S_AFTER_NUM_RE.replace_all(&text, "$1 S").to_string();
+    text = POSSESSIVE_RE.replace_all(&text, "$1'S").to_string();
+    text = X_POSSESSIVE_RE.replace_all(&text, "X's").to_string();
 
     // Handle initials and acronyms
     text = INITIALS_RE
         .replace_all(&text, |caps: &regex::Captures| caps[0].replace('.', "-"))
         .to_string();
-    text = ACRONYM_RE.replace_all(&text, "-").to_string();
+    text = replace_between(&text, '.', "-", |c| c.is_ascii_alphabetic());
 
     text.trim().to_string()
 }
+
+/// Replaces every `separator` that has a character matching `side` on both
+/// sides, the way `(?<=x)separator(?=x)` would.
+fn replace_between(text: &str, separator: char, with: &str, side: impl Fn(char) -> bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let between = c == separator
+            && i > 0
+            && side(chars[i - 1])
+            && chars.get(i + 1).is_some_and(|&next| side(next));
+        if between {
+            out.push_str(with);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+lazy_static! {
+    static ref URL_RE: Regex =
+        Regex::new(r#"\b(?:(?:https?|ftp)://|www\.)[^\s<>"]*[^\s<>".,;:!?)\]']"#).unwrap();
+    static ref EMAIL_RE: Regex =
+        Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b")
+            .unwrap();
+    static ref PATH_RE: Regex =
+        Regex::new(concat!(
+            r"(?:[A-Za-z]:\\|~/|\.{1,2}/|/)[\w.-]*\w(?:[/\\][\w.-]*\w)*[/\\]?",
+            r"|[\w.-]*\w(?:[/\\][\w.-]*\w)+[/\\]?"
+        ))
+        .unwrap();
+    static ref SNAKE_CASE_RE: Regex =
+        Regex::new(r"\b[A-Za-z][A-Za-z0-9]*(?:_+[A-Za-z0-9]+)+\b").unwrap();
+    // camelCase, and PascalCase with a first hump long enough not to be a name
+    // like McDonald
+    static ref CAMEL_CASE_RE: Regex =
+        Regex::new(r"\b(?:[a-z][a-z0-9]+|[A-Z][a-z0-9]{2,})[A-Z][A-Za-z0-9]*\b").unwrap();
+    static ref EXTENSION_RE: Regex = Regex::new(r"\.[A-Za-z0-9]{1,5}$").unwrap();
+    static ref SPACES_RE: Regex = Regex::new(r" {2,}").unwrap();
+}
+
+/// How much of a URL, email address or file path is read out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verbosity {
+    /// Everything, separators included: "github dot com slash foo"
+    Full,
+    /// The part that identifies it: "a link to github dot com", "the file lib dot R S"
+    #[default]
+    DomainOnly,
+    /// Only what it is: "a link", "an email address", "a file path"
+    Placeholder,
+}
+
+/// Rewrites URLs, email addresses, file paths and code identifiers into words
+/// espeak reads sensibly. Identifiers are always split into their words;
+/// `verbosity` decides how much of the rest is spoken.
+pub fn normalize_tech_tokens(text: &str, verbosity: Verbosity) -> String {
+    let text = URL_RE.replace_all(text, |caps: &Captures| speak_url(&caps[0], verbosity));
+    let text = EMAIL_RE.replace_all(&text, |caps: &Captures| speak_email(&caps[0], verbosity));
+    let text = PATH_RE.replace_all(&text, |caps: &Captures| {
+        let path = &caps[0];
+        if is_file_path(path) {
+            speak_path(path, verbosity)
+        } else {
+            path.to_string()
+        }
+    });
+    let text = SNAKE_CASE_RE.replace_all(&text, |caps: &Captures| speak_identifier(&caps[0]));
+    let text = CAMEL_CASE_RE.replace_all(&text, |caps: &Captures| speak_identifier(&caps[0]));
+    SPACES_RE.replace_all(&text, " ").to_string()
+}
+
+fn speak_url(url: &str, verbosity: Verbosity) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.strip_prefix("www.").unwrap_or(rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let domain = host.split(':').next().unwrap_or(host);
+    match verbosity {
+        Verbosity::Full => spell_out(rest.trim_end_matches('/')),
+        Verbosity::DomainOnly => format!("a link to {}", spell_out(domain)),
+        Verbosity::Placeholder => "a link".to_string(),
+    }
+}
+
+fn speak_email(email: &str, verbosity: Verbosity) -> String {
+    let domain = email.rsplit('@').next().unwrap_or(email);
+    match verbosity {
+        Verbosity::Full => spell_out(email),
+        Verbosity::DomainOnly => format!("an email address at {}", spell_out(domain)),
+        Verbosity::Placeholder => "an email address".to_string(),
+    }
+}
+
+/// Whether a slash-separated token is a path rather than "and/or", "he/she/they",
+/// "km/h" or a date. Relative paths need a file extension or a trailing slash.
+fn is_file_path(token: &str) -> bool {
+    if token
+        .chars()
+        .all(|c| c.is_ascii_digit() || "/\\.-".contains(c))
+    {
+        return false;
+    }
+    token.starts_with(['/', '~', '.'])
+        || token.contains(":\\")
+        || token.ends_with(['/', '\\'])
+        || EXTENSION_RE
+            .find(token)
+            // An extension has a letter, unlike the minor version in v1.2/v1.3
+            .is_some_and(|extension| extension.as_str().chars().any(|c| c.is_ascii_alphabetic()))
+}
+
+fn speak_path(path: &str, verbosity: Verbosity) -> String {
+    let trimmed = path.trim_end_matches(['/', '\\']);
+    let name = trimmed.rsplit(['/', '\\']).next().unwrap_or(trimmed);
+    match verbosity {
+        Verbosity::Full => spell_out(path),
+        Verbosity::DomainOnly if trimmed.len() < path.len() => {
+            format!("the folder {}", spell_out(name))
+        }
+        Verbosity::DomainOnly => format!("the file {}", spell_out(name)),
+        Verbosity::Placeholder => "a file path".to_string(),
+    }
+}
+
+/// `snake_case_name` -> "snake case name", `parseHTTPResponse` -> "parse H T T P response".
+fn speak_identifier(identifier: &str) -> String {
+    identifier
+        .split('_')
+        .map(speak_fragment)
+        .filter(|words| !words.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits at lower-to-upper changes and before the last capital of an acronym
+/// that starts a new word, e.g. `HTTPResponse` -> `HTTP`, `Response`.
+fn split_camel_case(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut words = vec![String::new()];
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if boundary {
+            words.push(String::new());
+        }
+        words.last_mut().unwrap().push(c);
+    }
+    words
+}
+
+/// Reads separators as words and the fragments between them with [`speak_fragment`].
+fn spell_out(text: &str) -> String {
+    let mut out = String::new();
+    let mut fragment = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            fragment.push(c);
+            continue;
+        }
+        out.push_str(&speak_fragment(&std::mem::take(&mut fragment)));
+        out.push_str(match c {
+            '.' => " dot ",
+            '/' => " slash ",
+            '\\' => " backslash ",
+            '-' => " dash ",
+            '_' => " underscore ",
+            ':' => " colon ",
+            '@' => " at ",
+            '?' => " question mark ",
+            '=' => " equals ",
+            '&' => " and ",
+            '#' => " hash ",
+            '~' => " home ",
+            '%' => " percent ",
+            '+' => " plus ",
+            _ => " ",
+        });
+    }
+    out.push_str(&speak_fragment(&fragment));
+    SPACES_RE.replace_all(out.trim(), " ").to_string()
+}
+
+fn speak_fragment(fragment: &str) -> String {
+    split_camel_case(fragment)
+        .iter()
+        .filter(|word| !word.is_empty())
+        .map(|word| speak_word(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Lowercases a word, spelling short ones without vowels letter by letter
+/// (`rs` -> "R S", `HTTP` -> "H T T P") since they're abbreviations.
+fn speak_word(word: &str) -> String {
+    let lower = word.to_lowercase();
+    let is_abbreviation = lower.len() <= 4
+        && lower.chars().all(|c| c.is_ascii_alphabetic())
+        && !lower.contains(['a', 'e', 'i', 'o', 'u', 'y']);
+    if is_abbreviation {
+        lower
+            .to_uppercase()
+            .chars()
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        lower
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urls() {
+        let text = "See https://github.com/foo/bar-baz.";
+        assert_eq!(
+            normalize_tech_tokens(text, Verbosity::Full),
+            "See github dot com slash foo slash bar dash baz."
+        );
+        assert_eq!(
+            normalize_tech_tokens(text, Verbosity::DomainOnly),
+            "See a link to github dot com."
+        );
+        assert_eq!(
+            normalize_tech_tokens(text, Verbosity::Placeholder),
+            "See a link."
+        );
+        assert_eq!(
+            normalize_tech_tokens(
+                "Go to www.example.org:8080/x?a=1 now",
+                Verbosity::DomainOnly
+            ),
+            "Go to a link to example dot org now"
+        );
+    }
+
+    #[test]
+    fn test_emails() {
+        let text = "Mail user.name@example.com today";
+        assert_eq!(
+            normalize_tech_tokens(text, Verbosity::Full),
+            "Mail user dot name at example dot com today"
+        );
+        assert_eq!(
+            normalize_tech_tokens(text, Verbosity::DomainOnly),
+            "Mail an email address at example dot com today"
+        );
+        assert_eq!(
+            normalize_tech_tokens(text, Verbosity::Placeholder),
+            "Mail an email address today"
+        );
+    }
+
+    #[test]
+    fn test_file_paths() {
+        assert_eq!(
+            normalize_tech_tokens("Edit src/lib.rs first", Verbosity::Full),
+            "Edit S R C slash lib dot R S first"
+        );
+        assert_eq!(
+            normalize_tech_tokens("Run /usr/bin/env or ~/bin/", Verbosity::DomainOnly),
+            "Run the file env or the folder bin"
+        );
+        assert_eq!(
+            normalize_tech_tokens(r"Open C:\Users\me\notes.txt", Verbosity::Placeholder),
+            "Open a file path"
+        );
+        // Slashes that aren't paths stay as they are
+        assert_eq!(
+            normalize_tech_tokens("and/or he/she/they 1/2/2024 at 50 km/h", Verbosity::Full),
+            "and/or he/she/they 1/2/2024 at 50 km/h"
+        );
+        assert_eq!(
+            normalize_tech_tokens("see v1.2/v1.3 notes", Verbosity::DomainOnly),
+            "see v1.2/v1.3 notes"
+        );
+        assert_eq!(
+            normalize_tech_tokens("unzip docs/v1.2/notes.7z", Verbosity::DomainOnly),
+            "unzip the file notes dot 7z"
+        );
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        assert_eq!(
+            normalize_text("Dr. Smith met MR. Jones and Mr. Brown."),
+            "Doctor Smith met Mister Jones and Mister Brown."
+        );
+        // Uppercase titles are only expanded before a name
+        assert_eq!(normalize_text("DR. and MS."), "DR. and MS.");
+        assert_eq!(
+            normalize_text("Pens, paper, etc. were sold. Also etc. Then more."),
+            "Pens, paper, etc were sold. Also etc. Then more."
+        );
+        assert_eq!(normalize_text("a\n  \n   \nb"), "a\n\n\nb");
+    }
+
+    #[test]
+    fn test_replace_between() {
+        let digit = |c: char| c.is_ascii_digit();
+        // Neighbours are checked, not consumed, so separators can be adjacent
+        assert_eq!(replace_between("1,000,000", ',', "", digit), "1000000");
+        assert_eq!(replace_between("1,2,3", ',', ";", digit), "1;2;3");
+        assert_eq!(replace_between(",1, a,b 2,", ',', "", digit), ",1, a,b 2,");
+        assert_eq!(replace_between("10-20", '-', " to ", digit), "10 to 20");
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(
+            normalize_tech_tokens("Call snake_case_name or MAX_SIZE", Verbosity::Full),
+            "Call snake case name or max size"
+        );
+        assert_eq!(
+            normalize_tech_tokens("Use parseHTTPResponse in JavaScript", Verbosity::Full),
+            "Use parse H T T P response in java script"
+        );
+        assert_eq!(
+            normalize_tech_tokens("Ask McDonald about iOS", Verbosity::Full),
+            "Ask McDonald about iOS"
+        );
+    }
+}
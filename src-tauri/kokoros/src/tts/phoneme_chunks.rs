@@ -0,0 +1,176 @@
+use crate::tts::segment::{split_sentences, terminate};
+use crate::tts::timings::align_words;
+use crate::tts::vocab::Vocab;
+
+/// Text of a chunk together with its phonemes, ready for inference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonemizedChunk {
+    pub text: String,
+    pub phonemes: String,
+}
+
+impl PhonemizedChunk {
+    pub fn token_count(&self, vocab: &Vocab) -> usize {
+        vocab.tokenize(&self.phonemes).len()
+    }
+}
+
+/// A sentence and its phonemes, kept word-aligned so it can be split further.
+struct Sentence {
+    text: String,
+    phonemes: String,
+    tokens: usize,
+}
+
+/// Splits `text` into chunks of at most `max_tokens` tokens of `vocab`,
+/// phonemizing every sentence (see [`split_sentences`]) exactly once. `phonemize` gets all sentences in one batch and returns
+/// their phonemes in the same order.
+///
+/// Sentences are merged while they fit, and a sentence that doesn't fit on its
+/// own is split between words. Token counts of merged sentences and of word runs
+/// are computed from the sentence's phonemes instead of phonemizing the candidate
+/// again: espeak phonemizes sentences as separate clauses and separates words with
+/// a space, so concatenating the parts gives the same tokens. Words are matched to
+/// espeak's words with [`align_words`], which keeps numbers and abbreviations that
+/// espeak expands into several words with the word they came from.
+pub fn chunk_phonemized<E>(
+    text: &str,
+    max_tokens: usize,
+    vocab: &Vocab,
+    phonemize: impl FnOnce(&[String]) -> Result<Vec<String>, E>,
+) -> Result<Vec<PhonemizedChunk>, E> {
+    let mut chunks = Vec::new();
+    let mut current: Option<PhonemizedChunk> = None;
+
+    let sentences: Vec<String> = split_sentences(text).into_iter().map(terminate).collect();
+    let phonemes = phonemize(&sentences)?;
+
+    for (text, phonemes) in sentences.into_iter().zip(phonemes) {
+        let sentence = Sentence {
+            tokens: vocab.tokenize(&phonemes).len(),
+            text,
+            phonemes,
+        };
+
+        if sentence.tokens > max_tokens {
+            // Keep the text in order: whatever was merged so far goes first
+            chunks.extend(current.take());
+            chunks.extend(split_sentence(&sentence, max_tokens, vocab));
+            continue;
+        }
+
+        current = Some(match current.take() {
+            Some(chunk) if chunk.token_count(vocab) + sentence.tokens <= max_tokens => {
+                PhonemizedChunk {
+                    text: format!("{} {}", chunk.text, sentence.text),
+                    phonemes: chunk.phonemes + &sentence.phonemes,
+                }
+            }
+            previous => {
+                chunks.extend(previous);
+                PhonemizedChunk {
+                    text: sentence.text,
+                    phonemes: sentence.phonemes,
+                }
+            }
+        });
+    }
+
+    chunks.extend(current);
+    Ok(chunks)
+}
+
+/// Splits an overlong sentence between words, greedily filling each chunk.
+fn split_sentence(sentence: &Sentence, max_tokens: usize, vocab: &Vocab) -> Vec<PhonemizedChunk> {
+    let text_words: Vec<&str> = sentence.text.split_whitespace().collect();
+    let phoneme_words: Vec<&str> = sentence.phonemes.split_whitespace().collect();
+
+    let mut chunks = Vec::new();
+    let mut text = String::new();
+    let mut phonemes = String::new();
+    let mut tokens = 0;
+
+    for (text_range, phoneme_range) in align_words(text_words.len(), phoneme_words.len()) {
+        let group_text = text_words[text_range].join(" ");
+        let group_phonemes = phoneme_words[phoneme_range].join(" ");
+        let group_tokens = vocab.tokenize(&group_phonemes).len();
+
+        // One more token for the space between words
+        if !text.is_empty() && tokens + 1 + group_tokens > max_tokens {
+            chunks.push(PhonemizedChunk {
+                text: std::mem::take(&mut text),
+                phonemes: std::mem::take(&mut phonemes),
+            });
+            tokens = 0;
+        }
+
+        if !text.is_empty() {
+            text.push(' ');
+            phonemes.push(' ');
+            tokens += 1;
+        }
+        text.push_str(&group_text);
+        phonemes.push_str(&group_phonemes);
+        tokens += group_tokens;
+    }
+
+    if !text.is_empty() {
+        chunks.push(PhonemizedChunk { text, phonemes });
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    /// Stands in for espeak: every letter, space and period is one token
+    fn phonemize(sentences: &[String]) -> Result<Vec<String>, Infallible> {
+        Ok(sentences.iter().map(|s| s.to_lowercase()).collect())
+    }
+
+    fn texts(chunks: &[PhonemizedChunk]) -> Vec<&str> {
+        chunks.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_merges_sentences_that_fit() {
+        let chunks =
+            chunk_phonemized("One. Two! Three?", 10, &Vocab::default(), phonemize).unwrap();
+        assert_eq!(texts(&chunks), vec!["One. Two!", "Three?"]);
+        assert_eq!(chunks[0].phonemes, "one.two!");
+        assert!(
+            chunks
+                .iter()
+                .all(|c| c.token_count(&Vocab::default()) <= 10)
+        );
+    }
+
+    #[test]
+    fn test_splits_long_sentence_between_words() {
+        let chunks =
+            chunk_phonemized("Hi. Aaa bbb ccc ddd. Bye.", 8, &Vocab::default(), phonemize).unwrap();
+        assert_eq!(texts(&chunks), vec!["Hi.", "Aaa bbb", "ccc ddd.", "Bye."]);
+        assert_eq!(chunks[2].phonemes, "ccc ddd.");
+        assert!(chunks.iter().all(|c| c.token_count(&Vocab::default()) <= 8));
+    }
+
+    #[test]
+    fn test_phonemizes_each_sentence_once() {
+        let mut calls = Vec::new();
+        let chunks = chunk_phonemized(
+            "A b c d e f. Go. H i j k.",
+            6,
+            &Vocab::default(),
+            |s: &[String]| {
+                calls.push(s.to_vec());
+                phonemize(s)
+            },
+        )
+        .unwrap();
+        assert_eq!(calls, vec![vec!["A b c d e f.", "Go.", "H i j k."]]);
+        let text: Vec<&str> = chunks.iter().flat_map(|c| c.text.split(' ')).collect();
+        assert_eq!(text.join(" "), "A b c d e f. Go. H i j k.");
+    }
+}
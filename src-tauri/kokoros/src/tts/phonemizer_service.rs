@@ -0,0 +1,323 @@
+use espeak_rs::text_to_phonemes;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Phonemized (text, language) pairs kept by the shared service
+pub const DEFAULT_CACHE_CAPACITY: usize = 4096;
+
+lazy_static! {
+    static ref GLOBAL: PhonemizerService = PhonemizerService::spawn(
+        |text, lang| {
+            text_to_phonemes(text, lang, None, true, false)
+                .map(|phonemes| phonemes.join(""))
+                .map_err(|e| e.to_string())
+        },
+        DEFAULT_CACHE_CAPACITY,
+    );
+}
+
+/// Phonemizes one text in the given language
+pub type Backend = dyn FnMut(&str, &str) -> Result<String, String> + Send;
+
+/// Texts to phonemize in one language, answered all at once
+struct Request {
+    texts: Vec<String>,
+    lang: String,
+    reply: Sender<Result<Vec<String>, String>>,
+}
+
+/// Snapshot of the service's counters since it started.
+#[derive(Debug, Clone, Serialize)]
+pub struct PhonemizerMetrics {
+    pub requests: u64,
+    /// Groups of requests the backend thread picked up together
+    pub batches: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub texts_phonemized: u64,
+    /// Time the backend thread spent phonemizing
+    pub busy_secs: f64,
+    pub uptime_secs: f64,
+    /// Backend throughput while busy
+    pub texts_per_sec: f64,
+    /// Share of the uptime the backend was busy; close to 1 means phonemization
+    /// is the bottleneck and more ONNX instances won't help
+    pub utilization: f64,
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    batches: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    texts_phonemized: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+/// Least-recently-used cache of phonemes keyed by (text, language).
+///
+/// Eviction scans for the oldest entry, which is cheap next to a single espeak call
+/// at the capacities used here.
+struct LruCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<(String, String), (String, u64)>,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, text: &str, lang: &str) -> Option<String> {
+        self.tick += 1;
+        let entry = self
+            .entries
+            .get_mut(&(text.to_string(), lang.to_string()))?;
+        entry.1 = self.tick;
+        Some(entry.0.clone())
+    }
+
+    fn insert(&mut self, text: String, lang: String, phonemes: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        let key = (text, lang);
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (phonemes, self.tick));
+    }
+}
+
+/// Owns the phonemizer backend on a dedicated thread.
+///
+/// espeak keeps process-wide state and can't be called concurrently, so rather than
+/// having every synthesis thread take a lock around it, callers send requests over a
+/// channel. The backend thread drains whatever is queued and answers it as one batch,
+/// and cached texts never reach it at all.
+pub struct PhonemizerService {
+    sender: Sender<Request>,
+    cache: Mutex<LruCache>,
+    counters: Arc<Counters>,
+    started: Instant,
+}
+
+impl PhonemizerService {
+    /// The process-wide espeak service.
+    pub fn global() -> &'static PhonemizerService {
+        &GLOBAL
+    }
+
+    /// Starts a service running `backend` on its own thread. The thread exits when
+    /// the service is dropped.
+    pub fn spawn(
+        backend: impl FnMut(&str, &str) -> Result<String, String> + Send + 'static,
+        cache_capacity: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let counters = Arc::new(Counters::default());
+
+        let thread_counters = Arc::clone(&counters);
+        thread::Builder::new()
+            .name("phonemizer".to_string())
+            .spawn(move || run_backend(Box::new(backend), receiver, thread_counters))
+            .expect("Failed to spawn phonemizer thread");
+
+        Self {
+            sender,
+            cache: Mutex::new(LruCache::new(cache_capacity)),
+            counters,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn phonemize(&self, text: &str, lang: &str) -> Result<String, String> {
+        Ok(self.phonemize_batch(&[text], lang)?.remove(0))
+    }
+
+    /// Phonemizes `texts` in order, sending the ones that aren't cached to the
+    /// backend as a single request.
+    pub fn phonemize_batch(&self, texts: &[&str], lang: &str) -> Result<Vec<String>, String> {
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+
+        let mut results: Vec<Option<String>> = {
+            let mut cache = self.cache.lock().unwrap();
+            texts.iter().map(|text| cache.get(text, lang)).collect()
+        };
+        let misses: Vec<usize> = (0..texts.len()).filter(|&i| results[i].is_none()).collect();
+        self.counters
+            .cache_hits
+            .fetch_add((texts.len() - misses.len()) as u64, Ordering::Relaxed);
+        self.counters
+            .cache_misses
+            .fetch_add(misses.len() as u64, Ordering::Relaxed);
+
+        if !misses.is_empty() {
+            let (reply, response) = mpsc::channel();
+            let request = Request {
+                texts: misses.iter().map(|&i| texts[i].to_string()).collect(),
+                lang: lang.to_string(),
+                reply,
+            };
+            self.sender
+                .send(request)
+                .map_err(|_| "phonemizer thread has stopped".to_string())?;
+            let phonemized = response
+                .recv()
+                .map_err(|_| "phonemizer thread has stopped".to_string())??;
+
+            let mut cache = self.cache.lock().unwrap();
+            for (&i, phonemes) in misses.iter().zip(phonemized) {
+                cache.insert(texts[i].to_string(), lang.to_string(), phonemes.clone());
+                results[i] = Some(phonemes);
+            }
+        }
+
+        Ok(results.into_iter().map(Option::unwrap_or_default).collect())
+    }
+
+    pub fn metrics(&self) -> PhonemizerMetrics {
+        let c = &self.counters;
+        let texts_phonemized = c.texts_phonemized.load(Ordering::Relaxed);
+        let busy_secs = Duration::from_nanos(c.busy_nanos.load(Ordering::Relaxed)).as_secs_f64();
+        let uptime_secs = self.started.elapsed().as_secs_f64();
+        PhonemizerMetrics {
+            requests: c.requests.load(Ordering::Relaxed),
+            batches: c.batches.load(Ordering::Relaxed),
+            cache_hits: c.cache_hits.load(Ordering::Relaxed),
+            cache_misses: c.cache_misses.load(Ordering::Relaxed),
+            texts_phonemized,
+            busy_secs,
+            uptime_secs,
+            texts_per_sec: if busy_secs > 0.0 {
+                texts_phonemized as f64 / busy_secs
+            } else {
+                0.0
+            },
+            utilization: if uptime_secs > 0.0 {
+                (busy_secs / uptime_secs).min(1.0)
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+fn run_backend(mut backend: Box<Backend>, receiver: Receiver<Request>, counters: Arc<Counters>) {
+    while let Ok(first) = receiver.recv() {
+        // Everything queued while the previous batch ran is handled together
+        let batch: Vec<Request> = std::iter::once(first).chain(receiver.try_iter()).collect();
+        let started = Instant::now();
+
+        for request in batch {
+            let phonemized: Result<Vec<String>, String> = request
+                .texts
+                .iter()
+                .map(|text| backend(text, &request.lang))
+                .collect();
+            if let Ok(phonemized) = &phonemized {
+                counters
+                    .texts_phonemized
+                    .fetch_add(phonemized.len() as u64, Ordering::Relaxed);
+            }
+            // The caller may have given up waiting
+            let _ = request.reply.send(phonemized);
+        }
+
+        counters.batches.fetch_add(1, Ordering::Relaxed);
+        counters
+            .busy_nanos
+            .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upper_case_service(capacity: usize) -> (PhonemizerService, Arc<AtomicU64>) {
+        let calls = Arc::new(AtomicU64::new(0));
+        let backend_calls = Arc::clone(&calls);
+        let service = PhonemizerService::spawn(
+            move |text, lang| {
+                backend_calls.fetch_add(1, Ordering::Relaxed);
+                if text == "fail" {
+                    return Err("unsupported".to_string());
+                }
+                Ok(format!("{}:{}", lang, text.to_uppercase()))
+            },
+            capacity,
+        );
+        (service, calls)
+    }
+
+    #[test]
+    fn test_batch_and_cache() {
+        let (service, calls) = upper_case_service(8);
+
+        let batch = service.phonemize_batch(&["a", "b", "a"], "en").unwrap();
+        assert_eq!(batch, vec!["en:A", "en:B", "en:A"]);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        assert_eq!(service.phonemize("b", "en").unwrap(), "en:B");
+        assert_eq!(service.phonemize("b", "fr").unwrap(), "fr:B");
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+        assert!(service.phonemize("fail", "en").is_err());
+
+        let metrics = service.metrics();
+        assert_eq!(metrics.requests, 4);
+        assert_eq!(metrics.cache_hits, 1);
+        assert_eq!(metrics.cache_misses, 5);
+        assert_eq!(metrics.texts_phonemized, 4);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a".into(), "en".into(), "A".into());
+        cache.insert("b".into(), "en".into(), "B".into());
+        assert_eq!(cache.get("a", "en").as_deref(), Some("A"));
+
+        cache.insert("c".into(), "en".into(), "C".into());
+        assert_eq!(cache.get("b", "en"), None);
+        assert_eq!(cache.get("a", "en").as_deref(), Some("A"));
+        assert_eq!(cache.get("c", "en").as_deref(), Some("C"));
+    }
+
+    #[test]
+    fn test_concurrent_callers() {
+        let (service, _) = upper_case_service(0);
+        let service = Arc::new(service);
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let service = Arc::clone(&service);
+                thread::spawn(move || service.phonemize(&format!("t{}", i), "en").unwrap())
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), format!("en:T{}", i));
+        }
+        assert_eq!(service.metrics().texts_phonemized, 8);
+    }
+}
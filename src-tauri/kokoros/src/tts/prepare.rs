@@ -0,0 +1,352 @@
+use crate::tts::normalize::{Verbosity, normalize_tech_tokens};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    static ref FENCE_RE: Regex = Regex::new(r"^\s*(```|~~~)\s*([\w+#.-]*)").unwrap();
+    static ref HEADING_RE: Regex = Regex::new(r"^\s{0,3}#{1,6}\s+(.*?)[\s#]*$").unwrap();
+    static ref SETEXT_RE: Regex = Regex::new(r"^\s{0,3}(=+|-+)\s*$").unwrap();
+    static ref RULE_RE: Regex = Regex::new(r"^\s{0,3}([-*_])(\s*[-*_]){2,}\s*$").unwrap();
+    static ref QUOTE_RE: Regex = Regex::new(r"^\s*(>\s?)+").unwrap();
+    static ref LIST_ITEM_RE: Regex =
+        Regex::new(r"^\s*(?:[-*+]|\d{1,9}[.)])\s+(?:\[[ xX]\]\s+)?(.*)$").unwrap();
+    static ref TABLE_SEPARATOR_RE: Regex =
+        Regex::new(r"^\s*\|?\s*:?-{3,}:?\s*(\|\s*:?-{3,}:?\s*)*\|?\s*$").unwrap();
+    static ref IMAGE_RE: Regex = Regex::new(r"!\[([^\]]*)\]\([^)]*\)").unwrap();
+    static ref LINK_RE: Regex = Regex::new(r"\[([^\]]+)\]\([^)]*\)").unwrap();
+    static ref REFERENCE_LINK_RE: Regex = Regex::new(r"\[([^\]]+)\]\[[^\]]*\]").unwrap();
+    static ref AUTOLINK_RE: Regex = Regex::new(r"<((?:https?|ftp)://[^>\s]+)>").unwrap();
+    static ref INLINE_CODE_RE: Regex = Regex::new(r"`+([^`]+)`+").unwrap();
+    static ref STRONG_STAR_RE: Regex = Regex::new(r"\*\*(\S(?:.*?\S)?)\*\*").unwrap();
+    static ref EMPHASIS_STAR_RE: Regex = Regex::new(r"\*(\S(?:[^*]*?\S)?)\*").unwrap();
+    // `_` only marks emphasis at word boundaries, snake_case and __dunder__ names
+    // keep theirs
+    static ref STRONG_UNDERSCORE_RE: Regex =
+        Regex::new(r"(^|[^\w])__(\S(?:.*?\S)?)__([^\w]|$)").unwrap();
+    static ref EMPHASIS_UNDERSCORE_RE: Regex =
+        Regex::new(r"(^|[^\w])_(\S(?:[^_]*?\S)?)_([^\w]|$)").unwrap();
+    static ref STRIKE_RE: Regex = Regex::new(r"~~(.+?)~~").unwrap();
+    static ref HTML_TAG_RE: Regex = Regex::new(r"</?[A-Za-z][^>]*>").unwrap();
+    static ref SPACES_RE: Regex = Regex::new(r"\s+").unwrap();
+    static ref SPACE_BEFORE_PUNCTUATION_RE: Regex = Regex::new(r"\s+([,.!?;:])").unwrap();
+}
+
+/// Emoji with the name they're read as under [`EmojiPolicy::Describe`]
+const EMOJI_NAMES: &[(&str, &str)] = &[
+    ("😀", "grinning face"),
+    ("😃", "smiling face"),
+    ("😄", "smiling face"),
+    ("😁", "beaming face"),
+    ("😂", "face with tears of joy"),
+    ("🙂", "slightly smiling face"),
+    ("😊", "smiling face"),
+    ("😉", "winking face"),
+    ("😍", "heart eyes"),
+    ("😅", "nervous laugh"),
+    ("🤔", "thinking face"),
+    ("😢", "crying face"),
+    ("😭", "loudly crying face"),
+    ("😮", "surprised face"),
+    ("😎", "smiling face with sunglasses"),
+    ("🙃", "upside-down face"),
+    ("👍", "thumbs up"),
+    ("👎", "thumbs down"),
+    ("👋", "waving hand"),
+    ("👏", "clapping hands"),
+    ("🙏", "folded hands"),
+    ("👀", "eyes"),
+    ("❤", "red heart"),
+    ("🎉", "party popper"),
+    ("✨", "sparkles"),
+    ("🔥", "fire"),
+    ("🚀", "rocket"),
+    ("💡", "light bulb"),
+    ("⚠", "warning"),
+    ("✅", "check mark"),
+    ("❌", "cross mark"),
+    ("⭐", "star"),
+];
+
+/// What happens to fenced code blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeBlockPolicy {
+    /// Say that there is a code block, naming its language when known
+    #[default]
+    Announce,
+    Skip,
+    /// Read the code as it is
+    Read,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmojiPolicy {
+    #[default]
+    Remove,
+    /// Read common emoji by name and remove the rest
+    Describe,
+    /// Leave emoji to the phonemizer
+    Keep,
+}
+
+/// How [`prepare_for_speech`] renders text that isn't plain prose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrepareOptions {
+    pub code_blocks: CodeBlockPolicy,
+    pub emoji: EmojiPolicy,
+    /// How much of URLs, email addresses and file paths outside link text is
+    /// read; link text itself is always kept
+    pub verbosity: Verbosity,
+}
+
+/// Turns markdown (as LLMs write it) into prose Kokoro can read.
+///
+/// Headings, list items and table rows become sentences of their own, links keep
+/// their text, emphasis and inline code markers are dropped, and code blocks,
+/// bare URLs, paths and emoji are handled according to `options`.
+pub fn prepare_for_speech(text: &str, options: &PrepareOptions) -> String {
+    let mut sentences: Vec<String> = Vec::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut code: Option<(String, String, Vec<&str>)> = None;
+
+    let flush = |paragraph: &mut Vec<String>, sentences: &mut Vec<String>| {
+        if !paragraph.is_empty() {
+            sentences.push(terminate(&paragraph.join(" ")));
+            paragraph.clear();
+        }
+    };
+
+    for line in text.lines() {
+        if let Some((fence, language, lines)) = code.as_mut() {
+            if line.trim_start().starts_with(fence.as_str()) {
+                if let Some(spoken) = speak_code_block(language, lines, options.code_blocks) {
+                    sentences.push(spoken);
+                }
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+        if let Some(caps) = FENCE_RE.captures(line) {
+            flush(&mut paragraph, &mut sentences);
+            code = Some((caps[1].to_string(), caps[2].to_string(), Vec::new()));
+            continue;
+        }
+
+        // "Title\n=====" headings: the title is already in the paragraph
+        if SETEXT_RE.is_match(line) && !paragraph.is_empty() {
+            let title = paragraph.pop().unwrap();
+            flush(&mut paragraph, &mut sentences);
+            sentences.push(terminate(&title));
+            continue;
+        }
+        if line.trim().is_empty() || RULE_RE.is_match(line) || TABLE_SEPARATOR_RE.is_match(line) {
+            flush(&mut paragraph, &mut sentences);
+            continue;
+        }
+
+        let line = QUOTE_RE.replace(line, "");
+        if let Some(caps) = HEADING_RE.captures(&line) {
+            flush(&mut paragraph, &mut sentences);
+            sentences.push(terminate(&render_inline(&caps[1], options)));
+        } else if let Some(caps) = LIST_ITEM_RE.captures(&line) {
+            flush(&mut paragraph, &mut sentences);
+            sentences.push(terminate(&render_inline(&caps[1], options)));
+        } else if line.trim_start().starts_with('|') {
+            flush(&mut paragraph, &mut sentences);
+            let cells: Vec<String> = line
+                .trim()
+                .trim_matches('|')
+                .split('|')
+                .map(|cell| render_inline(cell, options))
+                .filter(|cell| !cell.is_empty())
+                .collect();
+            if !cells.is_empty() {
+                sentences.push(terminate(&cells.join(", ")));
+            }
+        } else {
+            paragraph.push(render_inline(&line, options));
+        }
+    }
+    // An unclosed fence runs to the end of the text
+    let unclosed = code
+        .and_then(|(_, language, lines)| speak_code_block(&language, &lines, options.code_blocks));
+    sentences.extend(unclosed);
+    flush(&mut paragraph, &mut sentences);
+
+    let text = sentences
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let text = SPACES_RE.replace_all(&text, " ");
+    SPACE_BEFORE_PUNCTUATION_RE
+        .replace_all(&text, "$1")
+        .trim()
+        .to_string()
+}
+
+fn speak_code_block(language: &str, lines: &[&str], policy: CodeBlockPolicy) -> Option<String> {
+    match policy {
+        CodeBlockPolicy::Skip => None,
+        CodeBlockPolicy::Announce if language.is_empty() => Some("Here is a code block.".into()),
+        CodeBlockPolicy::Announce => Some(format!("Here is a {} code block.", language)),
+        CodeBlockPolicy::Read => Some(terminate(&lines.join(" "))),
+    }
+}
+
+/// Strips inline markdown from a line of prose.
+fn render_inline(text: &str, options: &PrepareOptions) -> String {
+    let text = IMAGE_RE.replace_all(text, "$1");
+    let text = LINK_RE.replace_all(&text, "$1");
+    let text = REFERENCE_LINK_RE.replace_all(&text, "$1");
+    let text = AUTOLINK_RE.replace_all(&text, "$1");
+    let text = INLINE_CODE_RE.replace_all(&text, "$1");
+    let text = STRONG_STAR_RE.replace_all(&text, "$1");
+    let text = STRONG_UNDERSCORE_RE.replace_all(&text, "$1$2$3");
+    let text = EMPHASIS_STAR_RE.replace_all(&text, "$1");
+    let text = EMPHASIS_UNDERSCORE_RE.replace_all(&text, "$1$2$3");
+    let text = STRIKE_RE.replace_all(&text, "$1");
+    let text = HTML_TAG_RE.replace_all(&text, " ");
+    let text = normalize_tech_tokens(&text, options.verbosity);
+    render_emoji(&text, options.emoji).trim().to_string()
+}
+
+fn render_emoji(text: &str, policy: EmojiPolicy) -> String {
+    if policy == EmojiPolicy::Keep {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !is_emoji(c) {
+            out.push(c);
+            continue;
+        }
+        if policy == EmojiPolicy::Describe {
+            let mut buf = [0; 4];
+            let name = EMOJI_NAMES
+                .iter()
+                .find(|(emoji, _)| *emoji == c.encode_utf8(&mut buf))
+                .map(|(_, name)| *name);
+            if let Some(name) = name {
+                out.push(' ');
+                out.push_str(name);
+                out.push(' ');
+            }
+        }
+    }
+    out
+}
+
+/// Emoji, their modifiers and the joiners and selectors that combine them.
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF // pictographs, emoticons, transport, skin tones, ...
+        | 0x2600..=0x27BF // miscellaneous symbols and dingbats
+        | 0x2B50 | 0x2B55 | 0x2B1B | 0x2B1C // stars and squares
+        | 0xFE0F | 0x200D | 0x20E3 // variation selector, zero-width joiner, keycap
+        | 0xE0020..=0xE007F // flag tags
+    )
+}
+
+/// Ends `text` with sentence punctuation unless it already has some.
+fn terminate(text: &str) -> String {
+    let text = text.trim();
+    match text.chars().last() {
+        None => String::new(),
+        Some(c) if ".!?:;…".contains(c) => text.to_string(),
+        Some(_) => format!("{}.", text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepare(text: &str) -> String {
+        prepare_for_speech(text, &PrepareOptions::default())
+    }
+
+    #[test]
+    fn test_structure() {
+        let markdown = "# Getting started\n\
+            Install it **first**, then run it:\n\
+            \n\
+            - Open the *settings*\n\
+            - Pick a voice\n\
+            1. Save\n\
+            \n\
+            > Quoted text\n\
+            \n\
+            ---\n\
+            | Name | Size |\n\
+            |------|-----:|\n\
+            | fp32 | 310 MB |";
+        assert_eq!(
+            prepare(markdown),
+            "Getting started. Install it first, then run it: Open the settings. \
+             Pick a voice. Save. Quoted text. Name, Size. fp32, 310 MB."
+        );
+    }
+
+    #[test]
+    fn test_links_and_inline_code() {
+        assert_eq!(
+            prepare("See [the docs](https://example.com/docs) or https://www.github.com/foo/bar."),
+            "See the docs or a link to github dot com."
+        );
+        let placeholder = PrepareOptions {
+            verbosity: Verbosity::Placeholder,
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare_for_speech("Go to <https://example.com> now", &placeholder),
+            "Go to a link now."
+        );
+        assert_eq!(
+            prepare("Call `my_function` with snake_case_name and _emphasis_."),
+            "Call my function with snake case name and emphasis."
+        );
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        let text = "Try this:\n```rust\nfn main() {}\n```\nDone.";
+        assert_eq!(prepare(text), "Try this: Here is a rust code block. Done.");
+
+        let skip = PrepareOptions {
+            code_blocks: CodeBlockPolicy::Skip,
+            ..Default::default()
+        };
+        assert_eq!(prepare_for_speech(text, &skip), "Try this: Done.");
+
+        let read = PrepareOptions {
+            code_blocks: CodeBlockPolicy::Read,
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare_for_speech(text, &read),
+            "Try this: fn main() {}. Done."
+        );
+    }
+
+    #[test]
+    fn test_emoji() {
+        let text = "Great job 👍🏽! Ship it 🚀🧑‍💻";
+        assert_eq!(prepare(text), "Great job! Ship it.");
+
+        let describe = PrepareOptions {
+            emoji: EmojiPolicy::Describe,
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare_for_speech(text, &describe),
+            "Great job thumbs up! Ship it rocket."
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::tts::chunking::ChunkingPolicy;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Time-to-first-audio the streaming path aims for unless a request sets its own
+pub const DEFAULT_FIRST_AUDIO_MS: f64 = 400.0;
+
+/// Words spoken per second at speed 1.0, about 150 words a minute
+const WORDS_PER_SECOND: f64 = 2.5;
+
+/// Fewest words a latency-sized first chunk gets; shorter ones sound clipped
+const MIN_FIRST_WORDS: usize = 3;
+
+/// Most words a later chunk gets, so one chunk never holds up the stream
+const MAX_LATER_WORDS: usize = 40;
+
+/// Weight of the newest measurement in the running average
+const SMOOTHING: f64 = 0.3;
+
+/// Chunks shorter than this are dominated by fixed per-call costs
+const MIN_MEASURED_AUDIO_SECS: f64 = 0.1;
+
+/// Running estimate of how fast a model instance synthesizes, in milliseconds of
+/// compute per second of audio (the real-time factor times 1000).
+#[derive(Debug, Default)]
+pub struct RealTimeFactor {
+    ms_per_audio_sec: Mutex<Option<f64>>,
+}
+
+impl RealTimeFactor {
+    /// Adds a measurement of `compute` spent on `samples` samples of audio.
+    pub fn record(&self, compute: Duration, samples: usize, sample_rate: u32) {
+        let audio_secs = samples as f64 / sample_rate.max(1) as f64;
+        if audio_secs < MIN_MEASURED_AUDIO_SECS {
+            return;
+        }
+        let measured = compute.as_secs_f64() * 1000.0 / audio_secs;
+        let mut average = self.ms_per_audio_sec.lock().unwrap();
+        *average = Some(match *average {
+            Some(average) => average + SMOOTHING * (measured - average),
+            None => measured,
+        });
+    }
+
+    /// Forgets what was measured, e.g. after the model was reloaded.
+    pub fn reset(&self) {
+        *self.ms_per_audio_sec.lock().unwrap() = None;
+    }
+
+    /// Current estimate, `None` until a chunk has been measured.
+    pub fn ms_per_audio_sec(&self) -> Option<f64> {
+        *self.ms_per_audio_sec.lock().unwrap()
+    }
+}
+
+/// Chunk sizes chosen for a stream, reported to the client with the stream.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkSizing {
+    pub first_chunk_words: usize,
+    pub later_chunk_words: usize,
+    /// Speed the sizes were derived from, `None` when nothing was measured yet
+    /// and the policy's own sizes are used
+    pub ms_per_audio_sec: Option<f64>,
+    pub estimated_first_audio_ms: Option<f64>,
+}
+
+/// Adapts `policy` to the measured synthesis speed.
+///
+/// The first chunk gets as many words as one instance can synthesize within
+/// `first_audio_ms`. Each later chunk may take as long to synthesize as the
+/// chunk before it takes to play, so chunks grow by `instances / rtf` and
+/// generation stays ahead of playback. When synthesis is slower than real time
+/// that's impossible, and later chunks get the largest size to keep per-call
+/// overhead down.
+pub fn size_chunks(
+    policy: &ChunkingPolicy,
+    ms_per_audio_sec: Option<f64>,
+    first_audio_ms: f64,
+    speed: f32,
+    instances: usize,
+) -> (ChunkingPolicy, ChunkSizing) {
+    let Some(ms) = ms_per_audio_sec.filter(|ms| *ms > 0.0) else {
+        let sizing = ChunkSizing {
+            first_chunk_words: policy.first_chunk_words,
+            later_chunk_words: policy.max_words,
+            ms_per_audio_sec: None,
+            estimated_first_audio_ms: None,
+        };
+        return (policy.clone(), sizing);
+    };
+
+    let words_per_sec = WORDS_PER_SECOND * speed.max(0.1) as f64;
+    let first = ((first_audio_ms / ms * words_per_sec) as usize)
+        .clamp(MIN_FIRST_WORDS, policy.max_words.max(MIN_FIRST_WORDS));
+
+    let rtf = ms / 1000.0;
+    let instances = instances.max(1) as f64;
+    let later = if rtf >= instances {
+        MAX_LATER_WORDS
+    } else {
+        ((first as f64 * instances / rtf) as usize).clamp(first, MAX_LATER_WORDS.max(first))
+    };
+
+    let adapted = ChunkingPolicy {
+        // Chunks are split once they reach these, so allow one word more
+        first_chunk_words: first + 1,
+        max_words: later + 1,
+        target_words: policy.target_words.min(later),
+        hard_split_first: true,
+        ..policy.clone()
+    };
+    let sizing = ChunkSizing {
+        first_chunk_words: first,
+        later_chunk_words: later,
+        ms_per_audio_sec: Some(ms),
+        estimated_first_audio_ms: Some(first as f64 / words_per_sec * ms),
+    };
+    (adapted, sizing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_real_time_factor() {
+        let rtf = RealTimeFactor::default();
+        assert_eq!(rtf.ms_per_audio_sec(), None);
+
+        // 200 ms for one second of audio, then 400 ms for one second
+        rtf.record(Duration::from_millis(200), 24000, 24000);
+        assert!((rtf.ms_per_audio_sec().unwrap() - 200.0).abs() < 1e-6);
+        rtf.record(Duration::from_millis(400), 24000, 24000);
+        assert!((rtf.ms_per_audio_sec().unwrap() - 260.0).abs() < 1e-6);
+
+        // Too short to say anything
+        rtf.record(Duration::from_secs(5), 100, 24000);
+        assert!((rtf.ms_per_audio_sec().unwrap() - 260.0).abs() < 1e-6);
+
+        rtf.reset();
+        assert_eq!(rtf.ms_per_audio_sec(), None);
+    }
+
+    #[test]
+    fn test_size_chunks() {
+        let policy = ChunkingPolicy::default();
+
+        let (same, sizing) = size_chunks(&policy, None, 400.0, 1.0, 1);
+        assert_eq!(same, policy);
+        assert_eq!(sizing.first_chunk_words, 12);
+
+        // 200 ms per second of audio: 2 s of audio in 400 ms is 5 words, and
+        // each later chunk can be 5x the one playing before it
+        let (fast, sizing) = size_chunks(&policy, Some(200.0), 400.0, 1.0, 1);
+        assert_eq!(
+            (sizing.first_chunk_words, sizing.later_chunk_words),
+            (5, 25)
+        );
+        assert_eq!(fast.first_chunk_words, 6);
+        assert!(fast.hard_split_first);
+        assert!((sizing.estimated_first_audio_ms.unwrap() - 400.0).abs() < 1e-6);
+
+        // Slower than real time: a short first chunk, then the largest chunks
+        let (_, sizing) = size_chunks(&policy, Some(1500.0), 400.0, 1.0, 1);
+        assert_eq!(sizing.first_chunk_words, MIN_FIRST_WORDS);
+        assert_eq!(sizing.later_chunk_words, MAX_LATER_WORDS);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::error::KokoError;
+use crate::onn::session_config::SessionConfig;
+use crate::tts::koko::InitConfig;
+use crate::utils::download::{self, Download, DownloadManager};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Manifest of the model variants this crate knows about
+const BUNDLED_MANIFEST: &str = include_str!("models.json");
+
+/// Numeric precision of a model export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    Fp32,
+    Fp16,
+    Int8,
+}
+
+/// A file a variant needs, with what it's checked against after download.
+/// Model files always have a checksum; voice sets may not, and are then only
+/// checked for their size.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryFile {
+    pub file: String,
+    pub url: String,
+    pub size: Option<u64>,
+    /// Lowercase hex SHA-256 of the file
+    pub sha256: Option<String>,
+    /// URLs tried in turn when `url` fails
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+}
+
+impl RegistryFile {
+    pub fn download(&self) -> Download {
+        Download {
+            name: self.file.clone(),
+            urls: std::iter::once(&self.url)
+                .chain(&self.mirrors)
+                .cloned()
+                .collect(),
+            size: self.size,
+            sha256: self.sha256.clone(),
+        }
+    }
+}
+
+/// Voices file shared by the variants of a model, and the voices in it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoiceSet {
+    #[serde(flatten)]
+    pub download: RegistryFile,
+    pub voices: Vec<String>,
+}
+
+/// One export of a model, e.g. the int8 quantization of Kokoro v1.0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelVariant {
+    pub id: String,
+    pub description: String,
+    pub precision: Precision,
+    #[serde(flatten)]
+    pub download: RegistryFile,
+    /// Model id the vocabulary and limits are looked up by, see
+    /// [`ModelConfig::load`](crate::tts::model_config::ModelConfig::load)
+    pub vocab: String,
+    /// Key of the variant's voices in [`ModelRegistry::voice_sets`]
+    pub voice_set: String,
+    /// Session settings the variant runs best with
+    #[serde(default)]
+    pub session: SessionConfig,
+}
+
+/// Model variants that can be installed and selected, read from a manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelRegistry {
+    pub voice_sets: HashMap<String, VoiceSet>,
+    pub variants: Vec<ModelVariant>,
+}
+
+impl ModelRegistry {
+    /// The manifest bundled with this crate.
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_MANIFEST).expect("bundled model manifest is invalid")
+    }
+
+    /// Parses a manifest, checking that every variant has a checksum and that
+    /// its voice set is listed.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let registry: ModelRegistry =
+            serde_json::from_str(json).map_err(|e| format!("invalid model manifest: {}", e))?;
+        for variant in &registry.variants {
+            if variant.download.sha256.is_none() {
+                return Err(format!("model variant {} has no sha256", variant.id));
+            }
+            if !registry.voice_sets.contains_key(&variant.voice_set) {
+                return Err(format!(
+                    "model variant {} uses unknown voice set {}",
+                    variant.id, variant.voice_set
+                ));
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn variant(&self, id: &str) -> Option<&ModelVariant> {
+        self.variants.iter().find(|variant| variant.id == id)
+    }
+
+    pub fn voice_set(&self, variant: &ModelVariant) -> &VoiceSet {
+        // from_json checked that it's there
+        &self.voice_sets[&variant.voice_set]
+    }
+
+    /// Model and voices paths of `variant`, each from the first of `dirs`
+    /// that has the file at its listed size; `None` unless both are there.
+    pub fn installed_paths(
+        &self,
+        variant: &ModelVariant,
+        dirs: &[PathBuf],
+    ) -> Option<(PathBuf, PathBuf)> {
+        let model = find_file(&variant.download, dirs)?;
+        let voices = find_file(&self.voice_set(variant).download, dirs)?;
+        Some((model, voices))
+    }
+
+    /// Model and voices paths of `variant`, downloading the files none of
+    /// `dirs` has into `target` and verifying them against the manifest.
+    /// Installed files are verified when the manifest has a checksum for them.
+    pub async fn install(
+        &self,
+        variant: &ModelVariant,
+        dirs: &[PathBuf],
+        target: &Path,
+        downloads: &DownloadManager,
+    ) -> Result<(PathBuf, PathBuf), KokoError> {
+        let mut paths = Vec::new();
+        for file in [&variant.download, &self.voice_set(variant).download] {
+            let installed = find_file(file, dirs).filter(|path| {
+                file.sha256.is_none()
+                    || download::verify(path, &file.download())
+                        .inspect_err(|e| tracing::warn!("Not using {}: {}", path.display(), e))
+                        .is_ok()
+            });
+            let path = match installed {
+                Some(path) => path,
+                None => {
+                    let path = target.join(&file.file);
+                    downloads.fetch(&file.download(), &path).await?;
+                    path
+                }
+            };
+            paths.push(path);
+        }
+        let voices = paths.pop().unwrap_or_default();
+        let model = paths.pop().unwrap_or_default();
+        Ok((model, voices))
+    }
+
+    /// Init config loading `variant` with its vocabulary and recommended settings.
+    pub fn init_config(&self, variant: &ModelVariant) -> InitConfig {
+        InitConfig {
+            model_url: variant.download.url.clone(),
+            voices_url: self.voice_set(variant).download.url.clone(),
+            model_id: Some(variant.vocab.clone()),
+            session: variant.session.clone(),
+            ..InitConfig::default()
+        }
+    }
+}
+
+/// `file` in the first of `dirs` that has it at its listed size.
+pub fn find_file(file: &RegistryFile, dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter()
+        .find(|dir| has_file(dir, file))
+        .map(|dir| dir.join(&file.file))
+}
+
+fn has_file(dir: &Path, download: &RegistryFile) -> bool {
+    match std::fs::metadata(dir.join(&download.file)) {
+        Ok(meta) => meta.is_file() && download.size.is_none_or(|size| meta.len() == size),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::download;
+
+    #[test]
+    fn test_bundled_manifest() {
+        let registry = ModelRegistry::bundled();
+        let precisions: Vec<Precision> = registry.variants.iter().map(|v| v.precision).collect();
+        assert_eq!(precisions, vec![Precision::Fp32]);
+        let fp32 = registry.variant("kokoro-v1.0-fp32").unwrap();
+        assert!(
+            registry
+                .voice_set(fp32)
+                .voices
+                .contains(&"af_heart".to_string())
+        );
+
+        let config = registry.init_config(fp32);
+        assert_eq!(config.model_id.as_deref(), Some("kokoro-v1.0"));
+        assert_eq!(config.session, fp32.session);
+
+        let err = ModelRegistry::from_json(
+            r#"{"voice_sets": {}, "variants": [{"id": "a",
+            "description": "", "precision": "fp32", "file": "a.onnx", "url": "", "size": null,
+            "sha256": "00", "vocab": "kokoro-v1.0", "voice_set": "missing"}]}"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("unknown voice set missing"), "{}", err);
+
+        let err = ModelRegistry::from_json(
+            r#"{"voice_sets": {}, "variants": [{"id": "a",
+            "description": "", "precision": "fp32", "file": "a.onnx", "url": "", "size": null,
+            "sha256": null, "vocab": "kokoro-v1.0", "voice_set": "missing"}]}"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("a has no sha256"), "{}", err);
+    }
+
+    #[test]
+    fn test_verify_file() {
+        let dir = std::env::temp_dir().join(format!("kokoros-registry-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("model.onnx");
+        std::fs::write(&path, b"abc").unwrap();
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(download::sha256_file(&path).unwrap(), sha256);
+
+        let mut expected = RegistryFile {
+            file: "model.onnx".into(),
+            url: "https://example.com/model.onnx".into(),
+            size: Some(3),
+            sha256: Some(sha256.to_uppercase()),
+            mirrors: vec!["https://mirror.example.com/model.onnx".into()],
+        };
+        assert_eq!(
+            expected.download().urls,
+            vec![expected.url.clone(), expected.mirrors[0].clone()]
+        );
+        assert_eq!(download::verify(&path, &expected.download()), Ok(()));
+        assert_eq!(
+            find_file(&expected, &[PathBuf::from("missing"), dir.clone()]),
+            Some(path.clone())
+        );
+
+        expected.size = Some(4);
+        assert!(
+            download::verify(&path, &expected.download())
+                .unwrap_err()
+                .contains("3 bytes")
+        );
+        assert!(!has_file(&dir, &expected));
+
+        expected.size = None;
+        expected.sha256 = Some("00".repeat(32));
+        assert!(
+            download::verify(&path, &expected.download())
+                .unwrap_err()
+                .contains("SHA-256")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_install_bundled_manifest() {
+        let dir = std::env::temp_dir().join(format!("kokoros-install-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let registry = ModelRegistry::bundled();
+        let downloads = DownloadManager::new(download::DownloadOptions {
+            offline: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        for variant in &registry.variants {
+            // Nothing is installed, so every variant goes for a download
+            let err = registry
+                .install(variant, &[dir.clone()], &dir, &downloads)
+                .await
+                .unwrap_err();
+            assert!(
+                err.to_string().contains(&variant.download.file)
+                    && err.to_string().contains("offline"),
+                "{}: {}",
+                variant.id,
+                err
+            );
+        }
+
+        // The shipped voices have no checksum and are downloaded all the same
+        let voices = &registry.voice_sets["kokoro-v1.0"].download;
+        assert!(voices.sha256.is_none());
+        let mut registry = registry.clone();
+        let variant = &mut registry.variants[0];
+        variant.download.size = Some(3);
+        variant.download.sha256 =
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".into());
+        std::fs::write(dir.join(&variant.download.file), b"abc").unwrap();
+        let variant = &registry.variants[0];
+        let err = registry
+            .install(variant, &[dir.clone()], &dir, &downloads)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(&voices.file) && err.to_string().contains("offline"),
+            "{}",
+            err
+        );
+
+        // and used without one once they're there
+        std::fs::write(dir.join(&voices.file), b"voices").unwrap();
+        assert_eq!(
+            registry
+                .install(variant, &[dir.clone()], &dir, &downloads)
+                .await
+                .unwrap(),
+            (dir.join(&variant.download.file), dir.join(&voices.file))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
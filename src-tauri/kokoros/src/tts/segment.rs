@@ -0,0 +1,155 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Abbreviations whose period doesn't end a sentence, lowercase and without the
+/// final period. "etc." isn't one: before a capital it usually does end one.
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "mx", "dr", "prof", "sr", "jr", "st", "mt", "ft", "vs", "cf", "e.g", "i.e",
+    "approx", "fig", "vol", "gen", "gov", "sen", "rep", "capt", "col", "lt", "sgt", "rev", "hon",
+    "jan", "feb", "mar", "apr", "jun", "jul", "aug", "sep", "sept", "oct", "nov", "dec", "inc",
+    "ltd", "corp", "dept", "univ",
+];
+
+/// Abbreviations that are only read as one before a number, as in "No. 5";
+/// otherwise the period ends the sentence ("I said no.")
+const NUMBER_ABBREVIATIONS: &[&str] = &["no"];
+
+/// Punctuation that ends a sentence, including the CJK full stop and the
+/// fullwidth exclamation and question marks.
+const TERMINALS: &[char] = &['.', '!', '?', '…', '。', '！', '？', '．'];
+
+/// Closing quotes and brackets that may follow a sentence's terminal.
+const CLOSERS: &[char] = &[
+    '"', '\'', ')', ']', '”', '’', '»', '」', '』', '）', '】', '〉', '》',
+];
+
+/// Splits `text` into trimmed sentences, in order and without losing text.
+///
+/// Boundaries follow the Unicode sentence rules (UAX #29), which already keep
+/// decimals, "e.g. this" and "U.S.A." together and split CJK text at `。！？`
+/// without needing spaces. On top of that, a period after a known abbreviation
+/// ("Dr. Smith", "No. 5") or a single capital initial ("J. R. R. Tolkien", but
+/// not the pronoun "I") doesn't end the sentence unless a line break follows,
+/// and neither does a CJK quote that the sentence goes on after.
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for (offset, segment) in text.split_sentence_bound_indices() {
+        let end = offset + segment.len();
+        let candidate = &text[start..end];
+        // A quote closed right before more text is part of that sentence:
+        // 「はい。」と言った。
+        let continues =
+            candidate.ends_with(['」', '』']) || ends_with_abbreviation(candidate, &text[end..]);
+        if end < text.len() && !segment.contains('\n') && continues {
+            continue;
+        }
+        let sentence = candidate.trim();
+        if !sentence.is_empty() {
+            sentences.push(sentence);
+        }
+        start = end;
+    }
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+/// Whether `sentence` ends with terminal punctuation, possibly inside quotes.
+pub fn is_terminated(sentence: &str) -> bool {
+    sentence
+        .trim_end()
+        .trim_end_matches(CLOSERS)
+        .ends_with(TERMINALS)
+}
+
+/// `sentence` with a period added when it has no terminal punctuation, so the
+/// phonemizer gives it a sentence's intonation.
+pub fn terminate(sentence: &str) -> String {
+    let sentence = sentence.trim();
+    if sentence.is_empty() || is_terminated(sentence) {
+        sentence.to_string()
+    } else {
+        format!("{}.", sentence)
+    }
+}
+
+fn ends_with_abbreviation(text: &str, next: &str) -> bool {
+    let Some(word) = text.trim_end().strip_suffix('.') else {
+        return false;
+    };
+    let word = word
+        .rsplit(|c: char| c.is_whitespace() || c == '(' || c == '"')
+        .next()
+        .unwrap_or(word);
+    let mut chars = word.chars();
+    let is_initial =
+        matches!((chars.next(), chars.next()), (Some(c), None) if c.is_uppercase() && c != 'I');
+    let word = word.to_lowercase();
+    if NUMBER_ABBREVIATIONS.contains(&word.as_str()) {
+        return next.trim_start().starts_with(|c: char| c.is_ascii_digit());
+    }
+    is_initial || ABBREVIATIONS.contains(&word.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("Hello there! How are you? I'm fine."),
+            vec!["Hello there!", "How are you?", "I'm fine."]
+        );
+        assert_eq!(
+            split_sentences("Dr. Smith paid $3.50, e.g. for tea. J. R. R. Tolkien agreed"),
+            vec![
+                "Dr. Smith paid $3.50, e.g. for tea.",
+                "J. R. R. Tolkien agreed"
+            ]
+        );
+        assert_eq!(
+            split_sentences("We sold apples, pears, etc. Then we left."),
+            vec!["We sold apples, pears, etc.", "Then we left."]
+        );
+        assert_eq!(
+            split_sentences("I said no. We left after that."),
+            vec!["I said no.", "We left after that."]
+        );
+        assert_eq!(
+            split_sentences("So did I. Then we went home."),
+            vec!["So did I.", "Then we went home."]
+        );
+        assert_eq!(
+            split_sentences("See No. 5 on the list."),
+            vec!["See No. 5 on the list."]
+        );
+        // A line break ends a sentence even after an abbreviation
+        assert_eq!(
+            split_sentences("Ask the Dr.\nNow"),
+            vec!["Ask the Dr.", "Now"]
+        );
+    }
+
+    #[test]
+    fn test_split_cjk_sentences() {
+        assert_eq!(
+            split_sentences("你好。今天天气很好！你去哪里？"),
+            vec!["你好。", "今天天气很好！", "你去哪里？"]
+        );
+        assert_eq!(
+            split_sentences("「はい。」と言った。それから帰った"),
+            vec!["「はい。」と言った。", "それから帰った"]
+        );
+    }
+
+    #[test]
+    fn test_terminate() {
+        assert_eq!(terminate(" Hello "), "Hello.");
+        assert_eq!(terminate("Really?"), "Really?");
+        assert_eq!(terminate("He said \"stop.\""), "He said \"stop.\"");
+        assert_eq!(terminate("你好。"), "你好。");
+    }
+}
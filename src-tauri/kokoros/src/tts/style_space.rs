@@ -0,0 +1,339 @@
+use crate::tts::voices::{STYLE_DIM, VoicePack, voice_gender, voice_language};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Most axes a style space exposes; later components are mostly noise
+pub const MAX_AXES: usize = 8;
+
+/// Power iteration steps per component
+const ITERATIONS: usize = 200;
+
+/// Minimum |correlation| between an axis and a voice label to name the axis after it
+const MIN_LABEL_CORRELATION: f32 = 0.5;
+
+/// A principal direction of the style space.
+#[derive(Debug, Clone, Serialize)]
+pub struct StyleAxis {
+    /// `gender`, `accent` or `language` when the axis tracks that label, `pcN` otherwise
+    pub name: String,
+    /// What moving towards positive offsets does, e.g. `masculine -> feminine`
+    pub description: String,
+    /// Share of the total variance across voices explained by this axis
+    pub variance_ratio: f32,
+    /// Standard deviation of the voices along this axis; offsets are in these units
+    pub scale: f32,
+    #[serde(skip)]
+    direction: Vec<f32>,
+}
+
+/// Principal components of the loaded voices' style vectors.
+///
+/// Every voice is reduced to the mean of its style rows, so an axis describes
+/// how whole voices differ. Offsets along an axis are applied to every row of a
+/// pack, which keeps the per-length variation of the base voice intact.
+#[derive(Debug, Clone, Serialize)]
+pub struct StyleSpace {
+    #[serde(skip)]
+    mean: Vec<f32>,
+    pub axes: Vec<StyleAxis>,
+}
+
+/// Known voice labels that axes get named after: (name, description, label of a voice).
+type Label = (&'static str, &'static str, fn(&str) -> Option<f32>);
+
+const LABELS: [Label; 3] = [
+    ("gender", "masculine -> feminine", |name| {
+        voice_gender(name).map(|g| if g == "female" { 1.0 } else { -1.0 })
+    }),
+    (
+        "accent",
+        "american -> british",
+        |name| match voice_language(name) {
+            Some("en-us") => Some(-1.0),
+            Some("en-gb") => Some(1.0),
+            _ => None,
+        },
+    ),
+    ("language", "english -> other languages", |name| {
+        voice_language(name).map(|l| if l.starts_with("en") { -1.0 } else { 1.0 })
+    }),
+];
+
+impl StyleSpace {
+    /// Computes up to [`MAX_AXES`] principal components across `voices`.
+    pub fn analyze<'a>(
+        voices: impl IntoIterator<Item = (&'a String, &'a VoicePack)>,
+    ) -> Result<Self, String> {
+        let mut names = Vec::new();
+        let mut points = Vec::new();
+        for (name, pack) in voices {
+            names.push(name.as_str());
+            points.push(mean_row(pack));
+        }
+        if points.len() < 2 {
+            return Err("at least two voices are needed to analyze the style space".into());
+        }
+
+        let mut mean = vec![0.0; STYLE_DIM];
+        for point in &points {
+            for (m, v) in mean.iter_mut().zip(point) {
+                *m += v / points.len() as f32;
+            }
+        }
+        for point in &mut points {
+            for (v, m) in point.iter_mut().zip(&mean) {
+                *v -= m;
+            }
+        }
+
+        let mut covariance = vec![vec![0.0f32; STYLE_DIM]; STYLE_DIM];
+        for point in &points {
+            for (i, row) in covariance.iter_mut().enumerate() {
+                for (j, c) in row.iter_mut().enumerate() {
+                    *c += point[i] * point[j] / (points.len() - 1) as f32;
+                }
+            }
+        }
+        let total_variance: f32 = (0..STYLE_DIM).map(|i| covariance[i][i]).sum();
+        if total_variance <= f32::EPSILON {
+            return Err("all voices have the same style".into());
+        }
+
+        let mut axes = Vec::new();
+        let mut used_labels = Vec::new();
+        while axes.len() < MAX_AXES.min(points.len() - 1) {
+            let (variance, mut direction) = dominant_eigenvector(&covariance, &points);
+            if variance <= total_variance * 1e-6 {
+                break;
+            }
+            // Deflate so the next iteration finds the next component
+            for (i, row) in covariance.iter_mut().enumerate() {
+                for (j, c) in row.iter_mut().enumerate() {
+                    *c -= variance * direction[i] * direction[j];
+                }
+            }
+
+            let projections: Vec<f32> = points.iter().map(|p| dot(p, &direction)).collect();
+            let label = LABELS
+                .iter()
+                .filter(|(name, ..)| !used_labels.contains(name))
+                .filter_map(|(name, description, label)| {
+                    let r = label_correlation(&names, &projections, *label)?;
+                    Some((*name, *description, r))
+                })
+                .filter(|(.., r)| r.abs() >= MIN_LABEL_CORRELATION)
+                .max_by(|a, b| a.2.abs().total_cmp(&b.2.abs()));
+
+            let (name, description) = match label {
+                Some((name, description, r)) => {
+                    if r < 0.0 {
+                        direction.iter_mut().for_each(|v| *v = -*v);
+                    }
+                    used_labels.push(name);
+                    (name.to_string(), description.to_string())
+                }
+                None => {
+                    let n = axes.len() + 1;
+                    (format!("pc{}", n), format!("principal component {}", n))
+                }
+            };
+
+            axes.push(StyleAxis {
+                name,
+                description,
+                variance_ratio: variance / total_variance,
+                scale: variance.sqrt(),
+                direction,
+            });
+        }
+
+        Ok(Self { mean, axes })
+    }
+
+    pub fn axis(&self, name: &str) -> Option<&StyleAxis> {
+        self.axes.iter().find(|a| a.name == name)
+    }
+
+    /// Position of a voice on each axis, in units of [`StyleAxis::scale`].
+    pub fn coordinates(&self, pack: &VoicePack) -> HashMap<String, f32> {
+        let point: Vec<f32> = mean_row(pack)
+            .iter()
+            .zip(&self.mean)
+            .map(|(v, m)| v - m)
+            .collect();
+        self.axes
+            .iter()
+            .map(|a| (a.name.clone(), dot(&point, &a.direction) / a.scale))
+            .collect()
+    }
+
+    /// Moves every row of `base` by `offsets`, given per axis in units of [`StyleAxis::scale`].
+    pub fn generate(
+        &self,
+        base: &VoicePack,
+        offsets: &HashMap<String, f32>,
+    ) -> Result<VoicePack, String> {
+        let mut delta = [0.0f32; STYLE_DIM];
+        for (name, offset) in offsets {
+            let axis = self
+                .axis(name)
+                .ok_or_else(|| format!("unknown style axis '{}'", name))?;
+            for (d, v) in delta.iter_mut().zip(&axis.direction) {
+                *d += offset * axis.scale * v;
+            }
+        }
+
+        let mut pack = base.clone();
+        for row in &mut pack {
+            for (v, d) in row[0].iter_mut().zip(&delta) {
+                *v += d;
+            }
+        }
+        Ok(pack)
+    }
+}
+
+fn mean_row(pack: &VoicePack) -> Vec<f32> {
+    let mut mean = vec![0.0; STYLE_DIM];
+    for row in pack {
+        for (m, v) in mean.iter_mut().zip(&row[0]) {
+            *m += v / pack.len() as f32;
+        }
+    }
+    mean
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Largest eigenvalue and its unit eigenvector of a symmetric matrix, by power iteration.
+///
+/// Starts from the data point with the largest residual variance, which always lies
+/// in the span of the remaining components, and makes the largest coordinate positive
+/// so results are stable across runs.
+fn dominant_eigenvector(matrix: &[Vec<f32>], points: &[Vec<f32>]) -> (f32, Vec<f32>) {
+    let mut vector = points
+        .iter()
+        .max_by(|a, b| {
+            let qa = dot(a, &multiply(matrix, a));
+            let qb = dot(b, &multiply(matrix, b));
+            qa.total_cmp(&qb)
+        })
+        .cloned()
+        .unwrap_or_else(|| vec![1.0; STYLE_DIM]);
+
+    for _ in 0..ITERATIONS {
+        let next = multiply(matrix, &vector);
+        let norm = dot(&next, &next).sqrt();
+        if norm <= f32::EPSILON {
+            return (0.0, vector);
+        }
+        vector = next.into_iter().map(|v| v / norm).collect();
+    }
+
+    let largest = vector
+        .iter()
+        .copied()
+        .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+        .unwrap_or(0.0);
+    if largest < 0.0 {
+        vector.iter_mut().for_each(|v| *v = -*v);
+    }
+    (dot(&vector, &multiply(matrix, &vector)), vector)
+}
+
+fn multiply(matrix: &[Vec<f32>], vector: &[f32]) -> Vec<f32> {
+    matrix.iter().map(|row| dot(row, vector)).collect()
+}
+
+/// Pearson correlation between axis projections and a voice label, over the voices
+/// that have the label. `None` when the label doesn't vary among them.
+fn label_correlation(
+    names: &[&str],
+    projections: &[f32],
+    label: fn(&str) -> Option<f32>,
+) -> Option<f32> {
+    let pairs: Vec<(f32, f32)> = names
+        .iter()
+        .zip(projections)
+        .filter_map(|(name, p)| Some((label(name)?, *p)))
+        .collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+
+    let n = pairs.len() as f32;
+    let (mean_l, mean_p) = pairs
+        .iter()
+        .fold((0.0, 0.0), |(l, p), (x, y)| (l + x / n, p + y / n));
+    let (mut cov, mut var_l, mut var_p) = (0.0, 0.0, 0.0);
+    for (l, p) in &pairs {
+        cov += (l - mean_l) * (p - mean_p);
+        var_l += (l - mean_l).powi(2);
+        var_p += (p - mean_p).powi(2);
+    }
+    if var_l <= f32::EPSILON || var_p <= f32::EPSILON {
+        return None;
+    }
+    Some(cov / (var_l * var_p).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(values: &[(usize, f32)]) -> VoicePack {
+        let mut row = [[0.0; STYLE_DIM]; 1];
+        for &(i, v) in values {
+            row[0][i] = v;
+        }
+        vec![row; 3]
+    }
+
+    fn voices() -> HashMap<String, VoicePack> {
+        // Gender varies most along dim 0, accent less along dim 1
+        HashMap::from([
+            ("af_a".to_string(), pack(&[(0, 2.0), (1, -0.5)])),
+            ("bf_b".to_string(), pack(&[(0, 2.2), (1, 0.5)])),
+            ("am_c".to_string(), pack(&[(0, -2.0), (1, -0.5)])),
+            ("bm_d".to_string(), pack(&[(0, -2.2), (1, 0.5)])),
+        ])
+    }
+
+    #[test]
+    fn test_named_axes() {
+        let voices = voices();
+        let space = StyleSpace::analyze(&voices).unwrap();
+
+        assert_eq!(space.axes[0].name, "gender");
+        assert!(space.axes[0].direction[0] > 0.99);
+        assert_eq!(space.axes[1].name, "accent");
+        assert!(space.axes[1].direction[1] > 0.99);
+        assert!(space.axes[0].variance_ratio > space.axes[1].variance_ratio);
+
+        let coordinates = space.coordinates(&voices["af_a"]);
+        assert!(coordinates["gender"] > 0.0);
+        assert!(coordinates["accent"] < 0.0);
+    }
+
+    #[test]
+    fn test_generate() {
+        let voices = voices();
+        let space = StyleSpace::analyze(&voices).unwrap();
+        let axis = space.axis("accent").unwrap();
+
+        let moved = space
+            .generate(
+                &voices["af_a"],
+                &HashMap::from([("accent".to_string(), 1.0)]),
+            )
+            .unwrap();
+        assert_eq!(moved.len(), 3);
+        assert!((moved[2][0][1] - (-0.5 + axis.scale)).abs() < 1e-3);
+        assert!((moved[2][0][0] - 2.0).abs() < 1e-3);
+
+        let unknown = HashMap::from([("warmth".to_string(), 1.0)]);
+        assert!(space.generate(&voices["af_a"], &unknown).is_err());
+    }
+}
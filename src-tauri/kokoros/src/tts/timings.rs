@@ -0,0 +1,292 @@
+use crate::tts::viseme::{VisemeKeyframe, viseme_keyframes};
+use crate::tts::vocab::Vocab;
+use serde::Serialize;
+
+/// Separator espeak puts between the phonemes of consecutive words
+const SPACE: char = ' ';
+
+/// Timing of a single phoneme token, in seconds from the start of the audio.
+#[derive(Debug, Clone, Serialize)]
+pub struct PhonemeTiming {
+    pub phoneme: char,
+    pub start: f32,
+    pub end: f32,
+    /// Index into [`AudioWithTimings::words`], `None` for punctuation between words
+    pub word: Option<usize>,
+}
+
+/// Timing of a source word, in seconds from the start of the audio.
+#[derive(Debug, Clone, Serialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub phonemes: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Synthesized audio together with word- and phoneme-level timestamps.
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioWithTimings {
+    pub audio: Vec<f32>,
+    pub sample_rate: u32,
+    pub words: Vec<WordTiming>,
+    pub phonemes: Vec<PhonemeTiming>,
+    /// True when at least one chunk had no model durations and its timings
+    /// were spread evenly across the chunk's audio instead
+    pub estimated: bool,
+}
+
+impl AudioWithTimings {
+    /// Mouth-shape keyframes for lip sync, derived from the phoneme timings.
+    pub fn visemes(&self) -> Vec<VisemeKeyframe> {
+        viseme_keyframes(&self.phonemes)
+    }
+}
+
+/// Accumulates audio and timings chunk by chunk, keeping every timestamp
+/// relative to the start of the full utterance.
+pub struct TimingsBuilder<'a> {
+    sample_rate: u32,
+    /// Vocabulary of the model the chunks were inferred with, which decides
+    /// the phonemes that got a token
+    vocab: &'a Vocab,
+    audio: Vec<f32>,
+    words: Vec<WordTiming>,
+    phonemes: Vec<PhonemeTiming>,
+    estimated: bool,
+}
+
+impl<'a> TimingsBuilder<'a> {
+    pub fn new(sample_rate: u32, vocab: &'a Vocab) -> Self {
+        Self {
+            sample_rate,
+            vocab,
+            audio: Vec::new(),
+            words: Vec::new(),
+            phonemes: Vec::new(),
+            estimated: false,
+        }
+    }
+
+    /// Appends one inferred chunk.
+    ///
+    /// `leading_tokens` is the number of silence tokens inserted before the
+    /// phonemes, and `durations` the model's per-token output (in frames) for
+    /// the padded sequence `[0, silence.., phonemes.., 0]`.
+    pub fn push_chunk(
+        &mut self,
+        text: &str,
+        phonemes: &str,
+        leading_tokens: usize,
+        durations: Option<&[f32]>,
+        audio: &[f32],
+    ) {
+        let offset = self.audio.len() as f32 / self.sample_rate as f32;
+        let chunk_secs = audio.len() as f32 / self.sample_rate as f32;
+
+        // Phoneme characters that survive tokenization, tagged with the
+        // espeak word they belong to
+        let mut tokens: Vec<(char, Option<usize>)> = Vec::new();
+        let mut phoneme_words: Vec<String> = Vec::new();
+        let mut in_word = false;
+        for c in phonemes.chars() {
+            if c == SPACE {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                phoneme_words.push(String::new());
+            }
+            if !self.vocab.contains(c) {
+                continue;
+            }
+            let word = if c == SPACE {
+                None
+            } else {
+                let last = phoneme_words.len() - 1;
+                phoneme_words[last].push(c);
+                Some(last)
+            };
+            tokens.push((c, word));
+        }
+
+        // Per-token spans (start, end) within this chunk, including the pads
+        let total_tokens = tokens.len() + leading_tokens + 2;
+        let spans = match durations.filter(|d| d.len() == total_tokens) {
+            Some(durations) => spans_from_durations(durations, chunk_secs),
+            None => {
+                self.estimated = true;
+                let step = chunk_secs / total_tokens.max(1) as f32;
+                (0..total_tokens)
+                    .map(|i| (i as f32 * step, (i + 1) as f32 * step))
+                    .collect()
+            }
+        };
+
+        // Map espeak words back onto the source words
+        let text_words: Vec<&str> = text.split_whitespace().collect();
+        let groups = align_words(text_words.len(), phoneme_words.len());
+        let mut word_of_phoneme_word = vec![None; phoneme_words.len()];
+        let mut word_spans: Vec<(f32, f32)> = vec![(f32::MAX, f32::MIN); phoneme_words.len()];
+        for (i, &(c, word)) in tokens.iter().enumerate() {
+            let (start, end) = spans[i + leading_tokens + 1];
+            if let Some(word) = word {
+                let span = &mut word_spans[word];
+                span.0 = span.0.min(start);
+                span.1 = span.1.max(end);
+            }
+            self.phonemes.push(PhonemeTiming {
+                phoneme: c,
+                start: offset + start,
+                end: offset + end,
+                word: None,
+            });
+        }
+
+        let first_phoneme = self.phonemes.len() - tokens.len();
+        for (text_range, phoneme_range) in groups {
+            let start = phoneme_range
+                .clone()
+                .map(|w| word_spans[w].0)
+                .fold(f32::MAX, f32::min);
+            let end = phoneme_range
+                .clone()
+                .map(|w| word_spans[w].1)
+                .fold(f32::MIN, f32::max);
+            if start > end {
+                continue;
+            }
+            let group_phonemes = phoneme_words[phoneme_range.clone()].join(" ");
+
+            // Several source words sharing an espeak word split its time by length
+            let group_words = &text_words[text_range.clone()];
+            let total_chars: usize = group_words.iter().map(|w| w.chars().count()).sum();
+            let mut cursor = start;
+            for word in group_words {
+                let share = word.chars().count() as f32 / total_chars.max(1) as f32;
+                let word_end = cursor + (end - start) * share;
+                self.words.push(WordTiming {
+                    word: word.to_string(),
+                    phonemes: group_phonemes.clone(),
+                    start: offset + cursor,
+                    end: offset + word_end,
+                });
+                cursor = word_end;
+            }
+            let first_word = self.words.len() - group_words.len();
+            for w in phoneme_range {
+                word_of_phoneme_word[w] = Some(first_word);
+            }
+        }
+
+        for (timing, &(_, word)) in self.phonemes[first_phoneme..].iter_mut().zip(&tokens) {
+            timing.word = word.and_then(|w| word_of_phoneme_word[w]);
+        }
+
+        self.audio.extend_from_slice(audio);
+    }
+
+    pub fn finish(self) -> AudioWithTimings {
+        AudioWithTimings {
+            audio: self.audio,
+            sample_rate: self.sample_rate,
+            words: self.words,
+            phonemes: self.phonemes,
+            estimated: self.estimated,
+        }
+    }
+}
+
+/// Converts per-token frame durations into (start, end) spans in seconds,
+/// scaled so the last span ends exactly at the end of the chunk's audio.
+fn spans_from_durations(durations: &[f32], chunk_secs: f32) -> Vec<(f32, f32)> {
+    let total: f32 = durations.iter().map(|d| d.max(0.0)).sum();
+    let scale = if total > 0.0 { chunk_secs / total } else { 0.0 };
+    let mut cursor = 0.0;
+    durations
+        .iter()
+        .map(|d| {
+            let start = cursor;
+            cursor += d.max(0.0) * scale;
+            (start, cursor)
+        })
+        .collect()
+}
+
+/// Pairs up ranges of source words with ranges of espeak words.
+///
+/// espeak usually emits one phoneme word per source word, in which case this is
+/// the identity. When it doesn't (numbers, abbreviations, merged function words)
+/// both sides are split into the same number of groups proportionally, which keeps
+/// the mapping monotonic and never leaves a word without a span.
+pub fn align_words(
+    text_words: usize,
+    phoneme_words: usize,
+) -> Vec<(std::ops::Range<usize>, std::ops::Range<usize>)> {
+    let groups = text_words.min(phoneme_words);
+    (0..groups)
+        .map(|k| {
+            (
+                k * text_words / groups..(k + 1) * text_words / groups,
+                k * phoneme_words / groups..(k + 1) * phoneme_words / groups,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_words() {
+        assert_eq!(
+            align_words(3, 3),
+            vec![(0..1, 0..1), (1..2, 1..2), (2..3, 2..3)]
+        );
+        // "I have 21 apples" -> espeak spells the number as two words
+        assert_eq!(
+            align_words(4, 5),
+            vec![(0..1, 0..1), (1..2, 1..2), (2..3, 2..3), (3..4, 3..5)]
+        );
+        assert!(align_words(0, 2).is_empty());
+    }
+
+    #[test]
+    fn test_timings_across_chunks() {
+        let vocab = Vocab::default();
+        let mut timings = TimingsBuilder::new(10, &vocab);
+        // [pad, h, i, pad] -> one word lasting frames 1..3 of 4
+        timings.push_chunk("hi", "hi", 0, Some(&[1.0, 1.0, 1.0, 1.0]), &[0.0; 40]);
+        // No durations: seven tokens spread evenly over the second chunk
+        timings.push_chunk("oh no", "oʊ no", 0, None, &[0.0; 70]);
+        let timings = timings.finish();
+
+        assert!(timings.estimated);
+        assert_eq!(timings.audio.len(), 110);
+        let words: Vec<(&str, f32, f32)> = timings
+            .words
+            .iter()
+            .map(|w| (w.word.as_str(), w.start, w.end))
+            .collect();
+        assert_eq!(
+            words,
+            vec![("hi", 1.0, 3.0), ("oh", 5.0, 7.0), ("no", 8.0, 10.0)]
+        );
+        assert_eq!(timings.phonemes.len(), 7);
+        assert_eq!(timings.phonemes[4].phoneme, ' ');
+        assert_eq!(timings.phonemes[4].word, None);
+        assert_eq!(timings.phonemes[6].word, Some(2));
+
+        // Symbols the model has no token for aren't timed
+        let vocab = Vocab::from_map([('h', 1), (' ', 2)].into_iter().collect());
+        let mut timings = TimingsBuilder::new(10, &vocab);
+        timings.push_chunk("hi", "hi", 0, None, &[0.0; 30]);
+        let phonemes: Vec<char> = timings
+            .finish()
+            .phonemes
+            .iter()
+            .map(|p| p.phoneme)
+            .collect();
+        assert_eq!(phonemes, vec!['h']);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::tts::timings::PhonemeTiming;
+use serde::Serialize;
+
+/// Mouth shapes of the VRM expression preset, plus `sil` for a closed/resting mouth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Viseme {
+    Aa,
+    Ih,
+    Ou,
+    Ee,
+    Oh,
+    Sil,
+}
+
+impl Viseme {
+    /// Name of the matching VRM blendshape, `None` for [`Viseme::Sil`].
+    pub fn vrm_expression(self) -> Option<&'static str> {
+        match self {
+            Viseme::Aa => Some("aa"),
+            Viseme::Ih => Some("ih"),
+            Viseme::Ou => Some("ou"),
+            Viseme::Ee => Some("ee"),
+            Viseme::Oh => Some("oh"),
+            Viseme::Sil => None,
+        }
+    }
+}
+
+/// A mouth shape to blend towards, starting at `time` seconds into the audio.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct VisemeKeyframe {
+    pub time: f32,
+    pub viseme: Viseme,
+    pub weight: f32,
+}
+
+/// Maps a symbol of the Kokoro vocabulary to a mouth shape and blend weight.
+///
+/// Returns `None` for stress, length and tone marks and for word separators,
+/// which don't change the mouth shape and simply extend the previous one.
+pub fn phoneme_to_viseme(phoneme: char) -> Option<(Viseme, f32)> {
+    let viseme = match phoneme {
+        // Modifiers and word boundaries
+        'ˈ' | 'ˌ' | 'ː' | 'ˑ' | 'ʼ' | 'ʴ' | 'ʰ' | 'ʱ' | 'ʲ' | 'ʷ' | 'ˠ' | 'ˤ' | '˞' | '↓' | '↑'
+        | '→' | '↗' | '↘' | '\'' | '\u{0329}' | ' ' => return None,
+
+        // Open vowels
+        'ɑ' | 'æ' | 'a' | 'ʌ' | 'ɐ' => (Viseme::Aa, 1.0),
+        // Reduced central vowels only open the mouth halfway
+        'ə' | 'ɘ' | 'ɚ' | 'ɜ' | 'ɝ' | 'ɞ' => (Viseme::Aa, 0.5),
+        // Close front vowels
+        'i' | 'ɪ' | 'ɨ' | 'ᵻ' | 'y' | 'ʏ' => (Viseme::Ih, 1.0),
+        // Mid front vowels
+        'e' | 'ɛ' => (Viseme::Ee, 1.0),
+        // Mid back and rounded front vowels
+        'o' | 'ɔ' | 'ɒ' | 'ɵ' | 'ø' | 'œ' | 'ɶ' | 'ɤ' => (Viseme::Oh, 1.0),
+        // Close back vowels and labial glides
+        'u' | 'ʊ' | 'ɯ' | 'ʉ' | 'w' | 'ʍ' | 'ɥ' | 'ɰ' => (Viseme::Ou, 1.0),
+        // Diphthong shorthands used by misaki-style phonemes: A=eɪ, I=aɪ, O=oʊ, W=aʊ, Y=ɔɪ
+        'A' => (Viseme::Ee, 1.0),
+        'I' | 'W' => (Viseme::Aa, 1.0),
+        'O' | 'Y' => (Viseme::Oh, 1.0),
+
+        // Lips pressed together
+        'p' | 'b' | 'm' | 'ɓ' | 'ʙ' | 'β' | 'ʘ' => (Viseme::Sil, 0.0),
+        // Lower lip against the teeth
+        'f' | 'v' | 'ɸ' | 'ʋ' | 'ⱱ' | 'ɱ' => (Viseme::Ih, 0.2),
+        // Rounded approximants and postalveolars
+        'ɹ' | 'ɻ' | 'r' | 'ʀ' | 'ʁ' | 'ʃ' | 'ʒ' | 'ʧ' | 'ʤ' => (Viseme::Ou, 0.5),
+        // Glottal stops and clicks interrupt the airflow
+        'ʔ' | 'ʡ' | 'ǀ' | 'ǁ' | 'ǂ' | 'ǃ' => (Viseme::Sil, 0.0),
+        // Every other consonant leaves the mouth slightly open
+        c if c.is_alphabetic() => (Viseme::Ih, 0.3),
+
+        // Pauses: punctuation and padding
+        _ => (Viseme::Sil, 0.0),
+    };
+    Some(viseme)
+}
+
+/// Turns phoneme timings into keyframes, dropping repeats of the same shape and
+/// closing the mouth after the last phoneme.
+pub fn viseme_keyframes(phonemes: &[PhonemeTiming]) -> Vec<VisemeKeyframe> {
+    let mut keyframes: Vec<VisemeKeyframe> = Vec::new();
+
+    for timing in phonemes {
+        let Some((viseme, weight)) = phoneme_to_viseme(timing.phoneme) else {
+            continue;
+        };
+        if keyframes
+            .last()
+            .is_some_and(|last| last.viseme == viseme && last.weight == weight)
+        {
+            continue;
+        }
+        keyframes.push(VisemeKeyframe {
+            time: timing.start,
+            viseme,
+            weight,
+        });
+    }
+
+    let mouth_open = keyframes.last().is_some_and(|k| k.viseme != Viseme::Sil);
+    if let Some(last) = phonemes.last().filter(|_| mouth_open) {
+        keyframes.push(VisemeKeyframe {
+            time: last.end,
+            viseme: Viseme::Sil,
+            weight: 0.0,
+        });
+    }
+
+    keyframes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts::vocab::Vocab;
+
+    #[test]
+    fn test_vocab_coverage() {
+        let vowels = "ɑɐæəɘɚɛɜɝɞɨɪʊʌʉɯɔɒoeiuøɵœɶɤʏyᵻ";
+        for c in Vocab::default().symbols() {
+            let mapped = phoneme_to_viseme(c);
+            if vowels.contains(c) {
+                assert!(
+                    matches!(mapped, Some((v, w)) if v != Viseme::Sil && w > 0.0),
+                    "vowel {c:?} should open the mouth"
+                );
+            }
+        }
+        assert_eq!(phoneme_to_viseme('ˈ'), None);
+        assert_eq!(phoneme_to_viseme('m'), Some((Viseme::Sil, 0.0)));
+        assert_eq!(phoneme_to_viseme('.'), Some((Viseme::Sil, 0.0)));
+    }
+
+    #[test]
+    fn test_viseme_keyframes() {
+        let phonemes: Vec<PhonemeTiming> = "mˈɑː"
+            .chars()
+            .enumerate()
+            .map(|(i, phoneme)| PhonemeTiming {
+                phoneme,
+                start: i as f32 * 0.1,
+                end: (i + 1) as f32 * 0.1,
+                word: Some(0),
+            })
+            .collect();
+
+        let keyframes = viseme_keyframes(&phonemes);
+        let shapes: Vec<(Viseme, f32)> = keyframes.iter().map(|k| (k.viseme, k.weight)).collect();
+        assert_eq!(
+            shapes,
+            vec![(Viseme::Sil, 0.0), (Viseme::Aa, 1.0), (Viseme::Sil, 0.0)]
+        );
+        assert!((keyframes[1].time - 0.2).abs() < 1e-6);
+        assert!((keyframes[2].time - 0.4).abs() < 1e-6);
+    }
+}
@@ -35,4 +35,98 @@ pub fn print_sorted_reverse_vocab() {
 lazy_static! {
     pub static ref VOCAB: HashMap<char, usize> = get_vocab();
     pub static ref REVERSE_VOCAB: HashMap<usize, char> = get_reverse_vocab();
+    static ref DEFAULT_VOCAB: Vocab =
+        Vocab::from_map(VOCAB.iter().map(|(&c, &id)| (c, id as i64)).collect());
+}
+
+/// Symbol table of a loaded model, mapping phonemes to the token ids it was
+/// trained with.
+///
+/// The default is the Kokoro v1.0 table from [`get_vocab`]; other models bring
+/// their own in their config, see [`crate::tts::model_config::ModelConfig`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vocab {
+    ids: HashMap<char, i64>,
+}
+
+impl Vocab {
+    pub fn from_map(ids: HashMap<char, i64>) -> Self {
+        Self { ids }
+    }
+
+    /// Parses a `vocab` object of a Kokoro config, e.g. `{"a": 43, "b": 44}`.
+    pub fn from_json(vocab: &serde_json::Value) -> Result<Self, String> {
+        let entries = vocab
+            .as_object()
+            .ok_or_else(|| "vocab must be an object of symbol -> id".to_string())?;
+        let mut ids = HashMap::with_capacity(entries.len());
+        for (symbol, id) in entries {
+            let mut chars = symbol.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return Err(format!(
+                    "vocab symbol '{}' is not a single character",
+                    symbol
+                ));
+            };
+            let id = id
+                .as_i64()
+                .ok_or_else(|| format!("vocab id of '{}' is not an integer", symbol))?;
+            ids.insert(c, id);
+        }
+        if ids.is_empty() {
+            return Err("vocab is empty".to_string());
+        }
+        Ok(Self { ids })
+    }
+
+    /// Every symbol the model has a token for, in no particular order.
+    pub fn symbols(&self) -> impl Iterator<Item = char> + '_ {
+        self.ids.keys().copied()
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.ids.contains_key(&c)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Token ids of `phonemes`, skipping symbols the model doesn't know.
+    pub fn tokenize(&self, phonemes: &str) -> Vec<i64> {
+        phonemes
+            .chars()
+            .filter_map(|c| self.ids.get(&c).copied())
+            .collect()
+    }
+}
+
+impl Default for Vocab {
+    fn default() -> Self {
+        DEFAULT_VOCAB.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts::tokenize::tokenize;
+
+    #[test]
+    fn test_default_matches_global_vocab() {
+        let phonemes = "həlˈoʊ, wˈɜːld!";
+        assert_eq!(Vocab::default().tokenize(phonemes), tokenize(phonemes));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let vocab = Vocab::from_json(&serde_json::json!({"a": 43, "ㄅ": 180, " ": 16})).unwrap();
+        assert_eq!(vocab.tokenize("a ㄅx"), vec![43, 16, 180]);
+        assert!(Vocab::from_json(&serde_json::json!({"ab": 1})).is_err());
+        assert!(Vocab::from_json(&serde_json::json!(["a"])).is_err());
+    }
 }
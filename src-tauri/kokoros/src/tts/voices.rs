@@ -0,0 +1,609 @@
+use crate::error::KokoError;
+use ndarray::{Array3, ArrayD, ArrayViewD};
+use ndarray_npy::{NpzReader, NpzWriter, read_npy};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Width of a single Kokoro style vector
+pub const STYLE_DIM: usize = 256;
+
+/// Most style rows a voice can have; one per possible token count of a chunk
+pub const MAX_STYLE_ROWS: usize = 512;
+
+/// Name of the archive imported voices are kept in, inside the user voices directory
+pub const IMPORTED_VOICES_FILE: &str = "imported.npz";
+
+/// Style vectors of one voice, indexed by the token length of the utterance
+pub type VoicePack = Vec<[[f32; STYLE_DIM]; 1]>;
+
+/// Where a voice came from, which decides whether and where it can be removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VoiceSource {
+    /// Shipped in the model's voices file
+    Builtin,
+    /// Saved from a blend, next to the voices file
+    Custom,
+    /// Imported at runtime into the user voices directory
+    Imported,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VoiceInfo {
+    pub name: String,
+    /// espeak language code, when the name follows Kokoro's `<lang><gender>_` convention
+    pub language: Option<&'static str>,
+    pub gender: Option<&'static str>,
+    pub source: VoiceSource,
+}
+
+/// All loaded voices and their origin.
+#[derive(Debug, Clone, Default)]
+pub struct VoiceLibrary {
+    pub packs: HashMap<String, VoicePack>,
+    sources: HashMap<String, VoiceSource>,
+}
+
+impl VoiceLibrary {
+    pub fn insert(&mut self, name: String, pack: VoicePack, source: VoiceSource) {
+        self.sources.insert(name.clone(), source);
+        self.packs.insert(name, pack);
+    }
+
+    pub fn extend(&mut self, voices: HashMap<String, VoicePack>, source: VoiceSource) {
+        for (name, pack) in voices {
+            self.insert(name, pack, source);
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<VoicePack> {
+        self.sources.remove(name);
+        self.packs.remove(name)
+    }
+
+    pub fn source(&self, name: &str) -> Option<VoiceSource> {
+        self.sources.get(name).copied()
+    }
+
+    /// Copies of every voice from `source`, e.g. to persist them.
+    pub fn voices_from(&self, source: VoiceSource) -> HashMap<String, VoicePack> {
+        self.packs
+            .iter()
+            .filter(|(name, _)| self.source(name) == Some(source))
+            .map(|(name, pack)| (name.clone(), pack.clone()))
+            .collect()
+    }
+
+    /// Metadata for every voice, sorted by name.
+    pub fn info(&self) -> Vec<VoiceInfo> {
+        let mut info: Vec<VoiceInfo> = self
+            .packs
+            .keys()
+            .map(|name| VoiceInfo {
+                name: name.clone(),
+                language: voice_language(name),
+                gender: voice_gender(name),
+                source: self.source(name).unwrap_or(VoiceSource::Builtin),
+            })
+            .collect();
+        info.sort_by(|a, b| a.name.cmp(&b.name));
+        info
+    }
+}
+
+/// espeak language of a voice named with Kokoro's prefix convention, e.g. `bf_emma` -> `en-gb`.
+pub fn voice_language(name: &str) -> Option<&'static str> {
+    if name.as_bytes().get(2) != Some(&b'_') {
+        return None;
+    }
+    match name.as_bytes()[0] {
+        b'a' => Some("en-us"),
+        b'b' => Some("en-gb"),
+        b'e' => Some("es"),
+        b'f' => Some("fr-fr"),
+        b'h' => Some("hi"),
+        b'i' => Some("it"),
+        b'j' => Some("ja"),
+        b'p' => Some("pt-br"),
+        b'z' => Some("cmn"),
+        _ => None,
+    }
+}
+
+pub fn voice_gender(name: &str) -> Option<&'static str> {
+    if name.as_bytes().get(2) != Some(&b'_') {
+        return None;
+    }
+    match name.as_bytes()[1] {
+        b'f' => Some("female"),
+        b'm' => Some("male"),
+        _ => None,
+    }
+}
+
+/// A voice for text in `lang` (an espeak code): `current` when it already speaks
+/// the language, otherwise one of `available` that does, preferring the current
+/// voice's gender. English variants count as the same language.
+pub fn compatible_voice(current: &str, lang: &str, available: &[String]) -> Option<String> {
+    let family = |lang: &str| lang.split('-').next().unwrap_or(lang).to_string();
+    let speaks = |name: &str| voice_language(name).map(family) == Some(family(lang));
+    if speaks(current) {
+        return Some(current.to_string());
+    }
+
+    let mut candidates: Vec<&String> = available.iter().filter(|name| speaks(name)).collect();
+    candidates.sort();
+    let gender = voice_gender(current);
+    candidates
+        .iter()
+        .find(|name| gender.is_some() && voice_gender(name) == gender)
+        .or(candidates.first())
+        .map(|name| name.to_string())
+}
+
+/// A weighted combination of voices.
+///
+/// Written as `name[:weight]` terms joined by `+`, e.g. `af_heart:0.75+af_aoede:0.25`.
+/// Weights are arbitrary floats and default to 1. The older `af_heart.7` form, where the
+/// digits after the dot are tenths, is still accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceBlend {
+    pub components: Vec<(String, f32)>,
+}
+
+impl VoiceBlend {
+    pub fn parse(spec: &str) -> Result<Self, KokoError> {
+        let mut components = Vec::new();
+
+        for term in spec.split('+').map(str::trim) {
+            let (name, weight) = if let Some((name, weight)) = term.split_once(':') {
+                let weight = weight.trim().parse::<f32>().map_err(|_| {
+                    KokoError::InvalidVoice(format!(
+                        "invalid weight '{}' for voice '{}'",
+                        weight, name
+                    ))
+                })?;
+                (name.trim(), weight)
+            } else if let Some((name, tenths)) = term.split_once('.') {
+                let tenths = tenths.parse::<u32>().map_err(|_| {
+                    KokoError::InvalidVoice(format!(
+                        "invalid weight '.{}' for voice '{}'",
+                        tenths, name
+                    ))
+                })?;
+                (name, tenths as f32 * 0.1)
+            } else {
+                (term, 1.0)
+            };
+
+            if name.is_empty() {
+                return Err(KokoError::InvalidVoice(format!(
+                    "missing voice name in blend '{}'",
+                    spec
+                )));
+            }
+            if !weight.is_finite() {
+                return Err(KokoError::InvalidVoice(format!(
+                    "invalid weight for voice '{}'",
+                    name
+                )));
+            }
+            components.push((name.to_string(), weight));
+        }
+
+        Ok(Self { components })
+    }
+
+    /// Rescales the weights so they sum to 1.
+    pub fn normalized(&self) -> Result<Self, KokoError> {
+        let total: f32 = self.components.iter().map(|(_, w)| w).sum();
+        if total.abs() < f32::EPSILON {
+            return Err(KokoError::InvalidVoice(
+                "cannot normalize a blend whose weights sum to zero".to_string(),
+            ));
+        }
+        Ok(Self {
+            components: self
+                .components
+                .iter()
+                .map(|(name, weight)| (name.clone(), weight / total))
+                .collect(),
+        })
+    }
+
+    fn resolve<'a>(
+        &self,
+        styles: &'a HashMap<String, VoicePack>,
+    ) -> Result<Vec<(&'a VoicePack, f32)>, KokoError> {
+        self.components
+            .iter()
+            .map(|(name, weight)| {
+                styles
+                    .get(name)
+                    .map(|pack| (pack, *weight))
+                    .ok_or_else(|| KokoError::VoiceNotFound(name.clone()))
+            })
+            .collect()
+    }
+
+    /// Blends the style row used for an utterance of `tokens_len` tokens.
+    pub fn blend_row(
+        &self,
+        styles: &HashMap<String, VoicePack>,
+        tokens_len: usize,
+    ) -> Result<Vec<f32>, KokoError> {
+        let mut blended = vec![0.0; STYLE_DIM];
+        for (pack, weight) in self.resolve(styles)? {
+            for (out, value) in blended.iter_mut().zip(style_row(pack, tokens_len)) {
+                *out += value * weight;
+            }
+        }
+        Ok(blended)
+    }
+
+    /// Blends every row of the voice packs into a new pack, as long as the shortest one.
+    pub fn blend_pack(&self, styles: &HashMap<String, VoicePack>) -> Result<VoicePack, KokoError> {
+        let packs = self.resolve(styles)?;
+        let rows = packs.iter().map(|(pack, _)| pack.len()).min().unwrap_or(0);
+        let mut blended = vec![[[0.0; STYLE_DIM]; 1]; rows];
+        for (pack, weight) in packs {
+            for (out_row, row) in blended.iter_mut().zip(pack.iter()) {
+                for (out, value) in out_row[0].iter_mut().zip(row[0].iter()) {
+                    *out += value * weight;
+                }
+            }
+        }
+        Ok(blended)
+    }
+}
+
+/// Style row for an utterance of `tokens_len` tokens, clamped to the last row of the pack.
+pub fn style_row(pack: &VoicePack, tokens_len: usize) -> &[f32; STYLE_DIM] {
+    &pack[style_index(pack.len(), tokens_len)][0]
+}
+
+/// Row of a pack with `rows` rows that styles an utterance of `tokens_len` tokens.
+pub fn style_index(rows: usize, tokens_len: usize) -> usize {
+    tokens_len.min(rows.saturating_sub(1))
+}
+
+/// Checks that a name can be used for a saved voice without clashing with the blend syntax.
+pub fn validate_voice_name(name: &str) -> Result<(), KokoError> {
+    if name.is_empty() {
+        return Err(KokoError::InvalidVoice(
+            "voice name must not be empty".to_string(),
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(KokoError::InvalidVoice(format!(
+            "voice name '{}' may only contain letters, digits, '_' and '-'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// File custom voices are saved to, next to the voices file: `voices-v1.0.bin`
+/// becomes `voices-v1.0.custom.npz`.
+pub fn custom_voices_path(voices_path: &str) -> PathBuf {
+    let path = Path::new(voices_path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "voices".to_string());
+    path.with_file_name(format!("{}.custom.npz", stem))
+}
+
+/// Validates an array shaped `(rows, 1, 256)` or `(rows, 256)` and converts it to a pack.
+pub fn pack_from_array(name: &str, array: ArrayViewD<f32>) -> Result<VoicePack, KokoError> {
+    let shape = array.shape();
+    let rows = match shape {
+        [rows, 1, STYLE_DIM] | [rows, STYLE_DIM] => *rows,
+        _ => {
+            return Err(KokoError::InvalidStyleShape(format!(
+                "voice '{}' has shape {:?}, expected (rows, 1, {}) or (rows, {})",
+                name, shape, STYLE_DIM, STYLE_DIM
+            )));
+        }
+    };
+    if rows == 0 || rows > MAX_STYLE_ROWS {
+        return Err(KokoError::InvalidStyleShape(format!(
+            "voice '{}' has {} style rows, expected 1 to {}",
+            name, rows, MAX_STYLE_ROWS
+        )));
+    }
+    if array.iter().any(|v| !v.is_finite()) {
+        return Err(KokoError::InvalidStyleShape(format!(
+            "voice '{}' contains NaN or infinite values",
+            name
+        )));
+    }
+
+    let mut pack = vec![[[0.0; STYLE_DIM]; 1]; rows];
+    for (out, value) in pack
+        .iter_mut()
+        .flat_map(|row| row[0].iter_mut())
+        .zip(array.iter())
+    {
+        *out = *value;
+    }
+    Ok(pack)
+}
+
+/// A voice file that can't be parsed.
+fn unreadable(path: &Path, e: impl Display) -> KokoError {
+    KokoError::InvalidVoice(format!("can't read voice file {}: {}", path.display(), e))
+}
+
+/// Reads every voice of an NPZ archive.
+pub fn read_npz_voices(path: impl AsRef<Path>) -> Result<HashMap<String, VoicePack>, KokoError> {
+    let path = path.as_ref();
+    let mut npz = NpzReader::new(File::open(path)?).map_err(|e| unreadable(path, e))?;
+    let mut map = HashMap::new();
+
+    for voice in npz.names().map_err(|e| unreadable(path, e))? {
+        let voice_data: ArrayD<f32> = npz.by_name(&voice).map_err(|e| unreadable(path, e))?;
+        let pack = pack_from_array(&voice, voice_data.view())?;
+        map.insert(voice, pack);
+    }
+
+    Ok(map)
+}
+
+/// Reads voices from a `.npz` archive, a single-voice `.npy` array, or a `.bin` file that
+/// is either an NPZ archive (like `voices-v1.0.bin`) or raw little-endian f32 style rows.
+///
+/// Single-voice files are named after the file stem.
+pub fn read_voice_file(path: impl AsRef<Path>) -> Result<HashMap<String, VoicePack>, KokoError> {
+    let path = path.as_ref();
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .ok_or_else(|| {
+            KokoError::InvalidVoice(format!("invalid voice file path: {}", path.display()))
+        })?;
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "npz" => read_npz_voices(path),
+        "npy" => {
+            let array: ArrayD<f32> = read_npy(path).map_err(|e| unreadable(path, e))?;
+            let pack = pack_from_array(&stem, array.view())?;
+            Ok(HashMap::from([(stem, pack)]))
+        }
+        "bin" => {
+            let bytes = std::fs::read(path)?;
+            // Zip local file header magic
+            if bytes.starts_with(b"PK\x03\x04") {
+                return read_npz_voices(path);
+            }
+            if bytes.len() % (4 * STYLE_DIM) != 0 {
+                return Err(KokoError::InvalidStyleShape(format!(
+                    "raw voice file {} is {} bytes, not a whole number of {}-float rows",
+                    path.display(),
+                    bytes.len(),
+                    STYLE_DIM
+                )));
+            }
+            let values: Vec<f32> = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            let array = ArrayD::from_shape_vec(vec![values.len() / STYLE_DIM, STYLE_DIM], values)
+                .map_err(|e| unreadable(path, e))?;
+            let pack = pack_from_array(&stem, array.view())?;
+            Ok(HashMap::from([(stem, pack)]))
+        }
+        _ => Err(KokoError::InvalidVoice(format!(
+            "unsupported voice file '{}', expected .npy, .npz or .bin",
+            path.display()
+        ))),
+    }
+}
+
+/// Reads a model's voice set: a single voice file (see [`read_voice_file`]) or a
+/// directory with one file per voice, as the v1.1-zh ONNX export ships them.
+pub fn read_voices(path: impl AsRef<Path>) -> Result<HashMap<String, VoicePack>, KokoError> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return read_voice_file(path);
+    }
+
+    let mut voices = HashMap::new();
+    for entry in std::fs::read_dir(path)? {
+        let file = entry?.path();
+        let supported = file
+            .extension()
+            .is_some_and(|e| ["npy", "npz", "bin"].contains(&e.to_string_lossy().as_ref()));
+        if file.is_file() && supported {
+            voices.extend(read_voice_file(&file)?);
+        }
+    }
+    if voices.is_empty() {
+        return Err(KokoError::InvalidVoice(format!(
+            "no voice files in {}",
+            path.display()
+        )));
+    }
+    Ok(voices)
+}
+
+/// Writes voices to an NPZ archive in the same `(rows, 1, 256)` layout Kokoro ships.
+/// An empty set of voices removes the archive instead.
+pub fn write_npz_voices(
+    path: impl AsRef<Path>,
+    voices: &HashMap<String, VoicePack>,
+) -> Result<(), KokoError> {
+    if voices.is_empty() {
+        if path.as_ref().exists() {
+            std::fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+
+    let mut npz = NpzWriter::new(File::create(path)?);
+    let mut names: Vec<&String> = voices.keys().collect();
+    names.sort();
+
+    for name in names {
+        let pack = &voices[name];
+        let array = Array3::from_shape_fn((pack.len(), 1, STYLE_DIM), |(i, j, k)| pack[i][j][k]);
+        npz.add_array(name.as_str(), &array)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+    }
+    npz.finish().map_err(|e| io::Error::other(e.to_string()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(value: f32, rows: usize) -> VoicePack {
+        vec![[[value; STYLE_DIM]; 1]; rows]
+    }
+
+    #[test]
+    fn test_parse_blend() {
+        let blend = VoiceBlend::parse("af_heart:0.25 + af_aoede:0.75").unwrap();
+        assert_eq!(
+            blend.components,
+            vec![
+                ("af_heart".to_string(), 0.25),
+                ("af_aoede".to_string(), 0.75)
+            ]
+        );
+
+        let legacy = VoiceBlend::parse("af_aoede.3+af_heart.7").unwrap();
+        assert!((legacy.components[0].1 - 0.3).abs() < 1e-6);
+        assert!((legacy.components[1].1 - 0.7).abs() < 1e-6);
+
+        assert_eq!(VoiceBlend::parse("af_sky").unwrap().components[0].1, 1.0);
+        assert!(VoiceBlend::parse("af_sky:abc").is_err());
+        assert!(VoiceBlend::parse("af_sky+").is_err());
+    }
+
+    #[test]
+    fn test_blend_and_normalize() {
+        let styles = HashMap::from([
+            ("a".to_string(), pack(1.0, 4)),
+            ("b".to_string(), pack(3.0, 2)),
+        ]);
+
+        let blend = VoiceBlend::parse("a:1+b:3").unwrap().normalized().unwrap();
+        let blended = blend.blend_pack(&styles).unwrap();
+        assert_eq!(blended.len(), 2);
+        assert!((blended[1][0][0] - 2.5).abs() < 1e-6);
+
+        // Rows past the end of a pack clamp instead of panicking
+        let row = blend.blend_row(&styles, 1000).unwrap();
+        assert!((row[0] - 2.5).abs() < 1e-6);
+
+        let err = VoiceBlend::parse("a+missing").unwrap().blend_pack(&styles);
+        assert!(matches!(err, Err(KokoError::VoiceNotFound(name)) if name == "missing"));
+        assert!(VoiceBlend::parse("a:1+b:-1").unwrap().normalized().is_err());
+    }
+
+    #[test]
+    fn test_pack_from_array() {
+        let array = ArrayD::from_elem(vec![510, 1, STYLE_DIM], 0.5f32);
+        assert_eq!(pack_from_array("v", array.view()).unwrap().len(), 510);
+
+        let flat = ArrayD::from_elem(vec![4, STYLE_DIM], 0.5f32);
+        assert_eq!(pack_from_array("v", flat.view()).unwrap()[3][0][255], 0.5);
+
+        let wide = ArrayD::from_elem(vec![4, 1, 128], 0.5f32);
+        assert!(pack_from_array("v", wide.view()).is_err());
+        let empty = ArrayD::from_elem(vec![0, STYLE_DIM], 0.5f32);
+        assert!(pack_from_array("v", empty.view()).is_err());
+        let nan = ArrayD::from_elem(vec![1, STYLE_DIM], f32::NAN);
+        assert!(pack_from_array("v", nan.view()).is_err());
+    }
+
+    #[test]
+    fn test_read_raw_bin_voice() {
+        let path =
+            std::env::temp_dir().join(format!("kokoros_{}_my_voice.bin", uuid::Uuid::new_v4()));
+        let bytes: Vec<u8> = (0..2 * STYLE_DIM)
+            .flat_map(|i| (i as f32).to_le_bytes())
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+
+        let voices = read_voice_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let pack = voices.values().next().unwrap();
+        assert_eq!(pack.len(), 2);
+        assert_eq!(pack[1][0][0], STYLE_DIM as f32);
+    }
+
+    #[test]
+    fn test_read_voice_dir() {
+        let dir = std::env::temp_dir().join(format!("kokoros_voices_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let row: Vec<u8> = (0..STYLE_DIM).flat_map(|_| 0.5f32.to_le_bytes()).collect();
+        std::fs::write(dir.join("zf_001.bin"), &row).unwrap();
+        std::fs::write(dir.join("zm_010.bin"), &row).unwrap();
+        std::fs::write(dir.join("README.md"), "voices").unwrap();
+
+        let voices = read_voices(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut names: Vec<&String> = voices.keys().collect();
+        names.sort();
+        assert_eq!(names, vec!["zf_001", "zm_010"]);
+    }
+
+    #[test]
+    fn test_voice_metadata() {
+        let mut library = VoiceLibrary::default();
+        library.insert("bf_emma".to_string(), pack(0.0, 1), VoiceSource::Builtin);
+        library.insert("my_voice".to_string(), pack(0.0, 1), VoiceSource::Imported);
+
+        let info = library.info();
+        assert_eq!(info[0].name, "bf_emma");
+        assert_eq!(info[0].language, Some("en-gb"));
+        assert_eq!(info[0].gender, Some("female"));
+        assert_eq!(info[1].source, VoiceSource::Imported);
+        assert_eq!(info[1].language, None);
+        assert_eq!(library.voices_from(VoiceSource::Imported).len(), 1);
+    }
+
+    #[test]
+    fn test_compatible_voice() {
+        let available: Vec<String> = ["ff_siwis", "jf_alpha", "jm_kumo", "zf_xiaobei"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            compatible_voice("am_adam", "ja", &available).as_deref(),
+            Some("jm_kumo")
+        );
+        assert_eq!(
+            compatible_voice("af_sky", "fr-fr", &available).as_deref(),
+            Some("ff_siwis")
+        );
+        assert_eq!(
+            compatible_voice("af_sky", "en-gb", &available).as_deref(),
+            Some("af_sky")
+        );
+        assert_eq!(compatible_voice("af_sky", "ru", &available), None);
+    }
+
+    #[test]
+    fn test_custom_voices_path() {
+        assert_eq!(
+            custom_voices_path("resources/voices-v1.0.bin"),
+            PathBuf::from("resources/voices-v1.0.custom.npz")
+        );
+    }
+}
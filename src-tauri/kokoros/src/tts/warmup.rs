@@ -0,0 +1,69 @@
+use crate::tts::g2p::G2p;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Text synthesized to warm a model instance up, short enough to fit one chunk
+pub const WARMUP_TEXT: &str = "Hello, warming up.";
+
+pub const WARMUP_LANG: &str = "en-us";
+
+/// Voice warm-ups use when it's installed
+pub const WARMUP_VOICE: &str = "af_heart";
+
+/// How long each warm-up step took. The first request after a warm-up no
+/// longer pays for these.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WarmupTimings {
+    /// Loading the G2P backend and its language data
+    pub phonemizer_secs: f64,
+    /// Dummy synthesis on each model instance, mostly ONNX graph initialization
+    pub instance_secs: Vec<f64>,
+    pub total_secs: f64,
+}
+
+impl WarmupTimings {
+    pub fn new(phonemizer: Duration, instances: &[Duration]) -> Self {
+        let instance_secs: Vec<f64> = instances.iter().map(Duration::as_secs_f64).collect();
+        Self {
+            phonemizer_secs: phonemizer.as_secs_f64(),
+            total_secs: phonemizer.as_secs_f64() + instance_secs.iter().sum::<f64>(),
+            instance_secs,
+        }
+    }
+}
+
+/// Phonemizes [`WARMUP_TEXT`] once, returning how long it took.
+pub fn preload_phonemizer(g2p: &dyn G2p) -> Result<Duration, String> {
+    let start = Instant::now();
+    g2p.phonemize(WARMUP_TEXT, WARMUP_LANG)?;
+    Ok(start.elapsed())
+}
+
+/// Voice to warm up with out of `voices`, which are sorted by name.
+pub fn warmup_voice(voices: &[String]) -> Option<&str> {
+    voices
+        .iter()
+        .find(|voice| voice.as_str() == WARMUP_VOICE)
+        .or_else(|| voices.first())
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_voice() {
+        let voices = vec!["af_alloy".to_string(), "af_heart".to_string()];
+        assert_eq!(warmup_voice(&voices), Some("af_heart"));
+        assert_eq!(warmup_voice(&voices[..1]), Some("af_alloy"));
+        assert_eq!(warmup_voice(&[]), None);
+
+        let timings = WarmupTimings::new(
+            Duration::from_millis(250),
+            &[Duration::from_secs(1), Duration::from_millis(500)],
+        );
+        assert_eq!(timings.instance_secs, vec![1.0, 0.5]);
+        assert!((timings.total_secs - 1.75).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,625 @@
+use crate::error::KokoError;
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_LENGTH, RANGE};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+/// Bytes downloaded between two progress events
+const PROGRESS_STEP: u64 = 1 << 20;
+
+/// How files are downloaded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadOptions {
+    /// Never download; files that aren't there yet are an error
+    pub offline: bool,
+    #[serde(with = "secs")]
+    pub connect_timeout: Duration,
+    /// Longest wait for the next bytes of a response before giving up on it
+    #[serde(with = "secs")]
+    pub read_timeout: Duration,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            offline: false,
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Durations as (fractional) seconds in settings files
+mod secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A file to download, from the first of `urls` that works, and what it's
+/// checked against. Without a size or checksum only what's given is checked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    /// Name shown in progress events
+    pub name: String,
+    pub urls: Vec<String>,
+    pub size: Option<u64>,
+    /// Lowercase hex SHA-256 of the file
+    pub sha256: Option<String>,
+}
+
+impl Download {
+    /// A download from a single URL, named after `path`, that isn't verified.
+    pub fn unverified(url: &str, path: &Path) -> Self {
+        Self {
+            name: path.file_name().map_or_else(
+                || url.to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            urls: vec![url.to_string()],
+            size: None,
+            sha256: None,
+        }
+    }
+}
+
+/// What a download is doing, for progress UIs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum DownloadEvent {
+    #[serde(rename_all = "camelCase")]
+    Started {
+        name: String,
+        url: String,
+        /// Bytes kept from an earlier, interrupted download
+        resumed_from: u64,
+        total: Option<u64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Progress {
+        name: String,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    Verifying {
+        name: String,
+    },
+    /// One source failed; the next mirror is tried if there is one
+    SourceFailed {
+        name: String,
+        url: String,
+        error: String,
+    },
+    Finished {
+        name: String,
+        path: PathBuf,
+    },
+}
+
+pub type ProgressCallback = Arc<dyn Fn(DownloadEvent) + Send + Sync>;
+
+/// Downloads files next to their destination first (`<file>.part`), resuming
+/// an interrupted download with an HTTP range request, and moves them into
+/// place once they're verified.
+#[derive(Clone)]
+pub struct DownloadManager {
+    client: reqwest::Client,
+    options: DownloadOptions,
+    progress: Option<ProgressCallback>,
+}
+
+impl DownloadManager {
+    pub fn new(options: DownloadOptions) -> Result<Self, KokoError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .read_timeout(options.read_timeout)
+            .build()
+            .map_err(|e| KokoError::Download(format!("failed to create HTTP client: {}", e)))?;
+        Ok(Self {
+            client,
+            options,
+            progress: None,
+        })
+    }
+
+    /// Reports download events to `progress`.
+    pub fn with_progress(mut self, progress: ProgressCallback) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn options(&self) -> &DownloadOptions {
+        &self.options
+    }
+
+    /// Makes sure `path` holds `download`. An existing file of the right size
+    /// is kept when it matches the checksum, or when there's none to check;
+    /// otherwise each URL is tried in turn until one gives a file that passes
+    /// verification.
+    pub async fn fetch(&self, download: &Download, path: &Path) -> Result<(), KokoError> {
+        let existing = std::fs::metadata(path).ok().filter(|meta| meta.is_file());
+        if existing
+            .as_ref()
+            .is_some_and(|meta| download.size.is_none_or(|size| meta.len() == size))
+        {
+            if download.sha256.is_none() {
+                return Ok(());
+            }
+            self.emit(DownloadEvent::Verifying {
+                name: download.name.clone(),
+            });
+            match verify(path, download) {
+                Ok(()) => return Ok(()),
+                Err(e) => tracing::warn!("Replacing {}: {}", path.display(), e),
+            }
+        }
+        if self.options.offline {
+            return Err(KokoError::Download(format!(
+                "{} is {} and downloads are off (offline mode)",
+                path.display(),
+                if existing.is_some() {
+                    "corrupt"
+                } else {
+                    "missing"
+                }
+            )));
+        }
+        if download.urls.is_empty() {
+            return Err(KokoError::Download(format!(
+                "{} is missing and has no download URL",
+                path.display()
+            )));
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("failed to create {}: {}", parent.display(), e),
+                )
+            })?;
+        }
+
+        let partial = partial_path(path);
+        let mut errors = Vec::new();
+        for url in &download.urls {
+            let result = match self.fetch_from(download, url, &partial).await {
+                Ok(()) => {
+                    self.emit(DownloadEvent::Verifying {
+                        name: download.name.clone(),
+                    });
+                    // A corrupt file can't be resumed, the next source starts over
+                    verify(&partial, download).inspect_err(|_| {
+                        let _ = std::fs::remove_file(&partial);
+                    })
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {
+                    std::fs::rename(&partial, path).map_err(|e| {
+                        std::io::Error::new(
+                            e.kind(),
+                            format!("failed to move {} into place: {}", path.display(), e),
+                        )
+                    })?;
+                    tracing::info!("Downloaded {} from {}", path.display(), url);
+                    self.emit(DownloadEvent::Finished {
+                        name: download.name.clone(),
+                        path: path.to_path_buf(),
+                    });
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::warn!("Downloading {} from {} failed: {}", download.name, url, e);
+                    self.emit(DownloadEvent::SourceFailed {
+                        name: download.name.clone(),
+                        url: url.clone(),
+                        error: e.clone(),
+                    });
+                    errors.push(format!("{}: {}", url, e));
+                }
+            }
+        }
+        Err(KokoError::Download(format!(
+            "failed to download {}: {}",
+            download.name,
+            errors.join("; ")
+        )))
+    }
+
+    /// Downloads `url` into `partial`, continuing after the bytes it already has.
+    async fn fetch_from(
+        &self,
+        download: &Download,
+        url: &str,
+        partial: &Path,
+    ) -> Result<(), String> {
+        let mut offset = std::fs::metadata(partial).map_or(0, |meta| meta.len());
+        if download.size.is_some_and(|size| offset >= size) {
+            // Complete already, or too long and failing verification
+            return Ok(());
+        }
+
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let mut response = request.send().await.map_err(|e| e.to_string())?;
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {}
+            // The server ignored the range, start over
+            StatusCode::OK => offset = 0,
+            // Nothing after `offset`, so the file is complete
+            StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => return Ok(()),
+            status => return Err(format!("server responded {}", status)),
+        }
+        let length = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        let total = length.map(|length| offset + length).or(download.size);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(offset > 0)
+            .truncate(offset == 0)
+            .open(partial)
+            .await
+            .map_err(|e| format!("failed to open {}: {}", partial.display(), e))?;
+        if offset > 0 {
+            tracing::info!("Resuming {} from {} at byte {}", download.name, url, offset);
+        } else {
+            tracing::info!("Downloading {} from {}", download.name, url);
+        }
+        self.emit(DownloadEvent::Started {
+            name: download.name.clone(),
+            url: url.to_string(),
+            resumed_from: offset,
+            total,
+        });
+
+        let mut downloaded = offset;
+        let mut reported = offset;
+        let received = loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e.to_string()),
+            };
+            if let Err(e) = file.write_all(&chunk).await {
+                break Err(format!("failed to write {}: {}", partial.display(), e));
+            }
+            downloaded += chunk.len() as u64;
+            if downloaded - reported >= PROGRESS_STEP {
+                reported = downloaded;
+                self.emit(DownloadEvent::Progress {
+                    name: download.name.clone(),
+                    downloaded,
+                    total,
+                });
+            }
+        };
+        // Keep what arrived before a failure, the next attempt resumes after it
+        file.flush()
+            .await
+            .map_err(|e| format!("failed to write {}: {}", partial.display(), e))?;
+        received?;
+        self.emit(DownloadEvent::Progress {
+            name: download.name.clone(),
+            downloaded,
+            total,
+        });
+
+        match length {
+            Some(length) if downloaded - offset < length => Err(format!(
+                "connection closed after {} of {} bytes",
+                downloaded - offset,
+                length
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn emit(&self, event: DownloadEvent) {
+        if let Some(progress) = &self.progress {
+            progress(event);
+        }
+    }
+}
+
+/// Where `path` is downloaded to before it's verified.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Checks `path` against the size and checksum of `download`. Without a
+/// checksum the file's own is logged so it can be added to the manifest.
+pub fn verify(path: &Path, download: &Download) -> Result<(), String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+        .len();
+    match download.size {
+        Some(expected) if size != expected => {
+            return Err(format!(
+                "{} has {} bytes, expected {}",
+                download.name, size, expected
+            ));
+        }
+        _ => {}
+    }
+
+    let sha256 = sha256_file(path)?;
+    match &download.sha256 {
+        Some(expected) if !expected.eq_ignore_ascii_case(&sha256) => Err(format!(
+            "{} has SHA-256 {}, expected {}",
+            download.name, sha256, expected
+        )),
+        Some(_) => Ok(()),
+        None => {
+            tracing::warn!(
+                "{} has no checksum to verify against, its SHA-256 is {}",
+                download.name,
+                sha256
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Lowercase hex SHA-256 of the file at `path`.
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    /// Serves `body` at `/file` over HTTP on a local port, honouring range
+    /// requests. Responses are cut after `cut_after` bytes while it's set, and
+    /// every other path is a 404. Returns the base URL and the requests seen.
+    fn serve(body: Vec<u8>, cut_after: Option<usize>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            let mut cut_after = cut_after;
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut range_start = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(range) = line.to_lowercase().strip_prefix("range: bytes=") {
+                        range_start = range.trim().trim_end_matches('-').parse::<usize>().ok();
+                    }
+                }
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or("")
+                    .to_string();
+                seen.lock().unwrap().push(format!(
+                    "{} {}",
+                    path,
+                    range_start.map_or("-".to_string(), |start| start.to_string())
+                ));
+
+                if path != "/file" {
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    );
+                    continue;
+                }
+                let start = range_start.unwrap_or(0).min(body.len());
+                let status = if range_start.is_some() {
+                    "206 Partial Content"
+                } else {
+                    "200 OK"
+                };
+                let rest = &body[start..];
+                let sent = cut_after
+                    .take()
+                    .map_or(rest.len(), |cut| cut.min(rest.len()));
+                let _ = stream.write_all(
+                    format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        status,
+                        rest.len()
+                    )
+                    .as_bytes(),
+                );
+                let _ = stream.write_all(&rest[..sent]);
+            }
+        });
+        (base, requests)
+    }
+
+    fn body() -> Vec<u8> {
+        (0..3 * PROGRESS_STEP as usize)
+            .map(|i| (i % 251) as u8)
+            .collect()
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("kokoros-download-{}", uuid::Uuid::new_v4()))
+            .join("model.onnx")
+    }
+
+    fn download(urls: Vec<String>, body: &[u8]) -> Download {
+        Download {
+            name: "model.onnx".into(),
+            urls,
+            size: Some(body.len() as u64),
+            sha256: Some(sha256_hex(body)),
+        }
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        Sha256::digest(data)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_resumes_and_verifies() {
+        let body = body();
+        let (base, requests) = serve(body.clone(), Some(1000));
+        let path = temp_path();
+        let download = download(vec![format!("{}/file", base)], &body);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let manager = DownloadManager::new(DownloadOptions::default())
+            .unwrap()
+            .with_progress(Arc::new(move |event| recorded.lock().unwrap().push(event)));
+
+        // The first response is cut short and its bytes are kept
+        assert!(manager.fetch(&download, &path).await.is_err());
+        assert_eq!(std::fs::metadata(partial_path(&path)).unwrap().len(), 1000);
+
+        manager.fetch(&download, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert!(!partial_path(&path).exists());
+        assert_eq!(*requests.lock().unwrap(), vec!["/file -", "/file 1000"]);
+
+        let events = events.lock().unwrap();
+        assert!(events.contains(&DownloadEvent::Started {
+            name: "model.onnx".into(),
+            url: format!("{}/file", base),
+            resumed_from: 1000,
+            total: Some(body.len() as u64),
+        }));
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Finished { .. })
+        ));
+        drop(events);
+
+        // An existing file is kept without a request
+        manager.fetch(&download, &path).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        // unless it doesn't match the checksum
+        std::fs::write(&path, vec![0; body.len()]).unwrap();
+        manager.fetch(&download, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mirrors_and_checksums() {
+        let body = body();
+        let (base, requests) = serve(body.clone(), None);
+        let path = temp_path();
+        let manager = DownloadManager::new(DownloadOptions::default()).unwrap();
+
+        // A missing file on the first mirror falls through to the second
+        let download = download(
+            vec![format!("{}/gone", base), format!("{}/file", base)],
+            &body,
+        );
+        manager.fetch(&download, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert_eq!(*requests.lock().unwrap(), vec!["/gone -", "/file -"]);
+
+        // A checksum mismatch is an error and leaves nothing behind
+        let other = temp_path();
+        let mut corrupt = download.clone();
+        corrupt.sha256 = Some("00".repeat(32));
+        let err = manager
+            .fetch(&corrupt, &other)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("SHA-256"), "{}", err);
+        assert!(!other.exists() && !partial_path(&other).exists());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::remove_dir_all(other.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_offline() {
+        let path = temp_path();
+        let manager = DownloadManager::new(DownloadOptions {
+            offline: true,
+            ..DownloadOptions::default()
+        })
+        .unwrap();
+        let download = Download::unverified("http://127.0.0.1:9/file", &path);
+        let err = manager
+            .fetch(&download, &path)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("offline"), "{}", err);
+
+        // Files that are there are fine offline
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"model").unwrap();
+        manager.fetch(&download, &path).await.unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_options_from_json() {
+        let options: DownloadOptions =
+            serde_json::from_str(r#"{"offline": true, "read_timeout": 2.5}"#).unwrap();
+        assert!(options.offline);
+        assert_eq!(options.read_timeout, Duration::from_millis(2500));
+        assert_eq!(
+            options.connect_timeout,
+            DownloadOptions::default().connect_timeout
+        );
+    }
+}
@@ -1,43 +1,42 @@
+use crate::error::KokoError;
+use crate::utils::download::{Download, DownloadEvent, DownloadManager, DownloadOptions};
 use indicatif::{ProgressBar, ProgressStyle};
 use serde_json::Value;
+use std::sync::Arc;
 use std::{io::Read, path::Path};
-use tokio::{fs::File, io::AsyncWriteExt};
 
-pub async fn download_file_from_url(
-    url: &str,
-    path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if let Some(parent) = Path::new(path).parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-
-    let mut resp = reqwest::get(url).await?;
-
-    if resp.status().is_success() {
-        let total_size = resp.content_length().unwrap_or(0);
-
-        eprintln!("Downloading {} - total size: {}", path, total_size);
-
-        let pb = ProgressBar::new(total_size);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .unwrap()
-            .progress_chars("#>-"));
-
-        let mut file = File::create(path).await?;
-        let mut downloaded = 0;
-
-        while let Some(chunk) = resp.chunk().await? {
-            file.write_all(&chunk).await?;
-            downloaded += chunk.len();
-            pb.set_position(downloaded.try_into()?);
-        }
-
-        pb.finish_with_message("Download completed");
-        Ok(())
-    } else {
-        Err(format!("Failed to download file: {}", resp.status()).into())
-    }
+/// Downloads `url` to `path` unless it's there already, showing a progress bar
+/// on stderr. An interrupted download is resumed on the next call.
+pub async fn download_file_from_url(url: &str, path: &str) -> Result<(), KokoError> {
+    let pb = ProgressBar::new(0);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+
+    let bar = pb.clone();
+    let downloads =
+        DownloadManager::new(DownloadOptions::default())?.with_progress(Arc::new(move |event| {
+            match event {
+                DownloadEvent::Started {
+                    resumed_from,
+                    total,
+                    ..
+                } => {
+                    bar.set_length(total.unwrap_or(0));
+                    bar.set_position(resumed_from);
+                }
+                DownloadEvent::Progress { downloaded, .. } => bar.set_position(downloaded),
+                _ => {}
+            }
+        }));
+
+    let path = Path::new(path);
+    downloads
+        .fetch(&Download::unverified(url, path), path)
+        .await?;
+    pb.finish_with_message("Download completed");
+    Ok(())
 }
 
 pub fn load_json_file(path: &str) -> Result<Value, String> {
@@ -1,4 +1,5 @@
 pub mod debug;
+pub mod download;
 pub mod fileio;
 pub mod mp3;
 pub mod wav;
@@ -1,235 +1,33 @@
-use std::error::Error;
+use std::collections::HashMap;
 use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 // Tauri specific imports
-use tauri::Manager; // Add Manager for event emission if needed for streaming
+use tauri::path::BaseDirectory::Resource;
+use tauri::{Emitter, Manager, State}; // Add Manager for event emission if needed for streaming
 
 use kokoros::{
+    error::KokoError,
+    onn::session_config::SessionConfig,
+    tts::g2p::G2pKind,
+    tts::inspect::SpeechInspection,
     tts::koko::{InitConfig as TTSKokoInitConfig, TTSKoko},
+    tts::phonemizer_service::{PhonemizerMetrics, PhonemizerService},
+    tts::prepare::{prepare_for_speech, PrepareOptions},
+    tts::registry::{ModelRegistry, ModelVariant},
+    tts::style_space::StyleAxis,
+    tts::voices::{voice_language, VoiceInfo},
+    utils::download::{DownloadEvent, DownloadManager, DownloadOptions},
     utils::mp3::pcm_to_mp3,
     utils::wav::{write_audio_chunk, WavHeader},
 };
-use regex::Regex;
-use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{error, info}; // Still good for Rust-side logging
 use uuid::Uuid;
 
-use crate::AppState;
-
-/// Break words used for chunk splitting
-const BREAK_WORDS: &[&str] = &[
-    "and", "or", "but", "&", "because", "if", "since", "though", "although", "however", "which",
-];
-
-#[allow(unused)]
-/// Split text into speech chunks for streaming (utility function, not directly a command)
-/// Prioritizes sentence boundaries over word count for natural speech breaks
-/// Then applies center-break word splitting for long chunks
-fn split_text_into_speech_chunks(text: &str, words_per_chunk: usize) -> Vec<String> {
-    let mut chunks = Vec::new();
-    let mut current_chunk = String::new();
-    let mut word_count = 0;
-
-    // First pass: split by punctuation
-    for word in text.split_whitespace() {
-        if !current_chunk.is_empty() {
-            current_chunk.push(' ');
-        }
-        // Check for numbered list patterns: 1. 2) 3: (4), 5(\s)[.\)\:]
-        let is_numbered_break = is_numbered_list_item(word);
-
-        if is_numbered_break && !current_chunk.is_empty() {
-            chunks.push(current_chunk.trim().to_string());
-            current_chunk.clear();
-            word_count = 0;
-        }
-        current_chunk.push_str(word);
-        word_count += 1;
-
-        // Check for unconditional breaks (always break regardless of word count)
-        let ends_with_unconditional = word.ends_with('.')
-            || word.ends_with('!')
-            || word.ends_with('?')
-            || word.ends_with(':')
-            || word.ends_with(';');
-
-        // Check for conditional breaks (commas - only break if enough words)
-        let ends_with_conditional = word.ends_with(',');
-
-        // Split conditions:
-        // 1. Unconditional punctuation - always break
-        // 2. Conditional punctuation + target word count reached
-        if ends_with_unconditional
-            || is_numbered_break
-            || (ends_with_conditional && word_count >= words_per_chunk)
-        {
-            chunks.push(current_chunk.trim().to_string());
-            current_chunk.clear();
-            word_count = 0;
-        }
-    }
-
-    if !current_chunk.trim().is_empty() {
-        chunks.push(current_chunk.trim().to_string());
-    }
-
-    // Second pass: apply center-break splitting for long chunks
-    // All chunks: ≥12 words
-    // First 2 chunks: punctuation priority, Others: break words only
-    let mut final_chunks = Vec::new();
-    for (index, chunk) in chunks.iter().enumerate() {
-        let threshold = 12;
-        let use_punctuation = index < 2; // First 2 chunks can use punctuation
-        let split_chunks = split_long_chunk_with_depth(chunk, threshold, use_punctuation, 0);
-        final_chunks.extend(split_chunks);
-    }
-
-    // Final processing: Move break words from end of chunks to beginning of next chunk
-    for i in 0..final_chunks.len() - 1 {
-        let current_chunk = &final_chunks[i];
-        let words: Vec<&str> = current_chunk.trim().split_whitespace().collect();
-
-        if let Some(last_word) = words.last() {
-            // Check if last word is a break word (case insensitive)
-            if BREAK_WORDS.contains(&last_word.to_lowercase().as_str()) && words.len() > 1 {
-                // Only move if it won't create an empty chunk (need more than 1 word)
-                let new_current = words[..words.len() - 1].join(" ");
-
-                // Add break word to beginning of next chunk
-                let next_chunk = &final_chunks[i + 1];
-                let new_next = format!("{} {}", last_word, next_chunk);
-
-                // Update the chunks
-                final_chunks[i] = new_current;
-                final_chunks[i + 1] = new_next;
-            }
-        }
-    }
-
-    final_chunks
-}
-
-#[allow(unused)]
-/// Check if a word is a numbered list item: 1. 2) 3: (4), 5(\s)[.\)\:]
-fn is_numbered_list_item(word: &str) -> bool {
-    let numbered_regex = Regex::new(r"^\(?[0-9]+[.\)\:],?$").unwrap();
-    numbered_regex.is_match(word)
-}
-
-#[allow(unused)]
-fn split_long_chunk_with_depth(
-    chunk: &str,
-    threshold: usize,
-    use_punctuation: bool,
-    depth: usize,
-) -> Vec<String> {
-    // Prevent infinite recursion
-    if depth >= 3 {
-        return vec![chunk.to_string()];
-    }
-    let words: Vec<&str> = chunk.split_whitespace().collect();
-    let word_count = words.len();
-
-    // Only split if chunk meets the threshold
-    if word_count < threshold {
-        return vec![chunk.to_string()];
-    }
-
-    let center = word_count / 2;
-
-    if use_punctuation {
-        // Priority 1: Search for commas closest to center
-        if let Some(pos) = find_closest_punctuation(&words, center, &[","]) {
-            if pos >= 3 && pos < words.len() {
-                let first_chunk = words[..pos].join(" ");
-                let second_chunk = words[pos..].join(" ");
-
-                // Recursively split both chunks if they're still too long
-                let mut result = Vec::new();
-                result.extend(split_long_chunk_with_depth(
-                    &first_chunk,
-                    threshold,
-                    use_punctuation,
-                    depth + 1,
-                ));
-                result.extend(split_long_chunk_with_depth(
-                    &second_chunk,
-                    threshold,
-                    use_punctuation,
-                    depth + 1,
-                ));
-                return result;
-            }
-        }
-    }
-
-    // Priority 2: Search for break words closest to center
-    if let Some(pos) = find_closest_break_word(&words, center, BREAK_WORDS) {
-        if pos >= 3 && pos < words.len() {
-            let first_chunk = words[..pos].join(" ");
-            let second_chunk = words[pos..].join(" ");
-
-            // Recursively split both chunks if they're still too long
-            let mut result = Vec::new();
-            result.extend(split_long_chunk_with_depth(
-                &first_chunk,
-                threshold,
-                use_punctuation,
-                depth + 1,
-            ));
-            result.extend(split_long_chunk_with_depth(
-                &second_chunk,
-                threshold,
-                use_punctuation,
-                depth + 1,
-            ));
-            return result;
-        }
-    }
-
-    // No suitable break point found, return original chunk
-    vec![chunk.to_string()]
-}
-
-#[allow(unused)]
-/// Find closest punctuation to center
-fn find_closest_punctuation(words: &[&str], center: usize, punctuation: &[&str]) -> Option<usize> {
-    let mut closest_pos = None;
-    let mut min_distance = usize::MAX;
-
-    for (i, word) in words.iter().enumerate() {
-        if punctuation.iter().any(|p| word.ends_with(p)) {
-            let distance = if i < center { center - i } else { i - center };
-            if distance < min_distance {
-                min_distance = distance;
-                closest_pos = Some(i + 1); // Split after the punctuation
-            }
-        }
-    }
-
-    closest_pos
-}
-
-#[allow(unused)]
-/// Find closest break word to center
-fn find_closest_break_word(words: &[&str], center: usize, break_words: &[&str]) -> Option<usize> {
-    let mut closest_pos = None;
-    let mut min_distance = usize::MAX;
-
-    for (i, word) in words.iter().enumerate() {
-        if break_words.contains(&word.to_lowercase().as_str()) {
-            let distance = if i < center { center - i } else { i - center };
-            if distance < min_distance {
-                min_distance = distance;
-                closest_pos = Some(i); // Break word becomes first word of second chunk
-            }
-        }
-    }
-
-    closest_pos
-}
+use crate::cstatus::{StartupStatus, Subsystem, SubsystemState};
+use crate::{cwarmup, AppState};
 
 #[derive(Deserialize, Default, Debug)]
 #[serde(rename_all = "lowercase")]
@@ -294,9 +92,9 @@ pub struct SpeechRequest {
     #[allow(dead_code)]
     return_download_link: Option<bool>,
 
-    /// Language code for text processing (not implemented)
+    /// espeak language code for text processing, the voice's language when
+    /// unset; `auto` detects it per span
     #[serde(default)]
-    #[allow(dead_code)]
     lang_code: Option<String>,
 
     /// Volume multiplier for output audio (not implemented)
@@ -309,47 +107,323 @@ pub struct SpeechRequest {
     #[allow(dead_code)]
     download_format: Option<String>,
 
+    /// How markdown, code blocks, URLs and emoji are turned into speech
+    #[serde(default)]
+    preparation: PrepareOptions,
+
     /// Text normalization options (not implemented)
     #[serde(default)]
     #[allow(dead_code)]
     normalization_options: Option<serde_json::Value>,
 }
 
-// Custom error struct for Tauri commands, must be Serialize
+/// Error of a Tauri command, serialized as `{ "code": ..., "message": ... }`.
+///
+/// The codes are stable for the frontend to switch on, the messages are for
+/// people. The ones shared with [`KokoError`] have the same code.
 #[derive(Debug, Serialize)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
 pub enum TauriSpeechError {
-    KokoError(String),
-    IoError(String),
-    Mp3ConversionError(String),
+    ModelLoad(String),
+    VoiceNotFound(String),
+    InvalidStyleShape(String),
+    InvalidVoice(String),
+    Phonemization(String),
+    Inference(String),
+    Encoding(String),
+    Download(String),
+    Io(String),
+    /// TTS hasn't loaded (yet), see the subsystem status
+    NotReady(String),
+    /// An argument of the command that isn't valid, e.g. an unknown backend
+    InvalidRequest(String),
+    /// The app itself failed, e.g. to send an event
+    Internal(String),
+}
+
+impl TauriSpeechError {
+    /// Prefixes the message with what the command was doing, keeping the code.
+    fn context(mut self, context: impl std::fmt::Display) -> Self {
+        let message = match &mut self {
+            TauriSpeechError::ModelLoad(message)
+            | TauriSpeechError::VoiceNotFound(message)
+            | TauriSpeechError::InvalidStyleShape(message)
+            | TauriSpeechError::InvalidVoice(message)
+            | TauriSpeechError::Phonemization(message)
+            | TauriSpeechError::Inference(message)
+            | TauriSpeechError::Encoding(message)
+            | TauriSpeechError::Download(message)
+            | TauriSpeechError::Io(message)
+            | TauriSpeechError::NotReady(message)
+            | TauriSpeechError::InvalidRequest(message)
+            | TauriSpeechError::Internal(message) => message,
+        };
+        *message = format!("{}: {}", context, message);
+        self
+    }
+
+    pub(crate) fn not_ready() -> Self {
+        TauriSpeechError::NotReady("TTS instance not initialized yet".to_string())
+    }
 }
 
-impl From<Box<dyn Error>> for TauriSpeechError {
-    fn from(err: Box<dyn Error>) -> Self {
-        TauriSpeechError::KokoError(err.to_string())
+impl From<KokoError> for TauriSpeechError {
+    fn from(err: KokoError) -> Self {
+        let message = err.to_string();
+        match err {
+            KokoError::ModelLoad(_) => TauriSpeechError::ModelLoad(message),
+            KokoError::VoiceNotFound(_) => TauriSpeechError::VoiceNotFound(message),
+            KokoError::InvalidStyleShape(_) => TauriSpeechError::InvalidStyleShape(message),
+            KokoError::InvalidVoice(_) => TauriSpeechError::InvalidVoice(message),
+            KokoError::Phonemization(_) => TauriSpeechError::Phonemization(message),
+            KokoError::Inference(_) => TauriSpeechError::Inference(message),
+            KokoError::Encoding(_) => TauriSpeechError::Encoding(message),
+            KokoError::Download(_) => TauriSpeechError::Download(message),
+            KokoError::Io(_) => TauriSpeechError::Io(message),
+        }
     }
 }
 
 impl From<io::Error> for TauriSpeechError {
     fn from(err: io::Error) -> Self {
-        TauriSpeechError::IoError(err.to_string())
+        TauriSpeechError::Io(err.to_string())
     }
 }
 
 // We will store our TTSKoko instance in Tauri's managed state.
 // This allows it to be accessible to commands without recreating it.
 
-// This function will be called during Tauri's setup
-pub async fn setup_tauri_commands(
-    model_path: &str,
-    voices_path: &str,
-    tts_instance_arc_mutex: Arc<Mutex<Option<TTSKoko>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let tts = TTSKoko::new(model_path, voices_path).await;
-    let mut tts_guard = tts_instance_arc_mutex.lock().await;
-    *tts_guard = Some(tts);
+/// Variant loaded until another one is selected, the one bundled with the app
+const DEFAULT_MODEL_VARIANT: &str = "kokoro-v1.0-fp32";
+
+/// Settings read from `name` in the app data directory, `defaults` without
+/// that file. A file that can't be read is logged and ignored.
+pub(crate) fn load_settings<T: DeserializeOwned>(
+    app_data_dir: &Path,
+    name: &str,
+    defaults: T,
+) -> T {
+    let path = app_data_dir.join(name);
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return defaults,
+        Err(e) => {
+            error!("Failed to read {}: {}", path.display(), e);
+            return defaults;
+        }
+    };
+    match serde_json::from_str(&json) {
+        Ok(settings) => {
+            info!("Using settings from {}", path.display());
+            settings
+        }
+        Err(e) => {
+            error!("Ignoring invalid {}: {}", path.display(), e);
+            defaults
+        }
+    }
+}
+
+/// ONNX Runtime options from `session.json` in the app data directory. Without
+/// that file `defaults` are used, with optimized models cached in the app data
+/// directory for the providers that can save them.
+pub fn load_session_config(app_data_dir: &Path, defaults: SessionConfig) -> SessionConfig {
+    let defaults = SessionConfig {
+        optimized_model_dir: defaults
+            .optimized_model_dir
+            .or_else(|| Some(app_data_dir.join("optimized-models"))),
+        ..defaults
+    };
+    load_settings(app_data_dir, "session.json", defaults)
+}
+
+/// Download manager using the options from `downloads.json` in the app data
+/// directory, reporting its progress to the frontend as `download_progress`
+/// events.
+pub fn download_manager(
+    app_handle: &tauri::AppHandle,
+    app_data_dir: &Path,
+) -> Result<DownloadManager, KokoError> {
+    let options = load_settings(app_data_dir, "downloads.json", DownloadOptions::default());
+    let app_handle = app_handle.clone();
+    Ok(
+        DownloadManager::new(options)?.with_progress(Arc::new(move |event: DownloadEvent| {
+            if let Err(e) = app_handle.emit("download_progress", &event) {
+                error!("Failed to emit download_progress event: {:?}", e);
+            }
+        })),
+    )
+}
+
+/// Model variants of the registry, where they're installed and which one is
+/// selected. Files are looked for in the bundled resources first, and the
+/// ones missing there are downloaded to `models` in the app data directory.
+pub struct ModelStore {
+    registry: ModelRegistry,
+    bundled_dir: PathBuf,
+    app_data_dir: PathBuf,
+    downloads: DownloadManager,
+}
+
+/// A registry entry as listed to the frontend.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelVariantInfo {
+    #[serde(flatten)]
+    variant: ModelVariant,
+    voices: Vec<String>,
+    installed: bool,
+    selected: bool,
+}
+
+impl ModelStore {
+    pub fn new(bundled_dir: PathBuf, app_data_dir: PathBuf, downloads: DownloadManager) -> Self {
+        Self {
+            registry: ModelRegistry::bundled(),
+            bundled_dir,
+            app_data_dir,
+            downloads,
+        }
+    }
+
+    fn models_dir(&self) -> PathBuf {
+        self.app_data_dir.join("models")
+    }
+
+    fn selection_path(&self) -> PathBuf {
+        self.models_dir().join("selected")
+    }
+
+    fn dirs(&self) -> Vec<PathBuf> {
+        vec![self.bundled_dir.clone(), self.models_dir()]
+    }
+
+    fn variant(&self, id: &str) -> Result<&ModelVariant, TauriSpeechError> {
+        self.registry.variant(id).ok_or_else(|| {
+            TauriSpeechError::InvalidRequest(format!("Unknown model variant {}", id))
+        })
+    }
+
+    /// Selected variant, the default one while nothing else is selected
+    fn selected_id(&self) -> String {
+        std::fs::read_to_string(self.selection_path())
+            .map(|id| id.trim().to_string())
+            .ok()
+            .filter(|id| self.registry.variant(id).is_some())
+            .unwrap_or_else(|| DEFAULT_MODEL_VARIANT.to_string())
+    }
+
+    fn info(&self, variant: &ModelVariant, selected: &str) -> ModelVariantInfo {
+        ModelVariantInfo {
+            variant: variant.clone(),
+            voices: self.registry.voice_set(variant).voices.clone(),
+            installed: self.is_installed(variant),
+            selected: variant.id == selected,
+        }
+    }
+
+    fn is_installed(&self, variant: &ModelVariant) -> bool {
+        self.registry
+            .installed_paths(variant, &self.dirs())
+            .is_some()
+    }
+
+    /// Model path, voices path and init config of `variant`, downloading the
+    /// files that aren't installed yet.
+    async fn load_config(
+        &self,
+        variant: &ModelVariant,
+    ) -> Result<(PathBuf, PathBuf, TTSKokoInitConfig), KokoError> {
+        let (model_path, voices_path) = self
+            .registry
+            .install(variant, &self.dirs(), &self.models_dir(), &self.downloads)
+            .await?;
+        let mut config = self.registry.init_config(variant);
+        config.session = load_session_config(&self.app_data_dir, config.session);
+        config.download = self.downloads.options().clone();
+        Ok((model_path, voices_path, config))
+    }
+
+    /// What the app starts with: the selected variant when it's installed,
+    /// otherwise the default one, downloading whatever of it the bundle
+    /// doesn't ship.
+    pub async fn startup_config(&self) -> Result<(PathBuf, PathBuf, TTSKokoInitConfig), KokoError> {
+        let selected = self.selected_id();
+        let variant = self
+            .registry
+            .variant(&selected)
+            .filter(|variant| self.is_installed(variant))
+            .or_else(|| self.registry.variant(DEFAULT_MODEL_VARIANT))
+            .ok_or_else(|| {
+                KokoError::ModelLoad(format!(
+                    "Default model variant {} missing from the registry",
+                    DEFAULT_MODEL_VARIANT
+                ))
+            })?;
+        info!("Starting with model variant {}", variant.id);
+        self.load_config(variant).await
+    }
+}
+
+/// Loads TTS for a model and the voices imported by the user.
+async fn load_tts(
+    model_path: &Path,
+    voices_path: &Path,
+    user_voices_dir: &Path,
+    config: TTSKokoInitConfig,
+) -> Result<TTSKoko, KokoError> {
+    let tts = TTSKoko::from_config(
+        &model_path.to_string_lossy(),
+        &voices_path.to_string_lossy(),
+        config,
+    )
+    .await?;
+    // Imported voices are optional, a broken archive shouldn't keep TTS from starting
+    if let Err(e) = tts.load_user_voices(user_voices_dir) {
+        error!(
+            "Failed to load imported voices from {}: {}",
+            user_voices_dir.display(),
+            e
+        );
+    }
+    Ok(tts)
+}
+
+/// Loads the selected model variant into the app state, downloading what
+/// the bundle doesn't ship. Run in the background at startup, and again when
+/// the user retries after a failure.
+pub async fn init_tts(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    if app_handle.try_state::<ModelStore>().is_none() {
+        let bundled_dir = app_handle
+            .path()
+            .resolve("resources", Resource)
+            .map_err(|e| format!("Failed to resolve bundled resources directory: {}", e))?;
+        let app_data_dir = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+        let downloads = download_manager(app_handle, &app_data_dir).map_err(|e| e.to_string())?;
+        app_handle.manage(ModelStore::new(bundled_dir, app_data_dir, downloads));
+    }
+    let store = app_handle.state::<ModelStore>();
+
+    let (model_path, voices_path, config) =
+        store.startup_config().await.map_err(|e| e.to_string())?;
+    let user_voices_dir = store.app_data_dir.join("voices");
+    let tts = load_tts(&model_path, &voices_path, &user_voices_dir, config)
+        .await
+        .map_err(|e| e.to_string())?;
+    *app_handle.state::<AppState>().tts_instance.lock().await = Some(tts);
     Ok(())
 }
 
+/// The model store, missing while TTS couldn't resolve its directories.
+fn model_store(app_handle: &tauri::AppHandle) -> Result<State<'_, ModelStore>, TauriSpeechError> {
+    app_handle.try_state::<ModelStore>().ok_or_else(|| {
+        TauriSpeechError::NotReady("Model store not initialized, see the TTS status".to_string())
+    })
+}
+
 /// Tauri command to handle TTS requests.
 ///
 /// This command will process the text-to-speech request and return the audio data
@@ -372,8 +446,11 @@ pub async fn generate_speech(
         speed: Speed(speed),
         initial_silence,
         stream: _, // This will be ignored for a direct command return
+        lang_code,
+        preparation,
         ..
     } = speech_request;
+    let input = prepare_for_speech(&input, &preparation);
 
     // For a Tauri command, we'll always behave like non-streaming,
     // as direct streaming is not a return type for commands.
@@ -392,7 +469,7 @@ pub async fn generate_speech(
         let raw_audio = tts
             .tts_raw_audio(
                 &input,
-                "en-us", // Assuming English for simplicity, could be derived from voice or added to request
+                lang_code.as_deref().unwrap_or(default_language(&voice)),
                 &voice,
                 speed,
                 initial_silence,
@@ -402,7 +479,7 @@ pub async fn generate_speech(
             )
             .map_err(|e| {
                 error!("Koko TTS error: {:?}", e);
-                TauriSpeechError::KokoError(format!("TTS generation failed: {}", e))
+                TauriSpeechError::from(e).context("TTS generation failed")
             })?;
         let sample_rate = TTSKokoInitConfig::default().sample_rate;
 
@@ -412,11 +489,11 @@ pub async fn generate_speech(
                 let header = WavHeader::new(1, sample_rate, 32);
                 header.write_header(&mut wav_data).map_err(|e| {
                     error!("WAV header error: {:?}", e);
-                    TauriSpeechError::IoError(format!("Failed to write WAV header: {}", e))
+                    TauriSpeechError::Encoding(format!("Failed to write WAV header: {}", e))
                 })?;
                 write_audio_chunk(&mut wav_data, &raw_audio).map_err(|e| {
                     error!("WAV chunk error: {:?}", e);
-                    TauriSpeechError::IoError(format!("Failed to write WAV chunk: {}", e))
+                    TauriSpeechError::Encoding(format!("Failed to write WAV chunk: {}", e))
                 })?;
 
                 (wav_data, "WAV")
@@ -424,7 +501,7 @@ pub async fn generate_speech(
             AudioFormat::Mp3 => {
                 let mp3_data = pcm_to_mp3(&raw_audio, sample_rate).map_err(|e| {
                     error!("MP3 conversion error: {:?}", e);
-                    TauriSpeechError::Mp3ConversionError(format!("Failed to convert to MP3: {}", e))
+                    TauriSpeechError::Encoding(format!("Failed to convert to MP3: {}", e))
                 })?;
                 (mp3_data, "MP3")
             }
@@ -439,7 +516,7 @@ pub async fn generate_speech(
             _ => {
                 let mp3_data = pcm_to_mp3(&raw_audio, sample_rate).map_err(|e| {
                     error!("MP3 conversion error for fallback: {:?}", e);
-                    TauriSpeechError::Mp3ConversionError(format!("Failed to convert to MP3: {}", e))
+                    TauriSpeechError::Encoding(format!("Failed to convert to MP3: {}", e))
                 })?;
                 (mp3_data, "MP3")
             }
@@ -454,20 +531,278 @@ pub async fn generate_speech(
 
         Ok(audio_data)
     } else {
-        Err(TauriSpeechError::KokoError(format!(
-            "TTS instance not initialized yet"
-        )))
+        Err(TauriSpeechError::not_ready())
     }
 }
 
+/// Tauri command listing every voice with its language, gender and source
+/// (`builtin`, `custom` or `imported`).
+#[tauri::command]
+pub async fn list_voices(app_handle: tauri::AppHandle) -> Result<Vec<VoiceInfo>, TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+    Ok(tts.list_voices())
+}
+
+/// Tauri command importing the voices of a `.npy`, `.npz` or `.bin` file.
+///
+/// `name` renames a single-voice file, which otherwise takes the file's name.
+/// Returns the names of the imported voices.
+#[tauri::command]
+pub async fn import_voice(
+    app_handle: tauri::AppHandle,
+    path: String,
+    name: Option<String>,
+) -> Result<Vec<String>, TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+
+    tts.import_voices(&path, name.as_deref()).map_err(|e| {
+        error!("Voice import error: {:?}", e);
+        TauriSpeechError::from(e).context(format!("Failed to import voices from {}", path))
+    })
+}
+
+/// Tauri command removing a custom or imported voice.
+#[tauri::command]
+pub async fn remove_voice(
+    app_handle: tauri::AppHandle,
+    name: String,
+) -> Result<(), TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+
+    tts.remove_voice(&name).map_err(|e| {
+        error!("Voice removal error: {:?}", e);
+        TauriSpeechError::from(e).context(format!("Failed to remove voice '{}'", name))
+    })
+}
+
+/// Sentence voice design candidates are previewed with when none is given
+const DEFAULT_PREVIEW_TEXT: &str = "Hello! This is how I sound with these settings.";
+
 #[derive(Serialize)]
-pub struct ModelObject {
+pub struct StyleAxes {
+    axes: Vec<StyleAxis>,
+    /// Where the requested base voice sits on each axis, empty without a base
+    coordinates: HashMap<String, f32>,
+}
+
+/// Tauri command returning the named style axes the voice designer offers as sliders,
+/// and optionally the position of a base voice on them.
+#[tauri::command]
+pub async fn get_style_axes(
+    app_handle: tauri::AppHandle,
+    base: Option<String>,
+) -> Result<StyleAxes, TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+
+    let result = (|| -> Result<StyleAxes, KokoError> {
+        let space = tts.style_space()?;
+        let coordinates = match &base {
+            Some(base) => space.coordinates(&tts.blend_voices(base, false)?),
+            None => HashMap::new(),
+        };
+        Ok(StyleAxes {
+            axes: space.axes,
+            coordinates,
+        })
+    })();
+    result.map_err(|e| TauriSpeechError::from(e).context("Style analysis failed"))
+}
+
+/// Tauri command synthesizing a short MP3 preview of a voice design candidate:
+/// `base` (a voice or blend spec) moved along the style axes by `offsets`.
+#[tauri::command]
+pub async fn preview_voice_design(
+    app_handle: tauri::AppHandle,
+    base: String,
+    offsets: HashMap<String, f32>,
+    text: Option<String>,
+) -> Result<Vec<u8>, TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+
+    let text = text.unwrap_or_else(|| DEFAULT_PREVIEW_TEXT.to_string());
+    let raw_audio = tts
+        .design_voice(&base, &offsets)
+        .and_then(|pack| tts.preview_voice(&text, "en-us", &pack, 1.0))
+        .map_err(|e| {
+            error!("Voice design preview error: {:?}", e);
+            TauriSpeechError::from(e).context("Voice design preview failed")
+        })?;
+
+    pcm_to_mp3(&raw_audio, TTSKokoInitConfig::default().sample_rate).map_err(|e| {
+        error!("MP3 conversion error: {:?}", e);
+        TauriSpeechError::Encoding(format!("Failed to convert to MP3: {}", e))
+    })
+}
+
+/// Tauri command saving a voice design candidate as a custom voice called `name`.
+#[tauri::command]
+pub async fn save_voice_design(
+    app_handle: tauri::AppHandle,
+    name: String,
+    base: String,
+    offsets: HashMap<String, f32>,
+) -> Result<(), TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+
+    tts.design_voice(&base, &offsets)
+        .and_then(|pack| tts.save_custom_voice(&name, pack))
+        .map_err(|e| {
+            error!("Voice design save error: {:?}", e);
+            TauriSpeechError::from(e).context(format!("Failed to save voice '{}'", name))
+        })
+}
+
+/// Tauri command returning throughput and cache counters of the shared phonemizer.
+#[tauri::command]
+pub fn get_phonemizer_metrics() -> PhonemizerMetrics {
+    PhonemizerService::global().metrics()
+}
+
+/// Tauri command switching the G2P backend (`espeak` or `lexicon`) used for synthesis.
+#[tauri::command]
+pub async fn set_g2p_backend(
+    app_handle: tauri::AppHandle,
+    backend: String,
+) -> Result<(), TauriSpeechError> {
+    let kind: G2pKind = backend.parse().map_err(TauriSpeechError::InvalidRequest)?;
+
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+    tts.set_g2p(kind);
+    Ok(())
+}
+
+/// Tauri command choosing whether text detected as another language is spoken by a
+/// voice for that language or by the requested voice.
+#[tauri::command]
+pub async fn set_language_switching(
+    app_handle: tauri::AppHandle,
+    switch_voices: bool,
+) -> Result<(), TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+    tts.set_switch_voices(switch_voices);
+    Ok(())
+}
+
+/// Language a request without a `lang_code` is spoken in: the voice's own.
+/// Language detection ([`kokoros::tts::langid::AUTO`]) only runs when a request
+/// asks for it, so a misdetected word can't change a reply's language.
+pub(crate) fn default_language(voice: &str) -> &'static str {
+    voice_language(voice).unwrap_or("en-us")
+}
+
+/// Tauri command showing how `input` would be spoken without synthesizing it:
+/// chunks, phonemes, token ids, dropped symbols, style rows and estimated length.
+/// `input` goes through the same speech preparation as in `generate_speech`.
+#[tauri::command]
+pub async fn inspect_speech(
+    app_handle: tauri::AppHandle,
+    input: String,
+    voice: String,
+    speed: Option<f32>,
+    lang_code: Option<String>,
+    preparation: Option<PrepareOptions>,
+) -> Result<SpeechInspection, TauriSpeechError> {
+    let app_state = app_handle.state::<AppState>();
+    let tts_guard = app_state.tts_instance.lock().await;
+    let tts = tts_guard.as_ref().ok_or_else(TauriSpeechError::not_ready)?;
+    let input = prepare_for_speech(&input, &preparation.unwrap_or_default());
+    Ok(tts.inspect_speech(
+        &input,
+        lang_code.as_deref().unwrap_or(default_language(&voice)),
+        &voice,
+        speed.unwrap_or(1.0),
+        None,
+    )?)
+}
+
+/// Tauri command listing the model variants of the registry.
+#[tauri::command]
+pub fn list_model_variants(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ModelVariantInfo>, TauriSpeechError> {
+    let store = model_store(&app_handle)?;
+    let selected = store.selected_id();
+    Ok(store
+        .registry
+        .variants
+        .iter()
+        .map(|variant| store.info(variant, &selected))
+        .collect())
+}
+
+/// Tauri command downloading a model variant and verifying it against the
+/// registry's checksums.
+#[tauri::command]
+pub async fn install_model_variant(
+    app_handle: tauri::AppHandle,
     id: String,
-    object: String,
-    created: u64,
-    owned_by: String,
+) -> Result<ModelVariantInfo, TauriSpeechError> {
+    let store = model_store(&app_handle)?;
+    let variant = store.variant(&id)?;
+    if !store.is_installed(variant) {
+        let (model_path, _) = store
+            .registry
+            .install(
+                variant,
+                &store.dirs(),
+                &store.models_dir(),
+                &store.downloads,
+            )
+            .await
+            .map_err(|e| {
+                error!("Installing model variant {} failed: {}", id, e);
+                TauriSpeechError::from(e)
+            })?;
+        info!("Installed model variant {} to {}", id, model_path.display());
+    }
+    Ok(store.info(variant, &store.selected_id()))
 }
 
-// Removed: handle_home, handle_model, request_id_middleware
-// These are specific to HTTP server routing and middleware.
-// The `handle_model` logic could be integrated into `list_models` if individual model lookup is needed.
+/// Tauri command switching to an installed model variant. The variant is loaded
+/// before the selection is saved, so a variant that fails to load isn't kept.
+#[tauri::command]
+pub async fn select_model_variant(
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<(), TauriSpeechError> {
+    let store = model_store(&app_handle)?;
+    let variant = store.variant(&id)?;
+    if !store.is_installed(variant) {
+        return Err(TauriSpeechError::InvalidRequest(format!(
+            "Model variant {} isn't installed",
+            id
+        )));
+    }
+
+    let (model_path, voices_path, config) = store.load_config(variant).await?;
+    let user_voices_dir = store.app_data_dir.join("voices");
+    let tts = load_tts(&model_path, &voices_path, &user_voices_dir, config).await?;
+
+    let app_state = app_handle.state::<AppState>();
+    *app_state.tts_instance.lock().await = Some(tts);
+
+    std::fs::create_dir_all(store.models_dir())?;
+    std::fs::write(store.selection_path(), &id)?;
+    info!("Selected model variant {}", id);
+    app_handle
+        .state::<StartupStatus>()
+        .set(&app_handle, Subsystem::Tts, SubsystemState::Ready);
+    cwarmup::start(app_handle.clone(), Subsystem::Tts);
+    Ok(())
+}
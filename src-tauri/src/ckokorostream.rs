@@ -1,253 +1,30 @@
 use std::collections::BTreeMap;
-use std::error::Error;
-use std::io;
 use std::sync::Arc;
 use std::time::Instant;
 
 // Tauri specific imports
-use tauri::{ipc::Response, AppHandle, Emitter, Manager, State}; // Add Manager for event emission if needed for streaming
+use tauri::{AppHandle, Emitter, Manager};
 
 use kokoros::{
-    tts::koko::{InitConfig as TTSKokoInitConfig, TTSKoko},
-    utils::mp3::pcm_to_mp3,
-    utils::wav::{write_audio_chunk, WavHeader},
+    tts::chunking::ChunkingPolicy,
+    tts::koko::TTSKoko,
+    tts::phonemizer_service::PhonemizerService,
+    tts::prepare::{prepare_for_speech, PrepareOptions},
+    tts::realtime::{self, DEFAULT_FIRST_AUDIO_MS},
+    tts::viseme::VisemeKeyframe,
 };
-use regex::Regex;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tokio::sync::mpsc;
-use tracing::{error, info}; // Still good for Rust-side logging
-use uuid::Uuid;
+use tracing::{debug, error, info}; // Still good for Rust-side logging
 
 use base64::{engine::general_purpose, Engine as _};
 
-/// Break words used for chunk splitting
-const BREAK_WORDS: &[&str] = &[
-    "and", "or", "but", "&", "because", "if", "since", "though", "although", "however", "which",
-];
-
-#[derive(Debug)]
-enum SpeechError {
-    // Deciding to modify this example in order to see errors
-    // (e.g. with tracing) is up to the developer
-    #[allow(dead_code)]
-    Koko(Box<dyn Error>),
-
-    #[allow(dead_code)]
-    Header(io::Error),
-
-    #[allow(dead_code)]
-    Chunk(io::Error),
-
-    #[allow(dead_code)]
-    Mp3Conversion(std::io::Error),
-}
-
-/// Split text into speech chunks for streaming (utility function, not directly a command)
-/// Prioritizes sentence boundaries over word count for natural speech breaks
-/// Then applies center-break word splitting for long chunks
-fn split_text_into_speech_chunks(text: &str, words_per_chunk: usize) -> Vec<String> {
-    let mut chunks = Vec::new();
-    let mut current_chunk = String::new();
-    let mut word_count = 0;
-
-    // First pass: split by punctuation
-    for word in text.split_whitespace() {
-        if !current_chunk.is_empty() {
-            current_chunk.push(' ');
-        }
-        // Check for numbered list patterns: 1. 2) 3: (4), 5(\s)[.\)\:]
-        let is_numbered_break = is_numbered_list_item(word);
-
-        if is_numbered_break && !current_chunk.is_empty() {
-            chunks.push(current_chunk.trim().to_string());
-            current_chunk.clear();
-            word_count = 0;
-        }
-        current_chunk.push_str(word);
-        word_count += 1;
-
-        // Check for unconditional breaks (always break regardless of word count)
-        let ends_with_unconditional = word.ends_with('.')
-            || word.ends_with('!')
-            || word.ends_with('?')
-            || word.ends_with(':')
-            || word.ends_with(';');
-
-        // Check for conditional breaks (commas - only break if enough words)
-        let ends_with_conditional = word.ends_with(',');
-
-        // Split conditions:
-        // 1. Unconditional punctuation - always break
-        // 2. Conditional punctuation + target word count reached
-        if ends_with_unconditional
-            || is_numbered_break
-            || (ends_with_conditional && word_count >= words_per_chunk)
-        {
-            chunks.push(current_chunk.trim().to_string());
-            current_chunk.clear();
-            word_count = 0;
-        }
-    }
-
-    if !current_chunk.trim().is_empty() {
-        chunks.push(current_chunk.trim().to_string());
-    }
-
-    // Second pass: apply center-break splitting for long chunks
-    // All chunks: ≥12 words
-    // First 2 chunks: punctuation priority, Others: break words only
-    let mut final_chunks = Vec::new();
-    for (index, chunk) in chunks.iter().enumerate() {
-        let threshold = 12;
-        let use_punctuation = index < 2; // First 2 chunks can use punctuation
-        let split_chunks = split_long_chunk_with_depth(chunk, threshold, use_punctuation, 0);
-        final_chunks.extend(split_chunks);
-    }
-
-    // Final processing: Move break words from end of chunks to beginning of next chunk
-    for i in 0..final_chunks.len() - 1 {
-        let current_chunk = &final_chunks[i];
-        let words: Vec<&str> = current_chunk.trim().split_whitespace().collect();
-
-        if let Some(last_word) = words.last() {
-            // Check if last word is a break word (case insensitive)
-            if BREAK_WORDS.contains(&last_word.to_lowercase().as_str()) && words.len() > 1 {
-                // Only move if it won't create an empty chunk (need more than 1 word)
-                let new_current = words[..words.len() - 1].join(" ");
-
-                // Add break word to beginning of next chunk
-                let next_chunk = &final_chunks[i + 1];
-                let new_next = format!("{} {}", last_word, next_chunk);
-
-                // Update the chunks
-                final_chunks[i] = new_current;
-                final_chunks[i + 1] = new_next;
-            }
-        }
-    }
-
-    final_chunks
-}
-
-/// Check if a word is a numbered list item: 1. 2) 3: (4), 5(\s)[.\)\:]
-fn is_numbered_list_item(word: &str) -> bool {
-    let numbered_regex = Regex::new(r"^\(?[0-9]+[.\)\:],?$").unwrap();
-    numbered_regex.is_match(word)
-}
-
-fn split_long_chunk_with_depth(
-    chunk: &str,
-    threshold: usize,
-    use_punctuation: bool,
-    depth: usize,
-) -> Vec<String> {
-    // Prevent infinite recursion
-    if depth >= 3 {
-        return vec![chunk.to_string()];
-    }
-    let words: Vec<&str> = chunk.split_whitespace().collect();
-    let word_count = words.len();
-
-    // Only split if chunk meets the threshold
-    if word_count < threshold {
-        return vec![chunk.to_string()];
-    }
-
-    let center = word_count / 2;
-
-    if use_punctuation {
-        // Priority 1: Search for commas closest to center
-        if let Some(pos) = find_closest_punctuation(&words, center, &[","]) {
-            if pos >= 3 && pos < words.len() {
-                let first_chunk = words[..pos].join(" ");
-                let second_chunk = words[pos..].join(" ");
-
-                // Recursively split both chunks if they're still too long
-                let mut result = Vec::new();
-                result.extend(split_long_chunk_with_depth(
-                    &first_chunk,
-                    threshold,
-                    use_punctuation,
-                    depth + 1,
-                ));
-                result.extend(split_long_chunk_with_depth(
-                    &second_chunk,
-                    threshold,
-                    use_punctuation,
-                    depth + 1,
-                ));
-                return result;
-            }
-        }
-    }
-
-    // Priority 2: Search for break words closest to center
-    if let Some(pos) = find_closest_break_word(&words, center, BREAK_WORDS) {
-        if pos >= 3 && pos < words.len() {
-            let first_chunk = words[..pos].join(" ");
-            let second_chunk = words[pos..].join(" ");
-
-            // Recursively split both chunks if they're still too long
-            let mut result = Vec::new();
-            result.extend(split_long_chunk_with_depth(
-                &first_chunk,
-                threshold,
-                use_punctuation,
-                depth + 1,
-            ));
-            result.extend(split_long_chunk_with_depth(
-                &second_chunk,
-                threshold,
-                use_punctuation,
-                depth + 1,
-            ));
-            return result;
-        }
-    }
-
-    // No suitable break point found, return original chunk
-    vec![chunk.to_string()]
-}
-
-/// Find closest punctuation to center
-fn find_closest_punctuation(words: &[&str], center: usize, punctuation: &[&str]) -> Option<usize> {
-    let mut closest_pos = None;
-    let mut min_distance = usize::MAX;
-
-    for (i, word) in words.iter().enumerate() {
-        if punctuation.iter().any(|p| word.ends_with(p)) {
-            let distance = if i < center { center - i } else { i - center };
-            if distance < min_distance {
-                min_distance = distance;
-                closest_pos = Some(i + 1); // Split after the punctuation
-            }
-        }
-    }
-
-    closest_pos
-}
-
-/// Find closest break word to center
-fn find_closest_break_word(words: &[&str], center: usize, break_words: &[&str]) -> Option<usize> {
-    let mut closest_pos = None;
-    let mut min_distance = usize::MAX;
-
-    for (i, word) in words.iter().enumerate() {
-        if break_words.contains(&word.to_lowercase().as_str()) {
-            let distance = if i < center { center - i } else { i - center };
-            if distance < min_distance {
-                min_distance = distance;
-                closest_pos = Some(i); // Break word becomes first word of second chunk
-            }
-        }
-    }
-
-    closest_pos
-}
+use crate::ckokoros2::{default_language, TauriSpeechError};
+use crate::AppState;
 
 #[derive(Deserialize, Default, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
-enum AudioFormat {
+pub enum AudioFormat {
     #[default]
     Mp3,
     Wav,
@@ -257,100 +34,17 @@ enum AudioFormat {
     Pcm,
 }
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct Voice(String);
-
-impl Default for Voice {
-    fn default() -> Self {
-        Self("af_heart".into())
-    }
-}
-
-#[derive(Deserialize, Debug, Clone, Copy)]
-pub struct Speed(f32);
-
-impl Default for Speed {
-    fn default() -> Self {
-        Self(1.)
-    }
-}
-
-#[derive(Deserialize, Debug)]
-// This struct will be directly deserialized from the JavaScript payload
-pub struct SpeechRequest {
-    // Only one Kokoro model exists
-    #[allow(dead_code)]
-    model: String,
-
-    input: String,
-
-    #[serde(default)]
-    voice: Voice,
-
-    #[serde(default)]
-    response_format: AudioFormat,
-
-    #[serde(default)]
-    speed: Speed,
-
-    #[serde(default)]
-    initial_silence: Option<usize>,
-
-    /// Enable streaming audio generation (not directly supported by simple commands, handled differently)
-    #[serde(default)]
-    stream: Option<bool>,
-
-    // OpenAI API compatibility parameters - accepted but not implemented
-    // These fields ensure request parsing compatibility with OpenAI clients
-    /// Return download link after generation (not implemented)
-    #[serde(default)]
-    #[allow(dead_code)]
-    return_download_link: Option<bool>,
-
-    /// Language code for text processing (not implemented)
-    #[serde(default)]
-    #[allow(dead_code)]
-    lang_code: Option<String>,
-
-    /// Volume multiplier for output audio (not implemented)
-    #[serde(default)]
-    #[allow(dead_code)]
-    volume_multiplier: Option<f32>,
-
-    /// Format for download when different from response_format (not implemented)
-    #[serde(default)]
-    #[allow(dead_code)]
-    download_format: Option<String>,
-
-    /// Text normalization options (not implemented)
-    #[serde(default)]
-    #[allow(dead_code)]
-    normalization_options: Option<serde_json::Value>,
-}
-
-// Custom error struct for Tauri commands, must be Serialize
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub enum TauriSpeechError {
-    TTSGenerationError(String),
-    EventEmissionError(String),
-    InvalidInput(String),
-    InternalError(String),
-}
+/// Index, PCM bytes and viseme keyframes of a synthesized chunk
+type ChunkResult = Result<(usize, Vec<u8>, Vec<VisemeKeyframe>), String>;
 
 #[derive(Debug)]
 struct TTSTask {
     id: usize,
     chunk: String,
     voice: String,
+    lang: String,
     speed: f32,
     initial_silence: Option<usize>,
-    result_tx: mpsc::UnboundedSender<(usize, Vec<u8>)>,
-}
-
-#[derive(Debug)]
-struct StreamingSession {
-    session_id: Uuid,
-    start_time: Instant,
 }
 
 #[derive(Clone)]
@@ -375,39 +69,17 @@ impl TTSWorkerPool {
         self.tts_instances.len()
     }
 
-    // process_chunk method removed - now handled inline in sequential queue processing
-}
-
-impl From<std::io::Error> for TauriSpeechError {
-    fn from(err: std::io::Error) -> Self {
-        TauriSpeechError::InternalError(format!("IO Error: {}", err))
-    }
-}
-
-impl From<tokio::sync::mpsc::error::SendError<TTSTask>> for TauriSpeechError {
-    fn from(err: tokio::sync::mpsc::error::SendError<TTSTask>) -> Self {
-        TauriSpeechError::InternalError(format!("Task channel send error: {}", err))
-    }
-}
-
-impl From<serde_json::Error> for TauriSpeechError {
-    fn from(err: serde_json::Error) -> Self {
-        TauriSpeechError::InternalError(format!("JSON serialization error: {}", err))
-    }
-}
-
-impl From<Box<dyn std::error::Error + Send + Sync>> for TauriSpeechError {
-    fn from(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
-        TauriSpeechError::InternalError(format!("Generic error: {}", err))
+    /// Average speed of the instances measured so far, ms of compute per second of audio
+    fn ms_per_audio_sec(&self) -> Option<f64> {
+        let measured: Vec<f64> = self
+            .tts_instances
+            .iter()
+            .filter_map(|tts| tts.real_time_factor().ms_per_audio_sec())
+            .collect();
+        (!measured.is_empty()).then(|| measured.iter().sum::<f64>() / measured.len() as f64)
     }
-}
 
-// We will store our TTSKoko instance in Tauri's managed state.
-// This allows it to be accessible to commands without recreating it.
-pub struct AppState {
-    pub worker_pool: Arc<TTSWorkerPool>,
-    // For multiple instances, you could have:
-    // pub tts_instances: Arc<Vec<TTSKoko>>,
+    // process_chunk method removed - now handled inline in sequential queue processing
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -418,54 +90,110 @@ pub struct SpeechRequestStream {
     pub speed: f32,
     pub initial_silence: Option<usize>,
     pub request_id: String, // Keep request_id as part of the request
+    /// espeak language code, the voice's language when unset; `auto` detects it
+    /// per span
+    #[serde(default)]
+    pub lang_code: Option<String>,
+    /// How markdown, code blocks, URLs and emoji are turned into speech
+    #[serde(default)]
+    pub preparation: PrepareOptions,
+    /// How the text is split into streamed chunks, before its sizes are adapted
+    /// to the measured synthesis speed
+    #[serde(default)]
+    pub chunking: ChunkingPolicy,
+    /// Time-to-first-audio the first chunk is sized for, in milliseconds
+    #[serde(default)]
+    pub target_first_audio_ms: Option<f64>,
 }
 
 fn get_colored_request_id_with_relative(request_id: &str, start_time: Instant) -> String {
     format!("{}[{}ms]", request_id, start_time.elapsed().as_millis())
 }
 
-// This function will be called during Tauri's setup
-pub async fn setup_tauri_commands(
-    app: &mut tauri::App,
-    model_path: &str,
-    voices_path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let tts = TTSKoko::new(model_path, voices_path).await;
-    app.manage(AppState {
-        worker_pool: Arc::new(TTSWorkerPool::new(vec![tts])),
+/// Chunks of a stream and the payload of its `audio_stream_start` event.
+struct StreamPlan {
+    chunks: Vec<String>,
+    start: serde_json::Value,
+}
+
+/// Splits the prepared `input` of `request` into chunks sized for the speed the
+/// instances have shown so far, see [`realtime::size_chunks`].
+fn plan_stream(
+    request: &SpeechRequestStream,
+    input: &str,
+    ms_per_audio_sec: Option<f64>,
+    instances: usize,
+) -> StreamPlan {
+    // For Tauri streaming, we primarily send PCM data. Mime type for frontend.
+    let (mime_type, sample_rate) = ("audio/pcm;codecs='pcm_s16le'", 24000); // Assuming 24kHz 16-bit PCM for streaming
+
+    let (chunking, chunk_sizing) = realtime::size_chunks(
+        &request.chunking,
+        ms_per_audio_sec,
+        request
+            .target_first_audio_ms
+            .unwrap_or(DEFAULT_FIRST_AUDIO_MS),
+        request.speed,
+        instances,
+    );
+    let chunks = chunking.split(input);
+    let chunk_words: Vec<usize> = chunks
+        .iter()
+        .map(|chunk| chunk.split_whitespace().count())
+        .collect();
+    let start = serde_json::json!({
+        "requestId": request.request_id,
+        "format": format!("{:?}", request.response_format).to_lowercase(),
+        "mimeType": mime_type,
+        "sampleRate": sample_rate,
+        "totalChunks": chunks.len(),
+        "chunkSizing": chunk_sizing, // Sizes chosen from the measured speed
+        "chunkWords": chunk_words,
     });
-    Ok(())
+    StreamPlan { chunks, start }
 }
 
-/// Tauri command to handle TTS requests.
+/// Tauri command streaming speech as events.
 ///
-/// This command will process the text-to-speech request and return the audio data
-/// as a `Vec<u8>`. Streaming is not directly supported via a single command return,
-/// so this will behave like the non-streaming `handle_tts` from the original.
+/// Returns once the chunks are queued. The audio follows as base64 PCM in
+/// `audio_stream_chunk` events, each with the viseme keyframes of its chunk,
+/// between an `audio_stream_start` event with the stream's metadata and an
+/// `audio_stream_end` event.
 #[tauri::command]
 pub async fn start_speech_stream(
-    app_state: State<'_, AppState>,
     app_handle: AppHandle, // Tauri's AppHandle to emit events
     request: SpeechRequestStream,
 ) -> Result<(), TauriSpeechError> {
-    let tts_worker_pool = app_state.worker_pool.clone();
-
-    let input = request.input;
+    let tts = app_handle
+        .state::<AppState>()
+        .tts_instance
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(TauriSpeechError::not_ready)?;
+    // Clones share the model and its measured speed
+    let tts_worker_pool = Arc::new(TTSWorkerPool::new(vec![tts]));
+
+    let input = prepare_for_speech(&request.input, &request.preparation);
+    let StreamPlan { chunks, start } = plan_stream(
+        &request,
+        &input,
+        tts_worker_pool.ms_per_audio_sec(),
+        tts_worker_pool.instance_count(),
+    );
     let voice = request.voice;
-    let response_format = request.response_format;
     let speed = request.speed;
     let initial_silence = request.initial_silence;
+    let lang = request
+        .lang_code
+        .unwrap_or_else(|| default_language(&voice).to_string());
     let request_id = request.request_id;
     let request_start = Instant::now(); // Record start time for this specific command invocation
 
-    // For Tauri streaming, we primarily send PCM data. Mime type for frontend.
-    let (mime_type, sample_rate) = ("audio/pcm;codecs='pcm_s16le'", 24000); // Assuming 24kHz 16-bit PCM for streaming
-
-    let mut chunks = split_text_into_speech_chunks(&input, 10);
     let total_chunks = chunks.len();
 
     let colored_request_id = get_colored_request_id_with_relative(&request_id, request_start);
-    dbg!(
+    debug!(
         "{} Processing {} chunks for streaming with window size {}",
         colored_request_id,
         total_chunks,
@@ -485,18 +213,9 @@ pub async fn start_speech_stream(
     }
 
     // Emit initial stream start event to frontend
-    if let Err(e) = app_handle.emit(
-        "audio_stream_start",
-        serde_json::json!({
-            "requestId": request_id,
-            "format": format!("{:?}", response_format).to_lowercase(),
-            "mimeType": mime_type,
-            "sampleRate": sample_rate,
-            "totalChunks": total_chunks,
-        }),
-    ) {
+    if let Err(e) = app_handle.emit("audio_stream_start", start) {
         error!("Failed to emit audio_stream_start event: {:?}", e);
-        return Err(TauriSpeechError::EventEmissionError(format!(
+        return Err(TauriSpeechError::Internal(format!(
             "Failed to emit stream start event: {}",
             e
         )));
@@ -518,14 +237,14 @@ pub async fn start_speech_stream(
             id,
             chunk,
             voice: voice.clone(),
+            lang: lang.clone(),
             speed,
             initial_silence: if id == 0 { initial_silence } else { None },
-            result_tx: mpsc::unbounded_channel().0, // Dummy sender for TTSTask, not used for Tauri events
         };
         // It's crucial to handle send errors for the channel here if it gets disconnected
         if let Err(e) = task_tx.send(task) {
             error!("Failed to send TTS task: {:?}", e);
-            return Err(TauriSpeechError::InternalError(format!(
+            return Err(TauriSpeechError::Internal(format!(
                 "Failed to queue task: {}",
                 e
             )));
@@ -542,10 +261,8 @@ pub async fn start_speech_stream(
 
     tokio::spawn(async move {
         let mut chunk_counter = 0;
-        let mut pending_chunks: BTreeMap<
-            usize,
-            tokio::task::JoinHandle<Result<(usize, Vec<u8>), String>>,
-        > = BTreeMap::new();
+        let mut pending_chunks: BTreeMap<usize, tokio::task::JoinHandle<ChunkResult>> =
+            BTreeMap::new();
         let mut next_to_send = 0;
         let mut chunks_processed = 0;
         let window_size = tts_worker_pool_clone.instance_count();
@@ -564,20 +281,21 @@ pub async fn start_speech_stream(
                             worker_pool_inner_clone.get_instance(chunk_counter);
                         let chunk_text = task.chunk.clone();
                         let voice = task.voice.clone();
+                        let lang = task.lang.clone();
                         let speed = task.speed;
                         let initial_silence = task.initial_silence;
                         let chunk_num = chunk_counter;
 
                         let handle = tokio::spawn(async move {
                             if chunk_text.trim().is_empty() {
-                                return Ok((task_id, Vec::new()));
+                                return Ok((task_id, Vec::new(), Vec::new()));
                             }
 
                             let result = tokio::task::spawn_blocking(move || {
                                 tts_instance
-                                    .tts_raw_audio(
+                                    .tts_raw_audio_with_timings(
                                         &chunk_text,
-                                        "en-us", // Hardcoded for now
+                                        &lang,
                                         &voice,
                                         speed,
                                         initial_silence,
@@ -585,13 +303,16 @@ pub async fn start_speech_stream(
                                         Some(&actual_instance_id),
                                         Some(chunk_num),
                                     )
-                                    .map(|audio| audio)
                                     .map_err(|e| format!("TTS processing error: {:?}", e))
                             })
                             .await;
 
                             match result {
-                                Ok(Ok(audio_samples)) => {
+                                Ok(Ok(timed_audio)) => {
+                                    // Keyframe times are relative to the start of this chunk
+                                    let visemes = timed_audio.visemes();
+                                    let audio_samples = timed_audio.audio;
+
                                     // Convert f32 samples to i16 PCM bytes
                                     let mut pcm_data = Vec::with_capacity(audio_samples.len() * 2);
                                     for sample in audio_samples {
@@ -603,7 +324,7 @@ pub async fn start_speech_stream(
                                         pcm_data.len(),
                                         std::sync::atomic::Ordering::Relaxed,
                                     );
-                                    Ok((task_id, pcm_data))
+                                    Ok((task_id, pcm_data, visemes))
                                 }
                                 Ok(Err(e)) => Err(e),
                                 Err(e) => Err(format!("Task execution join error: {:?}", e)),
@@ -628,7 +349,7 @@ pub async fn start_speech_stream(
             while let Some(handle) = pending_chunks.remove(&next_to_send) {
                 if handle.is_finished() {
                     match handle.await {
-                        Ok(Ok((task_id, pcm_data))) => {
+                        Ok(Ok((task_id, pcm_data, visemes))) => {
                             let base64_chunk = general_purpose::STANDARD.encode(&pcm_data); // Base64 encode
                             if let Err(e) = app_handle_clone_worker.emit(
                                 // Emit to frontend
@@ -637,6 +358,7 @@ pub async fn start_speech_stream(
                                     "requestId": request_id_clone_worker,
                                     "index": task_id,
                                     "chunk": base64_chunk, // Send base64 encoded chunk
+                                    "visemes": visemes, // Lip sync keyframes, seconds from chunk start
                                 }),
                             ) {
                                 error!(
@@ -692,6 +414,15 @@ pub async fn start_speech_stream(
             bytes_transferred,
             duration_seconds
         );
+        let metrics = PhonemizerService::global().metrics();
+        info!(
+            "{} Phonemizer: {:.0} texts/s, {:.0}% busy, {} cache hits / {} misses",
+            colored_request_id_final,
+            metrics.texts_per_sec,
+            metrics.utilization * 100.0,
+            metrics.cache_hits,
+            metrics.cache_misses
+        );
 
         // Emit stream end event
         if let Err(e) = app_handle_clone_worker.emit(
@@ -705,14 +436,55 @@ pub async fn start_speech_stream(
     Ok(())
 }
 
-#[derive(Serialize)]
-pub struct ModelObject {
-    id: String,
-    object: String,
-    created: u64,
-    owned_by: String,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(input: &str) -> SpeechRequestStream {
+        serde_json::from_value(serde_json::json!({
+            "input": input,
+            "voice": "af_heart",
+            "response_format": "pcm",
+            "speed": 1.0,
+            "initial_silence": null,
+            "request_id": "req",
+        }))
+        .unwrap()
+    }
 
-// Removed: handle_home, handle_model, request_id_middleware
-// These are specific to HTTP server routing and middleware.
-// The `handle_model` logic could be integrated into `list_models` if individual model lookup is needed.
+    #[test]
+    fn test_plan_stream_reports_sizing() {
+        let input = "word ".repeat(200);
+        let request = request(&input);
+
+        // Nothing measured yet, the policy's own sizes
+        let plan = plan_stream(&request, &input, None, 1);
+        let sizing = &plan.start["chunkSizing"];
+        assert_eq!(
+            sizing["firstChunkWords"],
+            request.chunking.first_chunk_words
+        );
+        assert!(sizing["msPerAudioSec"].is_null());
+
+        // Fast synthesis, sizes derived from the measured speed
+        let (_, expected) = realtime::size_chunks(
+            &request.chunking,
+            Some(100.0),
+            DEFAULT_FIRST_AUDIO_MS,
+            1.0,
+            2,
+        );
+        let plan = plan_stream(&request, &input, Some(100.0), 2);
+        let sizing = &plan.start["chunkSizing"];
+        assert_eq!(sizing["firstChunkWords"], expected.first_chunk_words);
+        assert_eq!(sizing["laterChunkWords"], expected.later_chunk_words);
+        assert_eq!(sizing["msPerAudioSec"], 100.0);
+
+        let chunk_words = plan.start["chunkWords"].as_array().unwrap();
+        assert_eq!(plan.start["totalChunks"], plan.chunks.len());
+        assert_eq!(chunk_words.len(), plan.chunks.len());
+        assert!(chunk_words[0].as_u64().unwrap() <= expected.first_chunk_words as u64 + 1);
+        let total: u64 = chunk_words.iter().map(|w| w.as_u64().unwrap()).sum();
+        assert_eq!(total, 200);
+    }
+}
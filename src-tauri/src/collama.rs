@@ -1,6 +1,7 @@
 use ollama_rs::generation::chat::request::ChatMessageRequest;
 use ollama_rs::generation::chat::ChatMessage;
-use tauri::State;
+use ollama_rs::generation::completion::request::GenerationRequest;
+use tauri::{AppHandle, Manager, State};
 
 use crate::AppState;
 const MODEL: &str = "hf.co/mradermacher/Celeste-12B-V1.6-GGUF:Q4_K_M";
@@ -26,3 +27,37 @@ pub async fn gen_res(prompt: &str, state: State<'_, AppState>) -> Result<String,
 
     Ok(assistant_message.to_string())
 }
+
+/// Checks that the Ollama server is reachable and has the chat model pulled.
+pub async fn check_model(app_handle: &AppHandle) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let models = state
+        .ollama
+        .lock()
+        .await
+        .list_local_models()
+        .await
+        .map_err(|e| format!("Ollama isn't reachable: {}", e))?;
+    if models.iter().any(|model| model.name == MODEL) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Ollama doesn't have {}, pull it with `ollama pull {}`",
+            MODEL, MODEL
+        ))
+    }
+}
+
+/// Has Ollama load the chat model into memory with an empty prompt, so the
+/// first chat doesn't wait for it.
+pub async fn preload_model(app_handle: &AppHandle) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    state
+        .ollama
+        .lock()
+        .await
+        .generate(GenerationRequest::new(MODEL.to_string(), String::new()))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
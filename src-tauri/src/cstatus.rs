@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::{error, info};
+
+use crate::{ckokoros2, collama, cwarmup};
+
+/// Parts of the app that are started in the background, so the window shows
+/// up even when one of them can't start.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Subsystem {
+    Tts,
+    Llm,
+}
+
+impl Subsystem {
+    const ALL: [Subsystem; 2] = [Subsystem::Tts, Subsystem::Llm];
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum SubsystemState {
+    Loading,
+    Ready,
+    Failed { reason: String },
+}
+
+/// Payload of the `subsystem_status` event and entry of `get_subsystem_status`.
+#[derive(Serialize, Clone, Debug)]
+pub struct SubsystemStatus {
+    subsystem: Subsystem,
+    #[serde(flatten)]
+    state: SubsystemState,
+}
+
+/// State of every subsystem, kept in Tauri's managed state.
+#[derive(Default)]
+pub struct StartupStatus {
+    states: Mutex<HashMap<Subsystem, SubsystemState>>,
+}
+
+impl StartupStatus {
+    fn states(&self) -> MutexGuard<'_, HashMap<Subsystem, SubsystemState>> {
+        // The map stays consistent even if a holder panicked
+        self.states
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records the state of `subsystem` and tells the frontend about it.
+    pub fn set(&self, app_handle: &AppHandle, subsystem: Subsystem, state: SubsystemState) {
+        self.states().insert(subsystem, state.clone());
+        emit(app_handle, subsystem, state);
+    }
+
+    /// Marks `subsystem` as loading, false when it already is.
+    fn begin(&self, app_handle: &AppHandle, subsystem: Subsystem) -> bool {
+        let previous = self.states().insert(subsystem, SubsystemState::Loading);
+        if previous == Some(SubsystemState::Loading) {
+            return false;
+        }
+        emit(app_handle, subsystem, SubsystemState::Loading);
+        true
+    }
+
+    fn list(&self) -> Vec<SubsystemStatus> {
+        let states = self.states();
+        Subsystem::ALL
+            .iter()
+            .map(|&subsystem| SubsystemStatus {
+                subsystem,
+                state: states
+                    .get(&subsystem)
+                    .cloned()
+                    .unwrap_or(SubsystemState::Loading),
+            })
+            .collect()
+    }
+}
+
+fn emit(app_handle: &AppHandle, subsystem: Subsystem, state: SubsystemState) {
+    match &state {
+        SubsystemState::Failed { reason } => {
+            error!("{:?} failed to start: {}", subsystem, reason)
+        }
+        _ => info!("{:?} is {:?}", subsystem, state),
+    }
+    if let Err(e) = app_handle.emit("subsystem_status", SubsystemStatus { subsystem, state }) {
+        error!("Failed to emit subsystem_status event: {:?}", e);
+    }
+}
+
+/// Starts `subsystem` in the background unless it's already loading.
+pub fn start(app_handle: AppHandle, subsystem: Subsystem) -> bool {
+    let status = app_handle.state::<StartupStatus>();
+    if !status.begin(&app_handle, subsystem) {
+        return false;
+    }
+    tauri::async_runtime::spawn(async move {
+        let result = match subsystem {
+            Subsystem::Tts => ckokoros2::init_tts(&app_handle).await,
+            Subsystem::Llm => collama::check_model(&app_handle).await,
+        };
+        let ready = result.is_ok();
+        let state = match result {
+            Ok(()) => SubsystemState::Ready,
+            Err(reason) => SubsystemState::Failed { reason },
+        };
+        app_handle
+            .state::<StartupStatus>()
+            .set(&app_handle, subsystem, state);
+        if ready {
+            cwarmup::start(app_handle, subsystem);
+        }
+    });
+    true
+}
+
+/// Tauri command listing the state of every subsystem, for a frontend that
+/// missed the events sent before it was listening.
+#[tauri::command]
+pub fn get_subsystem_status(status: State<'_, StartupStatus>) -> Vec<SubsystemStatus> {
+    status.list()
+}
+
+/// Tauri command starting a subsystem again, e.g. once the Ollama server is
+/// running or the network is back.
+#[tauri::command]
+pub fn retry_subsystem(app_handle: AppHandle, subsystem: Subsystem) -> Result<(), String> {
+    if start(app_handle, subsystem) {
+        Ok(())
+    } else {
+        Err(format!("{:?} is already loading", subsystem))
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use kokoros::tts::warmup::WarmupTimings;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tracing::{error, info};
+
+use crate::cstatus::Subsystem;
+use crate::{ckokoros2, collama, AppState};
+
+/// Which subsystems are warmed up once they're ready, from `warmup.json` in
+/// the app data directory. Both are by default.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WarmupOptions {
+    /// Preload the phonemizer and run a dummy synthesis on every instance
+    pub tts: bool,
+    /// Have Ollama load the chat model
+    pub llm: bool,
+}
+
+impl Default for WarmupOptions {
+    fn default() -> Self {
+        Self {
+            tts: true,
+            llm: true,
+        }
+    }
+}
+
+/// How long the last warm-up of each subsystem took, `None` until one finished.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct WarmupMetrics {
+    pub tts: Option<WarmupTimings>,
+    pub llm_secs: Option<f64>,
+}
+
+/// Warm-up metrics, kept in Tauri's managed state.
+#[derive(Default)]
+pub struct Warmup {
+    metrics: Mutex<WarmupMetrics>,
+}
+
+impl Warmup {
+    fn metrics(&self) -> MutexGuard<'_, WarmupMetrics> {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn options(app_handle: &AppHandle) -> WarmupOptions {
+    match app_handle.path().app_data_dir() {
+        Ok(app_data_dir) => {
+            ckokoros2::load_settings(&app_data_dir, "warmup.json", WarmupOptions::default())
+        }
+        Err(_) => WarmupOptions::default(),
+    }
+}
+
+/// Warms up a subsystem that just became ready, in the background so requests
+/// are served meanwhile. Failures are logged; the subsystem works either way.
+pub fn start(app_handle: AppHandle, subsystem: Subsystem) {
+    let options = options(&app_handle);
+    let enabled = match subsystem {
+        Subsystem::Tts => options.tts,
+        Subsystem::Llm => options.llm,
+    };
+    if !enabled {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let result = match subsystem {
+            Subsystem::Tts => warm_up_tts(&app_handle).await,
+            Subsystem::Llm => warm_up_llm(&app_handle).await,
+        };
+        if let Err(e) = result {
+            error!("Warming up {:?} failed: {}", subsystem, e);
+        }
+    });
+}
+
+async fn warm_up_tts(app_handle: &AppHandle) -> Result<(), String> {
+    let tts = app_handle
+        .state::<AppState>()
+        .tts_instance
+        .lock()
+        .await
+        .clone()
+        .ok_or("TTS instance not initialized yet")?;
+    let timings =
+        tauri::async_runtime::spawn_blocking(move || tts.warm_up().map_err(|e| e.to_string()))
+            .await
+            .map_err(|e| e.to_string())??;
+    info!(
+        "Warmed up TTS in {:.2}s (phonemizer {:.2}s, instances {:?}s)",
+        timings.total_secs, timings.phonemizer_secs, timings.instance_secs
+    );
+    app_handle.state::<Warmup>().metrics().tts = Some(timings);
+    Ok(())
+}
+
+async fn warm_up_llm(app_handle: &AppHandle) -> Result<(), String> {
+    let start = Instant::now();
+    collama::preload_model(app_handle).await?;
+    let secs = start.elapsed().as_secs_f64();
+    info!("Warmed up the chat model in {:.2}s", secs);
+    app_handle.state::<Warmup>().metrics().llm_secs = Some(secs);
+    Ok(())
+}
+
+/// Tauri command with the timings of the last warm-ups.
+#[tauri::command]
+pub fn get_warmup_metrics(warmup: State<'_, Warmup>) -> WarmupMetrics {
+    warmup.metrics().clone()
+}
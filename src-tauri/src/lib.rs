@@ -3,12 +3,15 @@ use ollama_rs::generation::chat::ChatMessage;
 use ollama_rs::models::ModelOptions;
 use ollama_rs::Ollama;
 use std::sync::Arc;
-use tauri::path::BaseDirectory::Resource;
 use tauri::{Manager, PhysicalPosition};
 use tokio::sync::Mutex;
+use tracing::error;
 mod ckokoros2;
+mod ckokorostream;
 mod cmouse;
 mod collama;
+mod cstatus;
+mod cwarmup;
 
 struct AppState {
     pub ollama: Mutex<Ollama>,
@@ -17,6 +20,21 @@ struct AppState {
     pub tts_instance: Arc<Mutex<Option<TTSKoko>>>,
 }
 
+/// Moves the main window to the left edge, centered vertically on its monitor.
+fn position_window(app: &tauri::App) -> Result<(), String> {
+    let win = app.get_webview_window("main").ok_or("No main window")?;
+    let win_dim = win.outer_size().map_err(|e| e.to_string())?;
+    let monitor_dim = win
+        .current_monitor()
+        .map_err(|e| e.to_string())?
+        .ok_or("No monitor for the main window")?;
+    win.set_position(PhysicalPosition::new(
+        16,
+        (monitor_dim.size().height as f32 / 2.0 - win_dim.height as f32 / 2.0).round() as i32,
+    ))
+    .map_err(|e| e.to_string())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tracing_subscriber::fmt::init();
@@ -29,42 +47,16 @@ pub fn run() {
                 tts_instance: Arc::new(Mutex::new(None)),
             });
 
-            let win = app.get_webview_window("main").unwrap();
-            let win_dim = win.outer_size().unwrap();
-            let monitor_dim = win
-                .current_monitor()
-                .unwrap()
-                .expect("Error getting monitor x");
-            let _ = win.set_position(PhysicalPosition::new(
-                16,
-                (monitor_dim.size().height as f32 / 2.0 - win_dim.height as f32 / 2.0).round()
-                    as i32,
-            ));
-
-            let app_handle = app.handle();
-
-            let _ = tokio::runtime::Runtime::new().unwrap().block_on(async {
-                let model_path_buf = app_handle
-                    .path()
-                    .resolve("resources/kokoro-v1.0.onnx", Resource)
-                    .expect("Failed to resolve Kokoro model path");
-                let voices_path_buf = app_handle
-                    .path()
-                    .resolve("resources/voices-v1.0.bin", Resource)
-                    .expect("Failed to resolve Kokoro voice path");
+            app.manage(cstatus::StartupStatus::default());
+            app.manage(cwarmup::Warmup::default());
 
-                let app_state = app_handle.state::<AppState>();
-
-                ckokoros2::setup_tauri_commands(
-                    model_path_buf.to_str().unwrap(),
-                    voices_path_buf.to_str().unwrap(),
-                    app_state.tts_instance.clone(),
-                )
-                .await
-                .expect("Failed to set up Tauri commands and state");
-
-                Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
-            });
+            // The window comes up right away and shows what's still loading
+            // or failed, so a missing model or server doesn't keep it closed
+            if let Err(e) = position_window(app) {
+                error!("Failed to position the window: {}", e);
+            }
+            cstatus::start(app.handle().clone(), cstatus::Subsystem::Tts);
+            cstatus::start(app.handle().clone(), cstatus::Subsystem::Llm);
 
             Ok(())
         })
@@ -73,6 +65,23 @@ pub fn run() {
             cmouse::check_cursor_region,
             collama::gen_res,
             ckokoros2::generate_speech,
+            ckokorostream::start_speech_stream,
+            ckokoros2::list_voices,
+            ckokoros2::import_voice,
+            ckokoros2::remove_voice,
+            ckokoros2::get_style_axes,
+            ckokoros2::preview_voice_design,
+            ckokoros2::save_voice_design,
+            ckokoros2::get_phonemizer_metrics,
+            ckokoros2::set_g2p_backend,
+            ckokoros2::set_language_switching,
+            ckokoros2::inspect_speech,
+            ckokoros2::list_model_variants,
+            ckokoros2::install_model_variant,
+            ckokoros2::select_model_variant,
+            cstatus::get_subsystem_status,
+            cstatus::retry_subsystem,
+            cwarmup::get_warmup_metrics,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");